@@ -0,0 +1,114 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use dynamic_2core::{
+    dynamic_2core::{AgData, D2CSolver, Dynamic2CoreSolver},
+    euler_tour_tree::ETT,
+    link_cut_tree::{LinkCutTree, LCT},
+    lists::{splay::Splays, treap::Treaps},
+};
+use rand::{Rng, SeedableRng};
+
+type Solver = D2CSolver<ETT<Treaps<AgData>, AgData>, LCT<Splays>>;
+
+fn random_edges(n: usize, m: usize) -> Vec<(usize, usize)> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(628);
+    (0..m)
+        .map(|_| (rng.gen_range(0..n), rng.gen_range(0..n)))
+        .collect()
+}
+
+fn bulk_insert(c: &mut Criterion) {
+    let mut g = c.benchmark_group("Bulk insertion");
+    for (n, m) in [(1000, 2000), (10000, 20000)] {
+        let edges = random_edges(n, m);
+        let input_str = format!("N {n} M {m}");
+        g.bench_with_input(BenchmarkId::new("new", &input_str), &edges, |b, edges| {
+            b.iter(|| {
+                let mut t = Solver::new(n);
+                edges.iter().for_each(|&(u, v)| _ = t.add_edge(u, v));
+                black_box(t)
+            })
+        });
+        g.bench_with_input(
+            BenchmarkId::new("with_capacity", &input_str),
+            &edges,
+            |b, edges| {
+                b.iter(|| {
+                    let mut t = Solver::with_capacity(n, edges.len());
+                    edges.iter().for_each(|&(u, v)| _ = t.add_edge(u, v));
+                    black_box(t)
+                })
+            },
+        );
+    }
+    g.finish();
+}
+
+fn low_degree_queries(c: &mut Criterion) {
+    let mut g = c.benchmark_group("2-core queries on low degree vertices");
+    for n in [1000, 10000] {
+        // A cycle on the first tenth of the vertices, with the rest hanging as leaves
+        let k = n / 10;
+        let mut t = Solver::new(n);
+        t.add_cycle(&(0..k).collect::<Vec<_>>());
+        for u in k..n {
+            t.add_edge(u % k, u);
+        }
+        g.bench_with_input(BenchmarkId::new("is_in_2core", n), &n, |b, &n| {
+            b.iter(|| (0..n).filter(|&u| t.is_in_2core(u)).count())
+        });
+    }
+    g.finish();
+}
+
+fn lct_heavy(c: &mut Criterion) {
+    let mut g = c.benchmark_group("2-core queries and removals on random graphs");
+    g.sample_size(20);
+    let (n, m) = (5000, 7500);
+    let edges = random_edges(n, m);
+    fn run<LC: LinkCutTree>(n: usize, edges: &[(usize, usize)]) -> usize {
+        let mut t =
+            D2CSolver::<ETT<Treaps<AgData>, AgData>, LC>::from_edges(n, edges.iter().copied());
+        let mut in_2core = (0..n).filter(|&u| t.is_in_2core(u)).count();
+        for &(u, v) in edges.iter().step_by(3) {
+            t.remove_edge(u, v);
+            in_2core += t.is_in_2core(u) as usize;
+        }
+        in_2core
+    }
+    g.bench_function("LCT<Splays>", |b| b.iter(|| run::<LCT<Splays>>(n, &edges)));
+    g.bench_function("LCT<Treaps>", |b| b.iter(|| run::<LCT<Treaps>>(n, &edges)));
+    g.finish();
+}
+
+fn dense_removals(c: &mut Criterion) {
+    let mut g = c.benchmark_group("Removals on dense graphs");
+    g.sample_size(10);
+    // Most edges are extra edges, so this stresses the extra edge bookkeeping.
+    let (n, m) = (300, 15000);
+    let edges = random_edges(n, m);
+    g.bench_function(
+        BenchmarkId::new("remove all", format!("N {n} M {m}")),
+        |b| {
+            b.iter_batched(
+                || Solver::from_edges(n, edges.iter().copied()),
+                |mut t| {
+                    for &(u, v) in &edges {
+                        t.remove_edge(u, v);
+                    }
+                    t
+                },
+                BatchSize::LargeInput,
+            )
+        },
+    );
+    g.finish();
+}
+
+criterion_group!(
+    benches,
+    bulk_insert,
+    low_degree_queries,
+    lct_heavy,
+    dense_removals
+);
+criterion_main!(benches);
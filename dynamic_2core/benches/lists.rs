@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Bencher, BenchmarkId, Criterion};
-use dynamic_2core::lists::{splay::Splays, treap::Treaps, AggregatedData, Lists};
+use dynamic_2core::lists::{splay::Splays, treap::Treaps, AggregatedData, Lists, Monoid};
 use flexi_logger::Logger;
 use rand::{seq::SliceRandom, Rng, SeedableRng};
 use std::{
@@ -19,12 +19,15 @@ enum Operation {
 #[derive(Clone, Copy, Debug)]
 enum OperationDistribution {
     Default,
+    /// Mostly queries, so lists rarely change between them.
+    QueryHeavy,
 }
 
 impl OperationDistribution {
     fn get_op(&self, rng: &mut impl Rng) -> Operation {
         let weights = match self {
             Self::Default => [3, 3, 1, 2, 3],
+            Self::QueryHeavy => [1, 1, 1, 10, 10],
         };
         use Operation::*;
         *[Concat, Split, Reverse, SameList, RangeAgg]
@@ -141,7 +144,10 @@ fn each_operation(c: &mut Criterion) {
     g.throughput(criterion::Throughput::Elements(1));
     g.measurement_time(Duration::from_secs(30));
     g.warm_up_time(Duration::from_secs(10));
-    for dist in [OperationDistribution::Default] {
+    for dist in [
+        OperationDistribution::Default,
+        OperationDistribution::QueryHeavy,
+    ] {
         let seed = rng.gen();
         log::debug!("Using seed {seed}");
         let input_str = format!("{dist:?}").to_lowercase();
@@ -155,7 +161,111 @@ fn each_operation(c: &mut Criterion) {
     g.finish();
 }
 
-criterion_group!(benches, same_operations, each_operation);
+fn map_all_impl<L: Lists<AggSum>>(b: &mut Bencher, n: usize, naive: bool) {
+    let mut l = L::from_iter((0..n).map(|i| i as i32));
+    b.iter(|| {
+        if naive {
+            for k in 0..n {
+                let u = l.find_kth(0, k);
+                l.mutate_data(u, |d| *d ^= 1);
+            }
+        } else {
+            l.map_in_place(0, |d| *d ^= 1);
+        }
+        black_box(l.total_agg(0));
+    });
+}
+
+fn map_all(c: &mut Criterion) {
+    let _ = &*LOGGER;
+    let mut g = c.benchmark_group("Map all elements");
+    for n in [1000usize, 100000] {
+        g.throughput(criterion::Throughput::Elements(n as u64));
+        for (name, naive) in [("map_in_place", false), ("mutate_data loop", true)] {
+            let input_str = format!("N {n} {name}");
+            g.bench_with_input(BenchmarkId::new("splay", &input_str), &n, |b, &n| {
+                map_all_impl::<Splays<AggSum>>(b, n, naive)
+            });
+            g.bench_with_input(BenchmarkId::new("treap", &input_str), &n, |b, &n| {
+                map_all_impl::<Treaps<AggSum>>(b, n, naive)
+            });
+        }
+    }
+    g.finish();
+}
+
+fn build_list(c: &mut Criterion) {
+    let _ = &*LOGGER;
+    let mut g = c.benchmark_group("Build a single list");
+    for n in [1000usize, 100000] {
+        g.throughput(criterion::Throughput::Elements(n as u64));
+        g.bench_with_input(BenchmarkId::new("build_balanced", n), &n, |b, &n| {
+            b.iter(|| {
+                let mut l = Treaps::<AggSum>::new(n);
+                black_box(l.build_balanced((0..n).map(|i| i as i32)));
+                l
+            })
+        });
+        g.bench_with_input(BenchmarkId::new("concat_all singletons", n), &n, |b, &n| {
+            b.iter(|| {
+                let mut l = Treaps::<AggSum>::new(n);
+                let singletons: Vec<_> = (0..n).map(|i| l.create(i as i32)).collect();
+                black_box(l.concat_all(singletons));
+                l
+            })
+        });
+    }
+    g.finish();
+}
+
+fn create_nodes_with<L: Lists<AggSum>>(
+    g: &mut criterion::BenchmarkGroup<'_, criterion::measurement::WallTime>,
+    name: &str,
+    n: usize,
+) {
+    g.bench_with_input(
+        BenchmarkId::new(format!("{name} create"), n),
+        &n,
+        |b, &n| {
+            b.iter(|| {
+                let mut l = L::new(0);
+                (0..n).for_each(|i| _ = black_box(l.create(i as i32)));
+                l
+            })
+        },
+    );
+    g.bench_with_input(
+        BenchmarkId::new(format!("{name} create_many"), n),
+        &n,
+        |b, &n| {
+            b.iter(|| {
+                let mut l = L::new(0);
+                black_box(l.create_many((0..n).map(|i| i as i32)));
+                l
+            })
+        },
+    );
+}
+
+fn create_nodes(c: &mut Criterion) {
+    let _ = &*LOGGER;
+    let mut g = c.benchmark_group("Create nodes");
+    for n in [1000usize, 1000000] {
+        g.throughput(criterion::Throughput::Elements(n as u64));
+        create_nodes_with::<Treaps<AggSum>>(&mut g, "treap", n);
+        create_nodes_with::<Splays<AggSum>>(&mut g, "splay", n);
+    }
+    g.finish();
+}
+
+criterion_group!(
+    benches,
+    same_operations,
+    each_operation,
+    map_all,
+    build_list,
+    create_nodes
+);
 criterion_main!(benches);
 
 pub static LOGGER: LazyLock<Mutex<flexi_logger::LoggerHandle>> = LazyLock::new(|| {
@@ -185,7 +295,7 @@ pub static LOGGER: LazyLock<Mutex<flexi_logger::LoggerHandle>> = LazyLock::new(|
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct AggSum(pub i32);
 
-impl AggregatedData for AggSum {
+impl Monoid for AggSum {
     type Data = i32;
 
     fn from(data: &Self::Data) -> Self {
@@ -195,7 +305,9 @@ impl AggregatedData for AggSum {
     fn merge(self, right: Self) -> Self {
         Self(self.0 + right.0)
     }
+}
 
+impl AggregatedData for AggSum {
     fn reverse(self) -> Self {
         self
     }
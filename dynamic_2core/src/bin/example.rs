@@ -20,9 +20,7 @@ fn is_2_core(t: &mut FastDynamic2CoreSolver, u: usize) {
 
 fn main() {
     let mut t = FastDynamic2CoreSolver::new(10);
-    for u in 0..9 {
-        t.add_edge(u, u + 1);
-    }
+    t.add_path(&(0..10).collect::<Vec<_>>());
     println!("Created a path of length 10 (vertices 0 to 9)");
     is_2_core(&mut t, 2);
     add_edge(&mut t, 0, 3);
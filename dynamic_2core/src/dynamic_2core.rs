@@ -1,17 +1,35 @@
 //! Implementation of the dynamic 2-core solver, using ETTs and LCTs.
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::mpsc::Sender,
+};
 
 use crate::{
-    euler_tour_tree::{EdgeRef, EulerTourTree},
+    euler_tour_tree::{EdgeRef, EulerTourTree, MergePolicy},
     link_cut_tree::LinkCutTree,
-    lists::{AggregatedData, Idx, SearchDirection},
+    lists::{AggregatedData, Idx, Monoid, SearchDirection},
+    metrics::{map_bytes, Counters},
 };
 
 /// Trait defining the operations of the dynamic 2-core solver.
+/// It is object safe, so implementations can be chosen at runtime with `Box<dyn Dynamic2CoreSolver>`.
 pub trait Dynamic2CoreSolver {
     /// New instance for an empty graph on n nodes
-    fn new(n: usize) -> Self;
+    fn new(n: usize) -> Self
+    where
+        Self: Sized;
+    /// New instance for the graph on n nodes with the given edges. Repeated edges and self loops are ignored.
+    fn from_edges(n: usize, edges: impl IntoIterator<Item = (usize, usize)>) -> Self
+    where
+        Self: Sized,
+    {
+        let mut solver = Self::new(n);
+        for (u, v) in edges {
+            solver.add_edge(u, v);
+        }
+        solver
+    }
     /// Add an edge between u and v. Returns whether is was added.
     fn add_edge(&mut self, u: usize, v: usize) -> bool;
     /// Remove an edge between u and v. Returns whether it was removed.
@@ -22,11 +40,42 @@ pub trait Dynamic2CoreSolver {
     fn is_in_2core(&mut self, u: usize) -> bool;
     /// Check if u is in the 1-core, that is, if it is contained in a subgraph with minimum degree 1.
     fn is_in_1core(&mut self, u: usize) -> bool;
+    /// Add edges between consecutive vertices. Returns how many edges were added.
+    fn add_path(&mut self, vertices: &[usize]) -> usize {
+        vertices
+            .windows(2)
+            .filter(|w| self.add_edge(w[0], w[1]))
+            .count()
+    }
+    /// Add edges between consecutive vertices, and from the last to the first. Returns how many edges were added.
+    fn add_cycle(&mut self, vertices: &[usize]) -> usize {
+        let closing = match vertices {
+            [first, .., last] => self.add_edge(*last, *first) as usize,
+            _ => 0,
+        };
+        self.add_path(vertices) + closing
+    }
+    /// Number of independent cycles of the graph, that is, its cyclomatic number: edges - nodes + components.
+    fn num_independent_cycles(&self) -> usize;
+    /// The connected components of the graph, as lists of vertices. Each component is in increasing order, and the
+    /// components are ordered by their smallest vertex.
+    fn connected_components(&mut self) -> Vec<Vec<usize>>;
+    /// Whether the graph has no cycles.
+    fn is_forest(&self) -> bool {
+        self.num_independent_cycles() == 0
+    }
+    /// Optional function that checks the internal invariants of the solver, returning a description of the first
+    /// one that is violated. Used for testing and debugging.
+    fn validate(&mut self) -> Result<(), String> {
+        Ok(())
+    }
 }
 
 type Level = usize;
 type Node = usize;
 type EdgeId = usize;
+/// End of a list of extra edges.
+const NO_EDGE: EdgeId = usize::MAX;
 /// Data used in the Euler Tour Tree
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord)]
 pub enum Data {
@@ -38,8 +87,9 @@ pub enum Data {
     },
     Edge {
         e_id: EdgeId,
-        // Level of this tree edge
-        level: Level,
+        /// Whether this ETT is the one of the edge's level, which is the highest it is on.
+        /// The level itself is only kept in [EdgeInfo], since the aggregate only needs this.
+        top_level: bool,
     },
 }
 
@@ -70,42 +120,32 @@ impl Data {
             _ => panic!("Expected Node"),
         }
     }
-    fn unwrap_edge(&self) -> (&EdgeId, &Level) {
+    fn unwrap_edge(&self) -> (&EdgeId, &bool) {
         match self {
-            Data::Edge { e_id, level } => (e_id, level),
+            Data::Edge { e_id, top_level } => (e_id, top_level),
             _ => panic!("Expected Edge"),
         }
     }
-    fn unwrap_edge_mut(&mut self) -> (&mut EdgeId, &mut Level) {
+    fn unwrap_edge_mut(&mut self) -> (&mut EdgeId, &mut bool) {
         match self {
-            Data::Edge { e_id, level } => (e_id, level),
+            Data::Edge { e_id, top_level } => (e_id, top_level),
             _ => panic!("Expected Edge"),
         }
     }
 }
 
 /// Aggregated [Data] for the Euler Tour Tree
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct AgData {
-    /// Minimum level of edge in range
-    min_edge_level: Level,
+    /// Total tree edges whose level is the level of this ETT in this range
+    total_top_level_edges: usize,
     /// Total extra edges in this level in this range
     total_extra_edges: usize,
     /// Total extra edges on any level. This is only used in level 0.
     total_any_extra_edges: usize,
 }
 
-impl Default for AgData {
-    fn default() -> Self {
-        Self {
-            min_edge_level: usize::MAX,
-            total_extra_edges: 0,
-            total_any_extra_edges: 0,
-        }
-    }
-}
-
-impl AggregatedData for AgData {
+impl Monoid for AgData {
     type Data = Data;
     fn from(data: &Self::Data) -> Self {
         match data {
@@ -115,10 +155,10 @@ impl AggregatedData for AgData {
             } => Self {
                 total_extra_edges: *extra_edges,
                 total_any_extra_edges: *any_extra_edges,
-                min_edge_level: usize::MAX,
+                total_top_level_edges: 0,
             },
-            Data::Edge { level, e_id: _ } => Self {
-                min_edge_level: *level,
+            Data::Edge { top_level, e_id: _ } => Self {
+                total_top_level_edges: *top_level as usize,
                 total_extra_edges: 0,
                 total_any_extra_edges: 0,
             },
@@ -126,11 +166,14 @@ impl AggregatedData for AgData {
     }
     fn merge(self, right: Self) -> Self {
         Self {
-            min_edge_level: self.min_edge_level.min(right.min_edge_level),
+            total_top_level_edges: self.total_top_level_edges + right.total_top_level_edges,
             total_extra_edges: self.total_extra_edges + right.total_extra_edges,
             total_any_extra_edges: self.total_any_extra_edges + right.total_any_extra_edges,
         }
     }
+}
+
+impl AggregatedData for AgData {
     fn reverse(self) -> Self {
         self
     }
@@ -145,6 +188,8 @@ struct EdgeInfo {
     level: Level,
     /// One reference for each level. If None, it is an extra edge.
     levels: Option<Vec<EdgeRef>>,
+    /// If it is an extra edge, its [prev, next] in the lists of extra edges of e.0 and of e.1 on its level.
+    extra_links: [[EdgeId; 2]; 2],
 }
 
 impl EdgeInfo {
@@ -153,23 +198,104 @@ impl EdgeInfo {
     }
 }
 
+/// Discovery time, lowlink and DFS parent of the nodes reached by [D2CSolver::lowlink], usize::MAX if not reached.
+/// Dense for a DFS over the whole graph, and sparse for a single component, so its cost doesn't depend on n.
+enum DfsLabels {
+    Dense(Vec<[usize; 3]>),
+    Sparse(BTreeMap<Node, [usize; 3]>),
+}
+
+impl DfsLabels {
+    const UNSEEN: [usize; 3] = [usize::MAX; 3];
+    fn get(&self, u: Node) -> [usize; 3] {
+        match self {
+            DfsLabels::Dense(v) => v[u],
+            DfsLabels::Sparse(m) => m.get(&u).copied().unwrap_or(Self::UNSEEN),
+        }
+    }
+    fn get_mut(&mut self, u: Node) -> &mut [usize; 3] {
+        match self {
+            DfsLabels::Dense(v) => &mut v[u],
+            DfsLabels::Sparse(m) => m.entry(u).or_insert(Self::UNSEEN),
+        }
+    }
+    /// Reached nodes, in increasing order.
+    fn reached(&self) -> Vec<Node> {
+        match self {
+            DfsLabels::Dense(v) => (0..v.len()).filter(|&u| v[u][0] != usize::MAX).collect(),
+            DfsLabels::Sparse(m) => m.keys().copied().collect(),
+        }
+    }
+}
+
+/// Change to the graph, see [D2CSolver::set_event_sender]. Edges are given as (u, v) with u < v.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphEvent {
+    EdgeAdded(usize, usize),
+    EdgeRemoved(usize, usize),
+    /// Adding the edge joined two components.
+    ComponentMerged(usize, usize),
+    /// Removing the edge split its component in two.
+    ComponentSplit(usize, usize),
+    /// The vertex entered or left the 2-core.
+    CoreChanged {
+        vertex: usize,
+        in_2core: bool,
+    },
+}
+
+/// Change to a component watched with [D2CSolver::watch_component]. Edges are given as (u, v) with u < v.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentEvent {
+    EdgeAdded(usize, usize),
+    EdgeRemoved(usize, usize),
+    /// Adding the edge joined another component to it.
+    Merged(usize, usize),
+    /// Removing the edge split it in two. The watch stays on the side of the vertex it was created with.
+    Split(usize, usize),
+}
+
+/// Callback of [D2CSolver::watch_component].
+pub type ComponentWatcher = Box<dyn FnMut(ComponentEvent) + Send>;
+
 /// Data structure for solving the dynamic 2-core problem using Euler Tour Trees and Link Cut Trees.
 pub struct D2CSolver<ETT, LC>
 where
     ETT: EulerTourTree<AgData>,
     LC: LinkCutTree,
 {
-    /// Number of nodes of the graph, never changes.
-    _n: usize,
+    /// Number of nodes of the graph, only grows on [D2CSolver::merge_from] and [D2CSolver::add_vertex].
+    n: usize,
+    /// Vertices removed by [D2CSolver::remove_vertex], which are reused by [D2CSolver::add_vertex].
+    free: BTreeSet<Node>,
+    /// Incremented on every change to the graph, used to detect stale [FrozenSolver]s.
+    generation: u64,
     /// ETT for each level in the HDT algorithm.
     ett: Vec<ETT>,
     edge_info: Vec<EdgeInfo>,
     /// (u, v) -> position on [edge_info] array
     e_to_id: BTreeMap<(Node, Node), usize>,
-    /// Only exists for extra edges
-    u_level_to_extras: BTreeMap<(Node, Level), BTreeSet<EdgeId>>,
+    /// Neighbors of each node
+    adj: Vec<BTreeSet<Node>>,
+    /// First extra edge of each node on each level, of a list linked through [EdgeInfo::extra_links].
+    /// Each node only has entries up to the highest level it has extra edges on.
+    extra_heads: Vec<Vec<EdgeId>>,
     /// Link cut tree of the spanning tree of level 0
     lc_0: LC,
+    /// Which root survives when two trees are merged in the ETTs.
+    merge_policy: MergePolicy,
+    /// Which side of a removed tree edge has its edges promoted.
+    promote_side: PromoteSide,
+    /// Number of times an edge had its level increased.
+    promotions: u64,
+    /// Number of extra edges, each of which closes an independent cycle with the spanning forest.
+    num_extra: usize,
+    /// Where changes are sent, if anyone is listening.
+    events: Option<Sender<GraphEvent>>,
+    /// Callbacks from [D2CSolver::watch_component], with a vertex of the component each one watches.
+    watchers: Vec<(Node, ComponentWatcher)>,
+    /// Whether a [D2CSolver::batch] is running, so 2-core changes are only found at its end.
+    in_batch: bool,
 }
 
 impl<ETT, LC> std::fmt::Debug for D2CSolver<ETT, LC>
@@ -234,11 +360,17 @@ where
 
     fn find_level_i_tree_edge(&mut self, i: Level, u: Idx) -> Option<EdgeId> {
         let found = self.ett[i].find_element(u, |d| {
-            if matches!(d.current_data, Data::Edge { level, .. } if *level == i) {
+            if matches!(
+                d.current_data,
+                Data::Edge {
+                    top_level: true,
+                    ..
+                }
+            ) {
                 SearchDirection::Found
-            } else if d.left_agg.min_edge_level <= i {
+            } else if d.left_agg.total_top_level_edges > 0 {
                 SearchDirection::Left
-            } else if d.right_agg.min_edge_level <= i {
+            } else if d.right_agg.total_top_level_edges > 0 {
                 SearchDirection::Right
             } else {
                 SearchDirection::NotFound
@@ -263,9 +395,7 @@ where
             }
         });
         if found != ETT::EMPTY {
-            let &id = self.u_level_to_extras[&(found, i)]
-                .first()
-                .expect("missing extra edge");
+            let id = self.extras_of(found, i).next().expect("missing extra edge");
             Some(id)
         } else {
             None
@@ -273,30 +403,11 @@ where
     }
     /// First and last nodes on level 0 with any_extra_edge > 0
     fn first_and_last_nodes_with_extra_edges(&mut self, u: Node) -> Option<(Node, Node)> {
-        let first = self.ett[0].find_element(u, |d| {
-            if d.left_agg.total_any_extra_edges > 0 {
-                SearchDirection::Left
-            } else if matches!(d.current_data, Data::Node { any_extra_edges, .. } if *any_extra_edges > 0)
-            {
-                SearchDirection::Found
-            } else if d.right_agg.total_any_extra_edges > 0 {
-                SearchDirection::Right
-            } else {
-                SearchDirection::NotFound
-            }
-        });
-        let last = self.ett[0].find_element(u, |d| {
-            if d.right_agg.total_any_extra_edges > 0 {
-                SearchDirection::Right
-            } else if matches!(d.current_data, Data::Node { any_extra_edges, .. } if *any_extra_edges > 0)
-            {
-                SearchDirection::Found
-            } else if d.left_agg.total_any_extra_edges > 0 {
-                SearchDirection::Left
-            } else {
-                SearchDirection::NotFound
-            }
-        });
+        let agg_can = |ag: &AgData| ag.total_any_extra_edges > 0;
+        let node_is =
+            |d: &Data| matches!(d, Data::Node { any_extra_edges, .. } if *any_extra_edges > 0);
+        let first = self.ett[0].find_first_node(u, agg_can, node_is);
+        let last = self.ett[0].find_last_node(u, agg_can, node_is);
         if first != last {
             Some((first, last))
         } else {
@@ -306,17 +417,64 @@ where
     fn mutate_node(&mut self, u: Node, lvl: Level, f: impl FnOnce(NodeM<'_>)) {
         self.ett[lvl].mutate_data(u, |d| f(d.unwrap_node_mut()))
     }
+    /// Which of the endpoints of the edge w is, which is also the index of its links in [EdgeInfo::extra_links].
+    fn extra_side(&self, e_id: EdgeId, w: Node) -> usize {
+        (self.edge_info[e_id].e.0 != w) as usize
+    }
+    /// Adds the extra edge to the front of the list of w on the level.
+    fn link_extra(&mut self, e_id: EdgeId, w: Node, lvl: Level) {
+        let heads = &mut self.extra_heads[w];
+        if heads.len() <= lvl {
+            heads.resize(lvl + 1, NO_EDGE);
+        }
+        let next = std::mem::replace(&mut heads[lvl], e_id);
+        let side = self.extra_side(e_id, w);
+        self.edge_info[e_id].extra_links[side] = [NO_EDGE, next];
+        if next != NO_EDGE {
+            let next_side = self.extra_side(next, w);
+            self.edge_info[next].extra_links[next_side][0] = e_id;
+        }
+    }
+    /// Removes the extra edge from the list of w on the level.
+    fn unlink_extra(&mut self, e_id: EdgeId, w: Node, lvl: Level) {
+        let side = self.extra_side(e_id, w);
+        let [prev, next] =
+            std::mem::replace(&mut self.edge_info[e_id].extra_links[side], [NO_EDGE; 2]);
+        if prev == NO_EDGE {
+            debug_assert_eq!(self.extra_heads[w][lvl], e_id);
+            self.extra_heads[w][lvl] = next;
+        } else {
+            let prev_side = self.extra_side(prev, w);
+            self.edge_info[prev].extra_links[prev_side][1] = next;
+        }
+        if next != NO_EDGE {
+            let next_side = self.extra_side(next, w);
+            self.edge_info[next].extra_links[next_side][0] = prev;
+        }
+        let heads = &mut self.extra_heads[w];
+        while heads.last() == Some(&NO_EDGE) {
+            heads.pop();
+        }
+    }
+    /// Extra edges of w on the level, most recently added first.
+    fn extras_of(&self, w: Node, lvl: Level) -> impl Iterator<Item = EdgeId> + '_ {
+        let mut e_id = self.extra_heads[w].get(lvl).copied().unwrap_or(NO_EDGE);
+        std::iter::from_fn(move || {
+            (e_id != NO_EDGE).then(|| {
+                let cur = e_id;
+                e_id = self.edge_info[cur].extra_links[self.extra_side(cur, w)][1];
+                cur
+            })
+        })
+    }
     // Does not affect the Data::Edge.levels field
     fn add_edge_id(&mut self, e_id: EdgeId) {
         let ((u, v), lvl) = self.edge(e_id);
         assert!(self.e_to_id.insert((u, v), e_id).is_none());
         if self.edge_info[e_id].is_extra() {
+            self.num_extra += 1;
             for w in [u, v] {
-                assert!(self
-                    .u_level_to_extras
-                    .entry((w, lvl))
-                    .or_default()
-                    .insert(e_id));
+                self.link_extra(e_id, w, lvl);
                 self.mutate_node(w, lvl, |n| *n.extra_edges += 1);
                 self.mutate_node(w, 0, |n| *n.any_extra_edges += 1);
             }
@@ -327,37 +485,53 @@ where
         let ((u, v), lvl) = self.edge(e_id);
         assert!(self.e_to_id.remove(&self.edge_info[e_id].e).is_some());
         if self.edge_info[e_id].is_extra() {
+            self.num_extra -= 1;
             for w in [u, v] {
-                assert!(self
-                    .u_level_to_extras
-                    .get_mut(&(w, lvl))
-                    .unwrap()
-                    .remove(&e_id));
+                self.unlink_extra(e_id, w, lvl);
                 self.mutate_node(w, lvl, |n| *n.extra_edges -= 1);
                 self.mutate_node(w, 0, |n| *n.any_extra_edges -= 1);
             }
         }
     }
+    /// The side of a cut tree edge, given by the roots of both sides on the level, whose edges are promoted.
+    fn choose_smaller(&mut self, lvl: Level, tu: Idx, tv: Idx) -> Idx {
+        let ett = &mut self.ett[lvl];
+        let smaller = ett.tree_size(tu) < ett.tree_size(tv);
+        match self.promote_side {
+            PromoteSide::Smaller if smaller => tu,
+            PromoteSide::Smaller => tv,
+            PromoteSide::Larger if smaller => tv,
+            PromoteSide::Larger => tu,
+            PromoteSide::Root => tu,
+        }
+    }
     fn add_level_to_edge(&mut self, e_id: EdgeId) {
         let ((u, v), lvl) = self.edge(e_id);
+        if lvl + 1 == self.ett.len() {
+            // Only reachable if the promoted side isn't the smaller one.
+            let node = Data::Node {
+                extra_edges: 0,
+                any_extra_edges: 0,
+            };
+            self.ett.push(ETT::new(vec![node; self.capacity()]));
+        }
+        self.promotions += 1;
         self.rem_edge_id(e_id);
         self.edge_info[e_id].level = lvl + 1;
         self.add_edge_id(e_id);
         if let Some(levels) = &mut self.edge_info[e_id].levels {
-            for (elvl, e, dir) in levels
-                .iter()
-                .enumerate()
-                .flat_map(|(elvl, e)| [(elvl, e, false), (elvl, e, true)])
-            {
-                self.ett[elvl].mutate_edata(*e, dir, |e| *e.unwrap_edge_mut().1 = lvl + 1);
+            // Only the copy on the old level stops being the top one
+            let old_top = levels[lvl];
+            for dir in [false, true] {
+                self.ett[lvl].mutate_edata(old_top, dir, |e| *e.unwrap_edge_mut().1 = false);
             }
-            let e = Data::Edge {
-                level: lvl + 1,
+            let e = |_| Data::Edge {
                 e_id,
+                top_level: true,
             };
             levels.push(
                 self.ett[lvl + 1]
-                    .connect(u, v, e.clone(), e)
+                    .connect_with(u, v, self.merge_policy, e)
                     .expect("shouldn't be connected at next level"),
             );
         } else {
@@ -370,15 +544,368 @@ where
     fn edge(&self, e_id: EdgeId) -> ((Node, Node), Level) {
         (self.edge_info[e_id].e, self.edge_info[e_id].level)
     }
-}
-
-/// Implementation of main dyn2core methods for the solver
-impl<ETT, LC> Dynamic2CoreSolver for D2CSolver<ETT, LC>
-where
-    ETT: EulerTourTree<AgData>,
-    LC: LinkCutTree,
-{
-    fn new(n: usize) -> Self {
+    /// Neighbors of u, in increasing order.
+    pub fn neighbors(&self, u: usize) -> impl Iterator<Item = usize> + '_ {
+        self.adj[u].iter().copied()
+    }
+    /// Number of edges incident to u.
+    pub fn degree(&self, u: usize) -> usize {
+        self.adj[u].len()
+    }
+    /// Number of vertices with each degree, that is, index d has how many vertices have degree d.
+    /// It ends at the maximum degree, so it's empty only if there are no vertices. Takes O(n).
+    pub fn degree_histogram(&self) -> Vec<usize> {
+        let mut histogram = vec![];
+        for u in 0..self.n {
+            let d = self.degree(u);
+            if histogram.len() <= d {
+                histogram.resize(d + 1, 0);
+            }
+            histogram[d] += 1;
+        }
+        histogram
+    }
+    /// Dense adjacency matrix of the graph, where [u][v] and [v][u] are true iff there is an edge between u and v.
+    /// This takes O(n^2) time and memory, so it is only meant for small graphs.
+    pub fn adjacency_matrix(&self) -> Vec<Vec<bool>> {
+        let mut matrix = vec![vec![false; self.n]; self.n];
+        for (u, v) in self.edges() {
+            matrix[u][v] = true;
+            matrix[v][u] = true;
+        }
+        matrix
+    }
+    /// Whether u and v are connected by a path of at most max_hops edges in the spanning forest kept by the solver.
+    /// Note this is NOT the distance in the graph: the shortest path may use non-tree edges and be shorter.
+    pub fn is_connected_within(&mut self, u: usize, v: usize, max_hops: usize) -> bool {
+        let Some(lca) = self.lc_0.lca(u, v) else {
+            return false;
+        };
+        let dist = self.lc_0.depth(u) + self.lc_0.depth(v) - 2 * self.lc_0.depth(lca);
+        dist <= max_hops
+    }
+    /// Vertices on the path from u to v in the spanning forest kept by the solver, including both, or None if they
+    /// aren't connected. This is NOT a shortest path in the graph, which may be shorter using non-tree edges.
+    pub fn tree_path(&mut self, u: usize, v: usize) -> Option<Vec<usize>> {
+        self.lc_0.path(u, v)
+    }
+    /// Tree edge of minimum weight on the path from u to v in the spanning forest kept by the solver, as (a, b, weight)
+    /// with a before b on the path. Ties are broken by the first on the path. None if they aren't connected or u == v.
+    /// The solver doesn't store weights, so they are given by weight(a, b). This walks the path, taking O(path length).
+    pub fn path_min_edge<W: Ord>(
+        &mut self,
+        u: usize,
+        v: usize,
+        weight: impl Fn(usize, usize) -> W,
+    ) -> Option<(usize, usize, W)> {
+        let path = self.tree_path(u, v)?;
+        path.windows(2)
+            .map(|e| (e[0], e[1], weight(e[0], e[1])))
+            .min_by(|a, b| a.2.cmp(&b.2))
+    }
+    /// Farthest node from u in the spanning forest kept by the solver, and its distance, found with a BFS.
+    fn farthest_in_tree(&self, u: Node) -> (Node, usize) {
+        let mut dist = BTreeMap::from([(u, 0)]);
+        let mut queue = std::collections::VecDeque::from([u]);
+        let mut farthest = (u, 0);
+        while let Some(w) = queue.pop_front() {
+            let d = dist[&w];
+            farthest = (w, d);
+            for &x in &self.adj[w] {
+                let is_tree = !self.edge_info[self.e_to_id[&(w.min(x), w.max(x))]].is_extra();
+                if is_tree && !dist.contains_key(&x) {
+                    dist.insert(x, d + 1);
+                    queue.push_back(x);
+                }
+            }
+        }
+        farthest
+    }
+    /// Diameter of the spanning tree of the component of u, that is, the length of its longest path.
+    /// This is NOT the diameter of the graph: distances are taken in the spanning forest kept by the solver, which
+    /// may be longer than in the graph, so it is only an upper bound. Takes linear time on the size of the component.
+    pub fn tree_diameter(&self, u: usize) -> usize {
+        // The farthest node from any node is an end of a longest path.
+        let (a, _) = self.farthest_in_tree(u);
+        self.farthest_in_tree(a).1
+    }
+    /// DFS over the components containing the given roots, filling in labels the discovery time, the earliest
+    /// discovery time reachable from the subtree using at most one back edge, and the DFS parent of each node.
+    /// Takes O(k + m) for components with k nodes and m edges, times O(lg k) if labels are sparse.
+    fn lowlink(&self, roots: impl IntoIterator<Item = Node>, mut labels: DfsLabels) -> DfsLabels {
+        let mut timer = 0;
+        for u in roots {
+            if labels.get(u)[0] != usize::MAX {
+                continue;
+            }
+            *labels.get_mut(u) = [timer, timer, usize::MAX];
+            timer += 1;
+            let mut stack = vec![(u, usize::MAX, self.adj[u].iter())];
+            while let Some((w, p, it)) = stack.last_mut() {
+                let (w, p) = (*w, *p);
+                match it.next() {
+                    Some(&x) if x == p => {}
+                    Some(&x) if labels.get(x)[0] == usize::MAX => {
+                        *labels.get_mut(x) = [timer, timer, w];
+                        timer += 1;
+                        stack.push((x, w, self.adj[x].iter()));
+                    }
+                    Some(&x) => {
+                        let tin_x = labels.get(x)[0];
+                        let low_w = &mut labels.get_mut(w)[1];
+                        *low_w = (*low_w).min(tin_x);
+                    }
+                    None => {
+                        stack.pop();
+                        if p != usize::MAX {
+                            let low_w = labels.get(w)[1];
+                            let low_p = &mut labels.get_mut(p)[1];
+                            *low_p = (*low_p).min(low_w);
+                        }
+                    }
+                }
+            }
+        }
+        labels
+    }
+    /// Bridges of the components containing the given roots, as (a, b) with a < b. Found with a DFS, see
+    /// [D2CSolver::lowlink].
+    fn bridges_from(
+        &self,
+        roots: impl IntoIterator<Item = Node>,
+        labels: DfsLabels,
+    ) -> BTreeSet<(Node, Node)> {
+        let labels = self.lowlink(roots, labels);
+        labels
+            .reached()
+            .into_iter()
+            .filter_map(|w| {
+                let [_, low, p] = labels.get(w);
+                (p != usize::MAX && low > labels.get(p)[0]).then_some((p.min(w), p.max(w)))
+            })
+            .collect()
+    }
+    /// All bridges of the graph, as (u, v) with u < v, in increasing order. These are the tree edges that no extra edge
+    /// covers. They are found with a DFS, in O(n + m).
+    pub fn bridges(&self) -> Vec<(usize, usize)> {
+        self.bridges_from(0..self.n, DfsLabels::Dense(vec![DfsLabels::UNSEEN; self.n]))
+            .into_iter()
+            .collect()
+    }
+    /// Whether removing the edge (u, v) would split its component, that is, whether it is a bridge, without removing it.
+    /// False if there is no such edge. Like the replacement search of [Dynamic2CoreSolver::remove_edge], this only
+    /// explores the smaller side of the cut, but as it promotes no edges it isn't amortized: both sides are explored
+    /// one edge at a time, so it takes O(k lg n), where k is the smaller of the total degrees of the two sides.
+    pub fn would_disconnect(&self, u: usize, v: usize) -> bool {
+        let e = (u.min(v), u.max(v));
+        let Some(&e_id) = self.e_to_id.get(&e) else {
+            return false;
+        };
+        if self.edge_info[e_id].is_extra() {
+            return false;
+        }
+        let other_tree_edge = |w: Node, x: Node| {
+            (w.min(x), w.max(x)) != e
+                && !self.edge_info[self.e_to_id[&(w.min(x), w.max(x))]].is_extra()
+        };
+        // Explore the spanning tree on both sides of the edge at the same pace, scanning one edge on each side per
+        // round, until one side is done. The other side then scanned at most one more edge.
+        let (mut stacks, mut seen) = (
+            [vec![(u, self.adj[u].iter())], vec![(v, self.adj[v].iter())]],
+            [BTreeSet::from([u]), BTreeSet::from([v])],
+        );
+        let small = 'explore: loop {
+            for i in 0..2 {
+                let Some((w, it)) = stacks[i].last_mut() else {
+                    break 'explore i;
+                };
+                let (w, next) = (*w, it.next().copied());
+                match next {
+                    Some(x) => {
+                        if other_tree_edge(w, x) && seen[i].insert(x) {
+                            stacks[i].push((x, self.adj[x].iter()));
+                        }
+                    }
+                    None => {
+                        stacks[i].pop();
+                    }
+                }
+            }
+        };
+        // Any other edge leaving the smaller side would replace it.
+        let side = &seen[small];
+        !side.iter().any(|&w| {
+            self.adj[w]
+                .iter()
+                .any(|&x| !side.contains(&x) && (w.min(x), w.max(x)) != e)
+        })
+    }
+    /// Whether u and v are 2-edge-connected, that is, no single edge removal disconnects them.
+    /// This only explores their component, taking O((k + m) lg n) if it has k vertices and m edges.
+    pub fn are_2edge_connected(&mut self, u: usize, v: usize) -> bool {
+        if !self.is_connected(u, v) {
+            return false;
+        }
+        // They are iff there is no bridge on the path between them.
+        let bridges = self.bridges_from([u], DfsLabels::Sparse(BTreeMap::new()));
+        let mut seen = BTreeSet::from([u]);
+        let mut stack = vec![u];
+        while let Some(w) = stack.pop() {
+            for &x in &self.adj[w] {
+                if !bridges.contains(&(w.min(x), w.max(x))) && seen.insert(x) {
+                    stack.push(x);
+                }
+            }
+        }
+        seen.contains(&v)
+    }
+    /// Whether u and v are in the same biconnected component, that is, they lie on a common cycle, so no single vertex
+    /// removal disconnects them. Two vertices joined only by a bridge are not. A vertex is always in the same
+    /// component as itself. This only explores their component, taking O((k + m) lg n) if it has k vertices and
+    /// m edges.
+    pub fn same_biconnected(&mut self, u: usize, v: usize) -> bool {
+        if u == v {
+            return true;
+        }
+        if !self.is_connected(u, v) {
+            return false;
+        }
+        // Rooting the DFS at u, each vertex w on the way up from v must not be an articulation point between its
+        // child x on the path and u, that is, the subtree of x must reach above w. For the child of u, it must
+        // reach u without the tree edge, that is, the edge between them must not be a bridge.
+        let labels = self.lowlink([u], DfsLabels::Sparse(BTreeMap::new()));
+        let mut x = v;
+        loop {
+            let [_, low, p] = labels.get(x);
+            if p == u {
+                return low <= labels.get(u)[0];
+            }
+            if low >= labels.get(p)[0] {
+                return false;
+            }
+            x = p;
+        }
+    }
+    /// Labels each vertex with the id of its 2-edge-connected component, so two vertices have the same id iff they
+    /// are 2-edge-connected. Ids are given in order of the smallest vertex in each component, starting from 0.
+    /// This finds all bridges and labels the components of the graph without them, taking O(n + m). Bridges are found
+    /// with a single DFS rather than with [D2CSolver::would_disconnect] on each tree edge, as that explores the smaller
+    /// side of every tree edge, which is Θ(n^2 lg n) on a path.
+    pub fn two_edge_connected_components(&mut self) -> Vec<usize> {
+        let bridges =
+            self.bridges_from(0..self.n, DfsLabels::Dense(vec![DfsLabels::UNSEEN; self.n]));
+        let mut label = vec![usize::MAX; self.n];
+        let mut next_id = 0;
+        for u in 0..self.n {
+            if label[u] != usize::MAX {
+                continue;
+            }
+            label[u] = next_id;
+            let mut stack = vec![u];
+            while let Some(w) = stack.pop() {
+                for &x in &self.adj[w] {
+                    if label[x] == usize::MAX && !bridges.contains(&(w.min(x), w.max(x))) {
+                        label[x] = next_id;
+                        stack.push(x);
+                    }
+                }
+            }
+            next_id += 1;
+        }
+        label
+    }
+    /// The graph as JSON, for visualization tools, in the form
+    /// `{"vertices": [{"id", "core"}], "edges": [{"u", "v", "tree", "level"}]}`. The core of a vertex is 2 if it is in
+    /// the 2-core, 1 if it is only in the 1-core, and 0 otherwise. Removed vertices are skipped. Edges are given as in
+    /// [D2CSolver::edges], with whether they are in the spanning forest and their level in the HDT algorithm.
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&mut self) -> String {
+        let ids: Vec<_> = (0..self.n).filter(|u| !self.free.contains(u)).collect();
+        let vertices: Vec<_> = ids
+            .into_iter()
+            .map(|u| {
+                let core = if self.is_in_2core(u) {
+                    2
+                } else {
+                    self.is_in_1core(u) as u8
+                };
+                serde_json::json!({ "id": u, "core": core })
+            })
+            .collect();
+        let edges: Vec<_> = self
+            .e_to_id
+            .iter()
+            .map(|(&(u, v), &e_id)| {
+                let info = &self.edge_info[e_id];
+                serde_json::json!({ "u": u, "v": v, "tree": !info.is_extra(), "level": info.level })
+            })
+            .collect();
+        serde_json::json!({ "vertices": vertices, "edges": edges }).to_string()
+    }
+    /// All edges (u, v) of the graph, with u < v, in increasing order.
+    pub fn edges(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.e_to_id.keys().copied()
+    }
+    /// Edges grouped by their level in the HDT algorithm, as (u, v, is_tree) with u < v. Index i has the edges at
+    /// level i in increasing order, up to the highest level with an edge, so it is empty if there are no edges.
+    pub fn edges_by_level(&self) -> Vec<Vec<(usize, usize, bool)>> {
+        let mut levels: Vec<Vec<_>> = vec![];
+        for (&(u, v), &e_id) in &self.e_to_id {
+            let info = &self.edge_info[e_id];
+            if levels.len() <= info.level {
+                levels.resize_with(info.level + 1, Vec::new);
+            }
+            levels[info.level].push((u, v, !info.is_extra()));
+        }
+        levels
+    }
+    /// Adds all edges of other to this graph, with the vertices shifted by offset.
+    /// If other's vertices don't fit, the vertex set is grown, which rebuilds the solver from its edges.
+    pub fn merge_from<ETT2, LC2>(&mut self, other: &D2CSolver<ETT2, LC2>, offset: usize)
+    where
+        ETT2: EulerTourTree<AgData>,
+        LC2: LinkCutTree,
+    {
+        if offset + other.n > self.n {
+            self.grow(offset + other.n, offset + other.n);
+        }
+        for (u, v) in other.edges() {
+            self.add_edge(u + offset, v + offset);
+        }
+    }
+    /// Number of vertices the structures have room for. Vertices from n up to it are allocated but not part of the graph.
+    fn capacity(&self) -> usize {
+        self.adj.len()
+    }
+    /// Grows the vertex set to n vertices. If they don't fit, the solver is rebuilt from its edges with room for
+    /// capacity vertices.
+    fn grow(&mut self, n: usize, capacity: usize) {
+        if n > self.capacity() {
+            self.rebuild(capacity.max(n));
+        }
+        self.n = n;
+    }
+    /// Rebuilds the solver from its edges, with room for capacity vertices.
+    fn rebuild(&mut self, capacity: usize) {
+        let n = self.n;
+        let mut grown = Self::new(capacity);
+        grown.n = n;
+        grown.generation = self.generation;
+        grown.merge_policy = self.merge_policy;
+        grown.promote_side = self.promote_side;
+        grown.promotions = self.promotions;
+        for (u, v) in self.edges() {
+            grown.add_edge(u, v);
+        }
+        grown.free = std::mem::take(&mut self.free);
+        grown.events = self.events.take();
+        grown.watchers = std::mem::take(&mut self.watchers);
+        *self = grown;
+    }
+    /// Same as [Dynamic2CoreSolver::new], but reserves space for expected_edges edges,
+    /// to avoid reallocations when the size of the workload is known.
+    /// Only the edge table is reserved, as the maps are B-trees, which allocate per node anyway.
+    pub fn with_capacity(n: usize, expected_edges: usize) -> Self {
         let log2n = (n.next_power_of_two().trailing_zeros() as usize) + 1;
         let ett = (0..log2n)
             .map(|_| {
@@ -392,53 +919,54 @@ where
             })
             .collect::<Vec<_>>();
         Self {
-            _n: n,
+            n,
+            free: BTreeSet::new(),
+            generation: 0,
             ett,
-            edge_info: Vec::new(),
+            edge_info: Vec::with_capacity(expected_edges),
             e_to_id: BTreeMap::new(),
-            u_level_to_extras: BTreeMap::new(),
+            adj: vec![BTreeSet::new(); n],
+            extra_heads: vec![vec![]; n],
             lc_0: LC::new(n),
+            merge_policy: MergePolicy::default(),
+            promote_side: PromoteSide::default(),
+            promotions: 0,
+            num_extra: 0,
+            events: None,
+            watchers: vec![],
+            in_batch: false,
         }
     }
-
-    fn add_edge(&mut self, u: usize, v: usize) -> bool {
-        if u > v {
-            return self.add_edge(v, u);
-        }
-        if u == v || self.e_to_id.contains_key(&(u, v)) {
-            return false;
-        }
-        let e_id = self.edge_info.len();
-        let e = Data::Edge { level: 0, e_id };
-        let added = self.ett[0].connect(u, v, e.clone(), e);
-        if added.is_some() {
-            assert!(self.lc_0.link(u, v));
-        }
-        self.edge_info.push(EdgeInfo {
-            e: (u, v),
-            level: 0,
-            levels: added.map(|e| vec![e]),
-        });
-        self.add_edge_id(e_id);
-        true
+    /// Same as [Dynamic2CoreSolver::remove_edge], but returns the roots of the components of u and v after the removal,
+    /// which are equal iff the component wasn't split. Returns None if there was no such edge.
+    pub fn remove_edge_split(&mut self, u: usize, v: usize) -> Option<(usize, usize)> {
+        self.remove_edge(u, v)
+            .then(|| (self.ett[0].root(u), self.ett[0].root(v)))
     }
-
-    fn remove_edge(&mut self, u: usize, v: usize) -> bool {
+    /// Same as [Dynamic2CoreSolver::remove_edge], but also returns how much work the replacement search did.
+    pub fn remove_edge_profiled(&mut self, u: usize, v: usize) -> (bool, RemoveStats) {
         if u > v {
-            return self.remove_edge(v, u);
+            return self.remove_edge_profiled(v, u);
+        }
+        let mut stats = RemoveStats::default();
+        if self.free.contains(&u) || self.free.contains(&v) {
+            return (false, stats);
         }
         let e_id = if let Some(id) = self.e_to_id.get(&(u, v)) {
             *id
         } else {
-            return false;
+            return (false, stats);
         };
-        if let Some(levels) = self.edge_info[e_id].levels.clone() {
+        let core_before = self.core_snapshot(u, v);
+        self.generation += 1;
+        self.adj[u].remove(&v);
+        self.adj[v].remove(&u);
+        let split = if let Some(levels) = self.edge_info[e_id].levels.clone() {
             log::trace!(
                 "Removing tree edge ({u}, {v}) at level {}",
                 self.edge_info[e_id].level
             );
-            self.lc_0.reroot(u);
-            assert_eq!(self.lc_0.cut(v), Some(u));
+            assert!(self.lc_0.cut_edge(u, v));
             let smallest_comp: Vec<_> = levels
                 .into_iter()
                 .enumerate()
@@ -449,60 +977,605 @@ where
                     let ett = &mut self.ett[lvl];
                     assert!(!ett.is_connected(tu, tv));
                     assert!(!ett.is_connected(u, v));
-                    if ett.tree_size(tu) < ett.tree_size(tv) {
-                        tu
-                    } else {
-                        tv
-                    }
+                    self.choose_smaller(lvl, tu, tv)
                 })
                 .collect();
             self.rem_edge_id(e_id);
 
-            for (i, small) in smallest_comp.into_iter().enumerate().rev() {
-                // Move all tree edges of level i to i + 1
-                while let Some(f_id) = self.find_level_i_tree_edge(i, small) {
-                    debug_assert!(!self.edge_info[f_id].is_extra(), "tree edge is extra");
-                    debug_assert_eq!(self.edge_info[f_id].level, i, "edge has wrong level");
-                    self.add_level_to_edge(f_id);
-                }
-                // For all extra edges of level i, check if they replace the removed edge, and move them to level i + 1
-                while let Some(f_id) = self.find_level_i_extra_edge(i, small) {
-                    let (a, b) = self.edge_info[f_id].e;
-                    if !self.ett[i].is_connected(a, b) {
-                        log::trace!("Extra edge ({a}, {b}) at level {i} will replace ({u}, {v})");
-                        assert!(self.lc_0.link(a, b));
-                        self.rem_edge_id(f_id);
-                        let mut rs = vec![];
-                        // This is a replacement edge, add it to the tree in this and previous levels, then exit.
-                        let e = Data::Edge {
-                            level: i,
-                            e_id: f_id,
-                        };
-                        for j in 0..=i {
-                            let r = self.ett[j]
-                                .connect(a, b, e.clone(), e.clone())
-                                .expect("shouldn't be connected at previous level");
-                            rs.push(r);
+            // Whether no replacement was found
+            'search: {
+                for (i, small) in smallest_comp.into_iter().enumerate().rev() {
+                    stats.levels_searched += 1;
+                    // Move all tree edges of level i to i + 1
+                    while let Some(f_id) = self.find_level_i_tree_edge(i, small) {
+                        debug_assert!(!self.edge_info[f_id].is_extra(), "tree edge is extra");
+                        debug_assert_eq!(self.edge_info[f_id].level, i, "edge has wrong level");
+                        self.add_level_to_edge(f_id);
+                        stats.tree_promotions += 1;
+                    }
+                    // For all extra edges of level i, check if they replace the removed edge, and move them to level i + 1
+                    while let Some(f_id) = self.find_level_i_extra_edge(i, small) {
+                        let (a, b) = self.edge_info[f_id].e;
+                        if !self.ett[i].is_connected(a, b) {
+                            log::trace!(
+                                "Extra edge ({a}, {b}) at level {i} will replace ({u}, {v})"
+                            );
+                            assert!(self.lc_0.link(a, b));
+                            self.rem_edge_id(f_id);
+                            let mut rs = vec![];
+                            // This is a replacement edge, add it to the tree in this and previous levels, then exit.
+                            for j in 0..=i {
+                                let e = |_| Data::Edge {
+                                    e_id: f_id,
+                                    top_level: j == i,
+                                };
+                                let r = self.ett[j]
+                                    .connect_with(a, b, self.merge_policy, e)
+                                    .expect("shouldn't be connected at previous level");
+                                rs.push(r);
+                            }
+                            self.edge_info[f_id].levels = Some(rs);
+                            self.add_edge_id(f_id);
+                            stats.replacement_found = true;
+                            break 'search false;
                         }
-                        self.edge_info[f_id].levels = Some(rs);
-                        self.add_edge_id(f_id);
-                        return true;
+                        self.add_level_to_edge(f_id);
+                        stats.extra_promotions += 1;
                     }
-                    self.add_level_to_edge(f_id);
                 }
+                true
             }
         } else {
             self.rem_edge_id(e_id);
-        }
+            false
+        };
+        let events = [GraphEvent::EdgeRemoved(u, v)]
+            .into_iter()
+            .chain(split.then_some(GraphEvent::ComponentSplit(u, v)));
+        self.send_events(events, core_before);
         // TODO swap with last to save space. May be tricky to keep all indices
+        (true, stats)
+    }
+    /// Same as [Dynamic2CoreSolver::add_edge], but also returns whether the 2-core changed.
+    /// It changes iff the edge closes a cycle (u and v were already connected) that isn't already in the 2-core,
+    /// which is the case iff u or v was not in it, as the tree path between two 2-core vertices is in the 2-core.
+    pub fn add_edge_tracked(&mut self, u: usize, v: usize) -> (bool, bool) {
+        let closes_new_cycle =
+            self.is_connected(u, v) && !(self.is_in_2core(u) && self.is_in_2core(v));
+        let added = self.add_edge(u, v);
+        (added, added && closes_new_cycle)
+    }
+    /// Removes all edges incident to u. Returns how many edges were removed.
+    /// Extra edges are removed first, as they are cheap and leave fewer candidates for replacement searches when removing the tree edges.
+    pub fn isolate(&mut self, u: usize) -> usize {
+        let (mut extra, tree): (Vec<_>, Vec<_>) = self
+            .neighbors(u)
+            .partition(|&v| self.edge_info[self.e_to_id[&(u.min(v), u.max(v))]].is_extra());
+        extra.extend(tree);
+        for &v in &extra {
+            assert!(self.remove_edge(u, v));
+        }
+        extra.len()
+    }
+    /// Removes all edges incident to u and frees its index, so it can be reused by [D2CSolver::add_vertex].
+    /// A removed vertex must not be referenced until its index is returned by [D2CSolver::add_vertex] again, and
+    /// adding or removing edges on it returns false.
+    pub fn remove_vertex(&mut self, u: usize) {
+        assert!(u < self.n, "vertex {u} out of range");
+        self.isolate(u);
+        assert!(self.free.insert(u), "vertex {u} was already removed");
+    }
+    /// Adds an isolated vertex and returns its index. The smallest index freed by [D2CSolver::remove_vertex] is
+    /// reused if there is any. Otherwise the vertex set grows by one. When the structures are full, they are rebuilt
+    /// from the edges with room for twice as many vertices, so, as in [GrowableSolver], adding up to n vertices only
+    /// rebuilds O(lg n) times.
+    pub fn add_vertex(&mut self) -> usize {
+        if let Some(u) = self.free.pop_first() {
+            return u;
+        }
+        let u = self.n;
+        self.grow(u + 1, (2 * u).max(1));
+        // Snapshots don't know about the new vertex.
+        self.generation += 1;
+        u
+    }
+    /// Copies the component containing u into a new solver, with its vertices relabeled to 0..k. Also returns the original
+    /// label of each new vertex, which are in increasing order. The vertices are read from the tour of the component,
+    /// so this takes O(k lg k) plus the time to add its edges to the new solver.
+    pub fn clone_component(&mut self, u: usize) -> (Self, Vec<usize>) {
+        let mut original = vec![];
+        self.ett[0].for_each_node(u, |w, _| original.push(w));
+        original.sort_unstable();
+        let new_label: BTreeMap<Node, Node> =
+            original.iter().enumerate().map(|(i, &w)| (w, i)).collect();
+        let mut solver = Self::with_capacity(original.len(), 0);
+        solver.merge_policy = self.merge_policy;
+        solver.promote_side = self.promote_side;
+        for &w in &original {
+            for &x in self.adj[w].range(w..) {
+                solver.add_edge(new_label[&w], new_label[&x]);
+            }
+        }
+        (solver, original)
+    }
+    /// Extracts the 2-core of the component containing u. Returns its edges (u < v) and its vertices, with the original labels and in increasing order.
+    /// The vertices are read from the tour of the component, so this takes O((k + m) lg n) for a component with k
+    /// vertices and m edges, regardless of the rest of the graph.
+    pub fn extract_2core(&mut self, u: usize) -> (Vec<(usize, usize)>, Vec<usize>) {
+        let mut comp = vec![];
+        self.ett[0].for_each_node(u, |w, _| comp.push(w));
+        let mut adj: BTreeMap<Node, BTreeSet<Node>> =
+            comp.iter().map(|&w| (w, self.adj[w].clone())).collect();
+        // Peel vertices with degree at most 1 until none is left.
+        let mut to_peel: Vec<_> = comp.iter().copied().filter(|w| adj[w].len() <= 1).collect();
+        while let Some(w) = to_peel.pop() {
+            let Some(neighbors) = adj.remove(&w) else {
+                continue;
+            };
+            for x in neighbors {
+                let nx = adj.get_mut(&x).unwrap();
+                nx.remove(&w);
+                if nx.len() == 1 {
+                    to_peel.push(x);
+                }
+            }
+        }
+        let edges = adj
+            .iter()
+            .flat_map(|(&a, ns)| ns.range(a..).map(move |&b| (a, b)))
+            .collect();
+        (edges, adj.into_keys().collect())
+    }
+    /// Membership of all vertices in the 2-core as a bitset: vertex u is in it iff bit u % 64 of word u / 64 is set.
+    /// Bits past n are unset. Instead of querying each vertex, this peels vertices with degree at most 1 until none is
+    /// left, taking O(n + m).
+    pub fn two_core_bitset(&self) -> Vec<u64> {
+        let mut degree: Vec<_> = (0..self.n).map(|u| self.degree(u)).collect();
+        let mut to_peel: Vec<_> = (0..self.n).filter(|&u| degree[u] <= 1).collect();
+        let mut peeled = vec![false; self.n];
+        while let Some(u) = to_peel.pop() {
+            if std::mem::replace(&mut peeled[u], true) {
+                continue;
+            }
+            for &v in &self.adj[u] {
+                degree[v] -= 1;
+                if degree[v] == 1 {
+                    to_peel.push(v);
+                }
+            }
+        }
+        let mut bits = vec![0; self.n.div_ceil(64)];
+        for u in (0..self.n).filter(|&u| !peeled[u]) {
+            bits[u / 64] |= 1 << (u % 64);
+        }
+        bits
+    }
+    /// Edges of the 2-core, in increasing order. These are exactly the edges with both endpoints in the 2-core.
+    /// Note this includes bridges on paths between cycles, since the 2-core is the maximal subgraph with minimum degree 2,
+    /// and removing them would lower the degree of their endpoints.
+    pub fn edges_in_2core(&mut self) -> Vec<(usize, usize)> {
+        let in_2core: Vec<_> = (0..self.n).map(|u| self.is_in_2core(u)).collect();
+        self.edges()
+            .filter(|&(u, v)| in_2core[u] && in_2core[v])
+            .collect()
+    }
+    /// Counters of internal operations of all inner structures. Always zero unless the `metrics` feature is enabled.
+    pub fn counters(&self) -> Counters {
+        self.ett
+            .iter()
+            .fold(self.lc_0.counters(), |c, ett| c + ett.counters())
+    }
+    /// Total number of times an edge was moved up a level. As each edge can only go up O(lg n) levels,
+    /// this is O(m lg n) for m added edges, which bounds the amortized cost of [Dynamic2CoreSolver::remove_edge].
+    pub fn total_promotions(&self) -> u64 {
+        self.promotions
+    }
+    /// Sizes of the graph and of the structure, in a single call. Most are kept up to date, but the 2-core is found with
+    /// [D2CSolver::two_core_bitset] and the maximum level by looking at all edges, so this takes O(n + m).
+    pub fn stats(&self) -> SolverStats {
+        let n = self.n - self.free.len();
+        let total_edges = self.e_to_id.len();
+        let num_tree_edges = total_edges - self.num_extra;
+        SolverStats {
+            n,
+            total_edges,
+            num_tree_edges,
+            // Each tree edge joins two components of isolated vertices
+            num_components: n - num_tree_edges,
+            two_core_size: self
+                .two_core_bitset()
+                .iter()
+                .map(|w| w.count_ones() as usize)
+                .sum(),
+            max_level: self
+                .e_to_id
+                .values()
+                .map(|&e_id| self.edge_info[e_id].level)
+                .max()
+                .unwrap_or(0),
+            total_promotions: self.promotions,
+        }
+    }
+    /// Estimate of the memory used, in bytes, including all levels of ETTs and the LCT.
+    /// It is O((n + m) lg n), as each level has an ETT with all nodes.
+    pub fn memory_bytes(&self) -> usize {
+        use std::mem::size_of;
+        // Inner structures count their own struct, which is already counted in ours or in a Vec's capacity.
+        let etts = self.ett.capacity() * size_of::<ETT>()
+            + self
+                .ett
+                .iter()
+                .map(|ett| ett.memory_bytes() - size_of::<ETT>())
+                .sum::<usize>();
+        let edge_info = self.edge_info.capacity() * size_of::<EdgeInfo>()
+            + self
+                .edge_info
+                .iter()
+                .map(|info| info.levels.as_ref().map_or(0, Vec::capacity) * size_of::<EdgeRef>())
+                .sum::<usize>();
+        let adj = self.adj.capacity() * size_of::<BTreeSet<Node>>()
+            + self
+                .adj
+                .iter()
+                .map(|ns| map_bytes::<Node, ()>(ns.len()))
+                .sum::<usize>();
+        let extras = self.extra_heads.capacity() * size_of::<Vec<EdgeId>>()
+            + self
+                .extra_heads
+                .iter()
+                .map(|heads| heads.capacity() * size_of::<EdgeId>())
+                .sum::<usize>();
+        size_of::<Self>() - size_of::<LC>()
+            + self.lc_0.memory_bytes()
+            + etts
+            + edge_info
+            + map_bytes::<(Node, Node), usize>(self.e_to_id.len())
+            + adj
+            + extras
+    }
+    /// Counter that changes whenever an edge is added or removed.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+    /// Precomputes the answers to all queries into a read-only structure with O(1) queries.
+    /// It is only valid until the next change to the graph, which can be checked with [FrozenSolver::is_up_to_date].
+    pub fn freeze(&mut self) -> FrozenSolver {
+        let component: Vec<_> = (0..self.n).map(|u| self.ett[0].root(u)).collect();
+        let mut comp_size = vec![0; self.n];
+        for &c in &component {
+            comp_size[c] += 1;
+        }
+        let in_1core = component.iter().map(|&c| comp_size[c] > 1).collect();
+        let in_2core = (0..self.n).map(|u| self.is_in_2core(u)).collect();
+        FrozenSolver {
+            generation: self.generation,
+            component,
+            in_1core,
+            in_2core,
+        }
+    }
+    /// Changes to the components and the 2-core since prev was taken. Both are compared vertex by vertex, so this takes
+    /// the O(n lg^2 n) of [D2CSolver::freeze] plus O(n). Panics if the number of vertices changed since then.
+    pub fn diff(&mut self, prev: &FrozenSolver) -> GraphDiff {
+        assert_eq!(
+            prev.component.len(),
+            self.n,
+            "the number of vertices changed since the snapshot"
+        );
+        let now = self.freeze();
+        // A component is unchanged iff all its vertices were in the same old component, and it had no other vertices.
+        let (mut new_of_old, mut old_of_new) = (vec![Node::MAX; self.n], vec![Node::MAX; self.n]);
+        let (mut split, mut merged) = (vec![false; self.n], vec![false; self.n]);
+        for w in 0..self.n {
+            let (c, d) = (prev.component[w], now.component[w]);
+            if new_of_old[c] == Node::MAX {
+                new_of_old[c] = d;
+            }
+            if old_of_new[d] == Node::MAX {
+                old_of_new[d] = c;
+            }
+            split[c] |= new_of_old[c] != d;
+            merged[d] |= old_of_new[d] != c;
+        }
+        let flipped = |in_2core| {
+            (0..self.n)
+                .filter(|&w| now.in_2core[w] == in_2core && prev.in_2core[w] != in_2core)
+                .collect()
+        };
+        GraphDiff {
+            component_changed: (0..self.n)
+                .filter(|&w| split[prev.component[w]] || merged[now.component[w]])
+                .collect(),
+            entered_2core: flipped(true),
+            left_2core: flipped(false),
+        }
+    }
+    /// Sends a [GraphEvent] to tx for every change to the graph from now on, replacing any previous sender. The events of each
+    /// change are sent in order: the edge event, then the component event, if any, then the 2-core changes by vertex.
+    /// Finding the 2-core changes takes O(k lg n) for a changed component of k vertices, so only set this if needed.
+    /// Events stop being sent once the receiver is dropped.
+    pub fn set_event_sender(&mut self, tx: Sender<GraphEvent>) {
+        self.events = Some(tx);
+    }
+    /// Calls f on every change to the component containing u, until the solver is dropped. The watch follows the
+    /// component through merges, and stays on the side of u when it is split. Unlike [D2CSolver::set_event_sender],
+    /// the 2-core isn't checked, but every watch is checked on each change, even on other components, so each change
+    /// costs O(W lg n) with W watches.
+    pub fn watch_component(&mut self, u: usize, f: ComponentWatcher) {
+        self.watchers.push((u, f));
+    }
+    /// Calls the watchers of the component changed by events, which start with the edge event.
+    fn notify_watchers(&mut self, events: &[GraphEvent]) {
+        let Some(&(GraphEvent::EdgeAdded(u, v) | GraphEvent::EdgeRemoved(u, v))) = events.first()
+        else {
+            return;
+        };
+        let mut watchers = std::mem::take(&mut self.watchers);
+        for (w, f) in &mut watchers {
+            // The components of u and v after the change are exactly the vertices of the changed component before it.
+            if !self.is_connected(*w, u) && !self.is_connected(*w, v) {
+                continue;
+            }
+            for &event in events {
+                f(match event {
+                    GraphEvent::EdgeAdded(a, b) => ComponentEvent::EdgeAdded(a, b),
+                    GraphEvent::EdgeRemoved(a, b) => ComponentEvent::EdgeRemoved(a, b),
+                    GraphEvent::ComponentMerged(a, b) => ComponentEvent::Merged(a, b),
+                    GraphEvent::ComponentSplit(a, b) => ComponentEvent::Split(a, b),
+                    GraphEvent::CoreChanged { .. } => continue,
+                });
+            }
+        }
+        self.watchers = watchers;
+    }
+    /// 2-core membership of the vertices in the components of u and v, if events are being sent.
+    /// These are the only ones that may change when adding or removing the edge (u, v).
+    fn core_snapshot(&mut self, u: Node, v: Node) -> Vec<(Node, bool)> {
+        if self.events.is_none() || self.in_batch {
+            return vec![];
+        }
+        let mut affected = vec![];
+        self.ett[0].for_each_node(u, |w, _| affected.push(w));
+        if !self.ett[0].is_connected(u, v) {
+            self.ett[0].for_each_node(v, |w, _| affected.push(w));
+        }
+        affected.sort_unstable();
+        affected
+            .into_iter()
+            .map(|w| (w, self.is_in_2core(w)))
+            .collect()
+    }
+    /// Sends the events of a change, followed by the 2-core changes since core_before, from [D2CSolver::core_snapshot].
+    fn send_events(
+        &mut self,
+        events: impl IntoIterator<Item = GraphEvent>,
+        core_before: Vec<(Node, bool)>,
+    ) {
+        if self.watchers.is_empty() && self.events.is_none() {
+            return;
+        }
+        let events: Vec<_> = events.into_iter().collect();
+        self.notify_watchers(&events);
+        let Some(tx) = self.events.take() else {
+            return;
+        };
+        let core_changes = core_before
+            .into_iter()
+            .filter_map(|(vertex, was)| {
+                let in_2core = self.is_in_2core(vertex);
+                (in_2core != was).then_some(GraphEvent::CoreChanged { vertex, in_2core })
+            })
+            .collect::<Vec<_>>();
+        let sent = events
+            .into_iter()
+            .chain(core_changes)
+            .try_for_each(|event| tx.send(event));
+        // If the receiver is gone, stop paying for the events.
+        if sent.is_ok() {
+            self.events = Some(tx);
+        }
+    }
+    /// Applies the changes done by f, which only has access to adding and removing edges, as a single change.
+    /// Queries are unaffected, but events for the 2-core changes are sent only once at the end, for the vertices whose
+    /// membership differs from before the batch, which is cheaper than finding them after every change. The other events
+    /// are still sent as each change is done. Without an event sender this is the same as doing the changes directly,
+    /// as all other bookkeeping, like the extra edge counters, is needed by the following changes.
+    pub fn batch(&mut self, f: impl FnOnce(&mut BatchCtx<'_, ETT, LC>)) {
+        let core_before = if self.events.is_some() && !self.in_batch {
+            (0..self.n).map(|w| (w, self.is_in_2core(w))).collect()
+        } else {
+            vec![]
+        };
+        let nested = std::mem::replace(&mut self.in_batch, true);
+        f(&mut BatchCtx { solver: self });
+        self.in_batch = nested;
+        self.send_events([], core_before);
+    }
+    /// Sets which tree root survives when components are merged. This doesn't change any answers, but affects the shape of the tours and thus performance.
+    pub fn set_merge_policy(&mut self, policy: MergePolicy) {
+        self.merge_policy = policy;
+    }
+    /// Sets which side of a removed tree edge has its edges promoted while searching for a replacement. This doesn't
+    /// change any answers, but any side other than the default loses the amortized bound on removals.
+    pub fn set_promote_side(&mut self, side: PromoteSide) {
+        self.promote_side = side;
+    }
+}
+
+/// Changes that can be done inside [D2CSolver::batch].
+pub struct BatchCtx<'a, ETT, LC>
+where
+    ETT: EulerTourTree<AgData>,
+    LC: LinkCutTree,
+{
+    solver: &'a mut D2CSolver<ETT, LC>,
+}
+
+impl<ETT, LC> BatchCtx<'_, ETT, LC>
+where
+    ETT: EulerTourTree<AgData>,
+    LC: LinkCutTree,
+{
+    /// Same as [Dynamic2CoreSolver::add_edge].
+    pub fn add_edge(&mut self, u: usize, v: usize) -> bool {
+        self.solver.add_edge(u, v)
+    }
+    /// Same as [Dynamic2CoreSolver::remove_edge].
+    pub fn remove_edge(&mut self, u: usize, v: usize) -> bool {
+        self.solver.remove_edge(u, v)
+    }
+}
+
+/// Read-only snapshot of the answers of a [D2CSolver], created by [D2CSolver::freeze].
+#[derive(Debug, Clone)]
+pub struct FrozenSolver {
+    /// Generation of the solver when this was created.
+    generation: u64,
+    /// Representative of the component of each node.
+    component: Vec<Node>,
+    in_1core: Vec<bool>,
+    in_2core: Vec<bool>,
+}
+
+impl FrozenSolver {
+    /// Whether the solver wasn't changed since this was created, and thus the answers are still correct.
+    pub fn is_up_to_date<ETT, LC>(&self, solver: &D2CSolver<ETT, LC>) -> bool
+    where
+        ETT: EulerTourTree<AgData>,
+        LC: LinkCutTree,
+    {
+        self.generation == solver.generation
+    }
+    /// Check if u and v are connected.
+    pub fn is_connected(&self, u: usize, v: usize) -> bool {
+        self.component[u] == self.component[v]
+    }
+    /// Check if u is in the 2-core.
+    pub fn is_in_2core(&self, u: usize) -> bool {
+        self.in_2core[u]
+    }
+    /// Check if u is in the 1-core.
+    pub fn is_in_1core(&self, u: usize) -> bool {
+        self.in_1core[u]
+    }
+}
+
+/// Which side of a removed tree edge has its edges promoted while searching for a replacement,
+/// see [D2CSolver::set_promote_side].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PromoteSide {
+    /// The side with fewer nodes, as in HDT, so each level has half the nodes of the previous one.
+    #[default]
+    Smaller,
+    /// The side with more nodes. Levels are no longer bounded by lg n, so more are added as needed.
+    Larger,
+    /// The side that keeps the root of the tour, whatever its size.
+    Root,
+}
+
+/// Changes between a [FrozenSolver] and the current graph, created by [D2CSolver::diff]. Vertices are in increasing order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphDiff {
+    /// Vertices whose component doesn't have the same vertices as before, because it was merged or split.
+    pub component_changed: Vec<usize>,
+    /// Vertices that are in the 2-core now, but weren't before.
+    pub entered_2core: Vec<usize>,
+    /// Vertices that were in the 2-core before, but aren't now.
+    pub left_2core: Vec<usize>,
+}
+
+/// Work done by a single removal, returned by [D2CSolver::remove_edge_profiled]. Removing an extra edge does no work.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoveStats {
+    /// Tree edges moved up a level, from the smaller side of the cut.
+    pub tree_promotions: usize,
+    /// Extra edges moved up a level, after failing to replace the removed tree edge.
+    pub extra_promotions: usize,
+    /// Levels searched for a replacement, starting from the removed edge's level and going down.
+    pub levels_searched: usize,
+    /// Whether a replacement was found, so the component wasn't split.
+    pub replacement_found: bool,
+}
+
+/// Snapshot of the size and shape of a [D2CSolver], returned by [D2CSolver::stats].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SolverStats {
+    /// Number of vertices, not counting the ones removed by [D2CSolver::remove_vertex].
+    pub n: usize,
+    pub total_edges: usize,
+    /// Edges in the spanning forest. The others are extra edges, each closing an independent cycle.
+    pub num_tree_edges: usize,
+    pub num_components: usize,
+    /// Number of vertices in the 2-core.
+    pub two_core_size: usize,
+    /// Highest level of any edge in the HDT algorithm, or 0 if there are no edges.
+    pub max_level: usize,
+    /// See [D2CSolver::total_promotions].
+    pub total_promotions: u64,
+}
+
+/// Implementation of main dyn2core methods for the solver
+impl<ETT, LC> Dynamic2CoreSolver for D2CSolver<ETT, LC>
+where
+    ETT: EulerTourTree<AgData>,
+    LC: LinkCutTree,
+{
+    fn new(n: usize) -> Self {
+        Self::with_capacity(n, 0)
+    }
+
+    fn add_edge(&mut self, u: usize, v: usize) -> bool {
+        if u > v {
+            return self.add_edge(v, u);
+        }
+        assert!(v < self.n, "vertex {v} out of range");
+        if u == v || self.e_to_id.contains_key(&(u, v)) {
+            return false;
+        }
+        if self.free.contains(&u) || self.free.contains(&v) {
+            return false;
+        }
+        let core_before = self.core_snapshot(u, v);
+        let e_id = self.edge_info.len();
+        let e = |_| Data::Edge {
+            e_id,
+            top_level: true,
+        };
+        let added = self.ett[0].connect_with(u, v, self.merge_policy, e);
+        if added.is_some() {
+            assert!(self.lc_0.link(u, v));
+        }
+        self.edge_info.push(EdgeInfo {
+            e: (u, v),
+            level: 0,
+            levels: added.map(|e| vec![e]),
+            extra_links: [[NO_EDGE; 2]; 2],
+        });
+        self.add_edge_id(e_id);
+        self.adj[u].insert(v);
+        self.adj[v].insert(u);
+        self.generation += 1;
+        let merged = self.edge_info[e_id].levels.is_some();
+        let events = [GraphEvent::EdgeAdded(u, v)]
+            .into_iter()
+            .chain(merged.then_some(GraphEvent::ComponentMerged(u, v)));
+        self.send_events(events, core_before);
         true
     }
 
+    fn remove_edge(&mut self, u: usize, v: usize) -> bool {
+        self.remove_edge_profiled(u, v).0
+    }
+
     fn is_connected(&mut self, u: usize, v: usize) -> bool {
         self.ett[0].is_connected(u, v)
     }
 
     fn is_in_2core(&mut self, u: usize) -> bool {
+        // Common in sparse graphs, and doesn't need to touch the trees.
+        if self.degree(u) < 2 {
+            return false;
+        }
         self.ett[0].reroot(u);
         self.lc_0.reroot(u);
         self.first_and_last_nodes_with_extra_edges(u)
@@ -520,4 +1593,289 @@ where
         // Definitely can be more efficient, O(1), but this works
         self.ett[0].tree_size(u) > 1
     }
+
+    /// Each extra edge closes exactly one independent cycle with the spanning forest, so this takes O(1).
+    fn num_independent_cycles(&self) -> usize {
+        self.num_extra
+    }
+
+    /// Groups the vertices by the root of their tour, in O(n lg n).
+    fn connected_components(&mut self) -> Vec<Vec<usize>> {
+        let mut comp_of_root = vec![usize::MAX; self.n];
+        let mut comps: Vec<Vec<usize>> = vec![];
+        for u in 0..self.n {
+            let r = self.ett[0].root(u);
+            if comp_of_root[r] == usize::MAX {
+                comp_of_root[r] = comps.len();
+                comps.push(vec![]);
+            }
+            comps[comp_of_root[r]].push(u);
+        }
+        comps
+    }
+
+    /// Checks every edge against its info, the ETTs on each level, the extra edge maps and counters, and that the
+    /// LCT has the same components as level 0. Takes O((n + m) lg^2 n).
+    fn validate(&mut self) -> Result<(), String> {
+        let degrees: usize = self.adj.iter().map(BTreeSet::len).sum();
+        if degrees != 2 * self.e_to_id.len() {
+            return Err(format!(
+                "sum of degrees is {degrees}, but there are {} edges",
+                self.e_to_id.len()
+            ));
+        }
+        // Lists of extra edges are walked checking their links. A repeated edge is an error, so a broken list can't
+        // loop forever.
+        let mut registered = BTreeSet::new();
+        for w in 0..self.n {
+            if self.extra_heads[w].last() == Some(&NO_EDGE) {
+                return Err(format!(
+                    "node {w} has empty lists of extra edges at the end"
+                ));
+            }
+            for lvl in 0..self.extra_heads[w].len() {
+                let (mut prev, mut e_id) = (NO_EDGE, self.extra_heads[w][lvl]);
+                while e_id != NO_EDGE {
+                    let Some(info) = self.edge_info.get(e_id) else {
+                        return Err(format!(
+                            "unknown id {e_id} in extra edges of {w} on level {lvl}"
+                        ));
+                    };
+                    if self.e_to_id.get(&info.e) != Some(&e_id)
+                        || !info.is_extra()
+                        || info.level != lvl
+                        || (info.e.0 != w && info.e.1 != w)
+                    {
+                        return Err(format!(
+                            "id {e_id} registered as extra edge of {w} on level {lvl}, but it is {info:?}"
+                        ));
+                    }
+                    if !registered.insert((w, lvl, e_id)) {
+                        return Err(format!(
+                            "id {e_id} is repeated in the extra edges of {w} on level {lvl}"
+                        ));
+                    }
+                    let [back, next] = info.extra_links[self.extra_side(e_id, w)];
+                    if back != prev {
+                        return Err(format!(
+                            "extra edge {e_id} of {w} on level {lvl} links back to {back}, expected {prev}"
+                        ));
+                    }
+                    (prev, e_id) = (e_id, next);
+                }
+            }
+        }
+        let mut tree_edges = 0;
+        for (&(u, v), &e_id) in &self.e_to_id {
+            let Some(info) = self.edge_info.get(e_id) else {
+                return Err(format!("edge ({u}, {v}) has unknown id {e_id}"));
+            };
+            if info.e != (u, v) || u >= v {
+                return Err(format!(
+                    "edge ({u}, {v}) has id {e_id} of edge {:?}",
+                    info.e
+                ));
+            }
+            if !self.adj[u].contains(&v) || !self.adj[v].contains(&u) {
+                return Err(format!("edge ({u}, {v}) missing from adjacency"));
+            }
+            if info.level >= self.ett.len() {
+                return Err(format!(
+                    "edge ({u}, {v}) has level {} out of range",
+                    info.level
+                ));
+            }
+            let levels = info.levels.as_deref().unwrap_or_default();
+            if !info.is_extra() && levels.len() != info.level + 1 {
+                return Err(format!(
+                    "tree edge ({u}, {v}) at level {} is on {} levels",
+                    info.level,
+                    levels.len()
+                ));
+            }
+            tree_edges += !info.is_extra() as usize;
+            for (lvl, ett) in self.ett.iter_mut().enumerate() {
+                let actual = ett.edge_ref_between(u, v).map(|e| e.inner_idx());
+                let expected = levels.get(lvl).map(|e| e.inner_idx());
+                if actual != expected {
+                    return Err(format!(
+                        "edge ({u}, {v}) is {actual:?} on level {lvl}, expected {expected:?}"
+                    ));
+                }
+                if let Some(&e) = levels.get(lvl) {
+                    let top_level = lvl == info.level;
+                    for d in ett.edata(e) {
+                        if *d != (Data::Edge { e_id, top_level }) {
+                            return Err(format!(
+                                "edge ({u}, {v}) on level {lvl} has data {d:?}, expected id={e_id} with top_level={top_level}"
+                            ));
+                        }
+                    }
+                }
+            }
+            if info.is_extra() {
+                for w in [u, v] {
+                    if !registered.contains(&(w, info.level, e_id)) {
+                        return Err(format!(
+                            "extra edge ({u}, {v}) not registered for {w} on level {}",
+                            info.level
+                        ));
+                    }
+                }
+                if !self.ett[info.level].is_connected(u, v) {
+                    return Err(format!(
+                        "extra edge ({u}, {v}) on level {} connects different trees",
+                        info.level
+                    ));
+                }
+            }
+        }
+        for w in 0..self.n {
+            let mut total = 0;
+            for lvl in 0..self.ett.len() {
+                let expected = registered
+                    .range((w, lvl, 0)..=(w, lvl, EdgeId::MAX))
+                    .count();
+                total += expected;
+                let Data::Node { extra_edges, .. } = *self.ett[lvl].data(w) else {
+                    return Err(format!("node {w} has edge data on level {lvl}"));
+                };
+                if extra_edges != expected {
+                    return Err(format!(
+                        "node {w} has {extra_edges} extra edges on level {lvl}, expected {expected}"
+                    ));
+                }
+            }
+            let Data::Node {
+                any_extra_edges, ..
+            } = *self.ett[0].data(w)
+            else {
+                unreachable!("checked above");
+            };
+            if any_extra_edges != total {
+                return Err(format!(
+                    "node {w} has {any_extra_edges} extra edges on any level, expected {total}"
+                ));
+            }
+        }
+        // Both forests span the same components iff roots map one-to-one between them.
+        let mut ett_to_lc = BTreeMap::new();
+        let mut lc_to_ett = BTreeMap::new();
+        for w in 0..self.n {
+            let (r_ett, r_lc) = (self.ett[0].root(w), self.lc_0.root(w));
+            if *ett_to_lc.entry(r_ett).or_insert(r_lc) != r_lc
+                || *lc_to_ett.entry(r_lc).or_insert(r_ett) != r_ett
+            {
+                return Err(format!(
+                    "node {w} is on different components in the LCT and level 0"
+                ));
+            }
+        }
+        if let Some(&w) = self
+            .free
+            .iter()
+            .find(|&&w| w >= self.n || self.degree(w) > 0)
+        {
+            return Err(format!("removed vertex {w} is out of range or has edges"));
+        }
+        if tree_edges + self.num_extra != self.e_to_id.len() {
+            return Err(format!(
+                "{tree_edges} tree edges and {} extra edges, but {} edges",
+                self.num_extra,
+                self.e_to_id.len()
+            ));
+        }
+        if tree_edges + ett_to_lc.len() != self.n {
+            return Err(format!(
+                "{tree_edges} tree edges, but {} components on {} nodes",
+                ett_to_lc.len(),
+                self.n
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// [D2CSolver] whose vertex set grows as needed, so [Dynamic2CoreSolver::add_edge] accepts any vertices.
+/// Vertices that were never used are isolated, so queries about them are answered as such.
+///
+/// Growing rebuilds the solver from its edges, in O(m lg^2 n). The number of vertices at least doubles each time, so
+/// inserting edges with ever-increasing vertices up to n only rebuilds O(lg n) times, and each edge is reinserted
+/// O(1) times on average.
+pub struct GrowableSolver<ETT, LC>
+where
+    ETT: EulerTourTree<AgData>,
+    LC: LinkCutTree,
+{
+    solver: D2CSolver<ETT, LC>,
+}
+
+impl<ETT, LC> GrowableSolver<ETT, LC>
+where
+    ETT: EulerTourTree<AgData>,
+    LC: LinkCutTree,
+{
+    /// The underlying solver. Its vertices are the ones allocated so far, which may be more than the ones used.
+    pub fn inner(&mut self) -> &mut D2CSolver<ETT, LC> {
+        &mut self.solver
+    }
+    pub fn into_inner(self) -> D2CSolver<ETT, LC> {
+        self.solver
+    }
+    fn has(&self, u: usize) -> bool {
+        u < self.solver.n
+    }
+}
+
+impl<ETT, LC> Dynamic2CoreSolver for GrowableSolver<ETT, LC>
+where
+    ETT: EulerTourTree<AgData>,
+    LC: LinkCutTree,
+{
+    fn new(n: usize) -> Self {
+        Self {
+            solver: D2CSolver::new(n),
+        }
+    }
+
+    fn add_edge(&mut self, u: usize, v: usize) -> bool {
+        if u == v {
+            return false;
+        }
+        let needed = u.max(v) + 1;
+        if needed > self.solver.n {
+            let n = needed.max(2 * self.solver.n);
+            self.solver.grow(n, n);
+        }
+        self.solver.add_edge(u, v)
+    }
+
+    fn remove_edge(&mut self, u: usize, v: usize) -> bool {
+        self.has(u) && self.has(v) && self.solver.remove_edge(u, v)
+    }
+
+    fn is_connected(&mut self, u: usize, v: usize) -> bool {
+        u == v || (self.has(u) && self.has(v) && self.solver.is_connected(u, v))
+    }
+
+    fn is_in_2core(&mut self, u: usize) -> bool {
+        self.has(u) && self.solver.is_in_2core(u)
+    }
+
+    fn is_in_1core(&mut self, u: usize) -> bool {
+        self.has(u) && self.solver.is_in_1core(u)
+    }
+
+    fn num_independent_cycles(&self) -> usize {
+        self.solver.num_independent_cycles()
+    }
+
+    /// Components of the vertices allocated so far.
+    fn connected_components(&mut self) -> Vec<Vec<usize>> {
+        self.solver.connected_components()
+    }
+
+    fn validate(&mut self) -> Result<(), String> {
+        self.solver.validate()
+    }
 }
@@ -2,12 +2,48 @@
 
 use std::{fmt::Debug, marker::PhantomData};
 
-use crate::lists::{AggregatedData, Idx, Lists, SearchData, SearchDirection};
+use crate::{
+    lists::{AggregatedData, Idx, Lists, Monoid, SearchData, SearchDirection},
+    metrics::Counters,
+};
 
 // Edges will be idx and idx + 1
 #[derive(Debug, Clone, Copy)]
 pub struct EdgeRef(Idx);
 
+/// Aggregate that only takes into account the nodes of the tour, so Ag doesn't need to know about edges.
+/// Nodes have data Some(data), and edges have None, which contribute Ag::default().
+#[derive(Debug, Clone, Default)]
+pub struct NodeOnlyAgg<Ag>(pub Ag);
+
+impl<Ag: AggregatedData> Monoid for NodeOnlyAgg<Ag> {
+    type Data = Option<Ag::Data>;
+    fn from(data: &Self::Data) -> Self {
+        Self(data.as_ref().map_or_else(Ag::default, Ag::from))
+    }
+    fn merge(self, right: Self) -> Self {
+        Self(self.0.merge(right.0))
+    }
+}
+
+impl<Ag: AggregatedData> AggregatedData for NodeOnlyAgg<Ag> {
+    fn reverse(self) -> Self {
+        Self(self.0.reverse())
+    }
+}
+
+/// Decides which tree's root survives when two trees are merged by [EulerTourTree::connect_with_policy].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+    /// The root of the tree containing the first node remains the root.
+    #[default]
+    KeepFirst,
+    /// The root of the tree with more nodes remains the root. Ties keep the first.
+    LargerTree,
+    /// The root with the smallest index remains the root.
+    SmallerRoot,
+}
+
 /// Interface of an Euler Tour Tree
 /// It maintains a collection of euler tours on a forest of trees. Each node and edge might have associated data, which can be aggregated.
 pub trait EulerTourTree<Ag: AggregatedData> {
@@ -16,41 +52,146 @@ pub trait EulerTourTree<Ag: AggregatedData> {
     fn new(node_data: Vec<Ag::Data>) -> Self;
     /// Makes the given node the root of its tree.
     fn reroot(&mut self, u: Idx);
+    /// Makes the given node the root of its tree, and returns the previous root, so it can be restored by rerooting on it.
+    fn reroot_returning(&mut self, u: Idx) -> Idx {
+        let old_root = self.root(u);
+        self.reroot(u);
+        old_root
+    }
     /// Returns the root of the euler tour tree containing u.
     fn root(&mut self, u: Idx) -> Idx;
     /// Remove the edge and return the root of the current tree and then the root of the new tree the edge removal created.
     fn disconnect(&mut self, edge: EdgeRef) -> (Idx, Idx);
     /// Connects the two nodes with an edge. The root of the first tree remais the root. Returns None if they are already connected.
-    fn connect(&mut self, u: Idx, w: Idx, uw_data: Ag::Data, wu_data: Ag::Data) -> Option<EdgeRef>;
+    /// uw_data is kept on the tour entry that goes from u to w, and wu_data on the one coming back, so while the tree is
+    /// rooted on u's side the tour is `... u uw <subtree of w> wu ...`, and rooted on w's side it is
+    /// `... w wu <subtree of u> uw ...`. [EulerTourTree::edata] and [EulerTourTree::endpoints] always give them in
+    /// the order [uw, wu] and [u, w], however the tree is rerooted.
+    fn connect(&mut self, u: Idx, w: Idx, uw_data: Ag::Data, wu_data: Ag::Data) -> Option<EdgeRef> {
+        self.connect_with_policy(u, w, uw_data, wu_data, MergePolicy::KeepFirst)
+    }
+    /// Same as [EulerTourTree::connect], but the root that remains is chosen by the policy.
+    fn connect_with_policy(
+        &mut self,
+        u: Idx,
+        w: Idx,
+        uw_data: Ag::Data,
+        wu_data: Ag::Data,
+        policy: MergePolicy,
+    ) -> Option<EdgeRef> {
+        let mut data = [Some(uw_data), Some(wu_data)];
+        self.connect_with(u, w, policy, |uw| data[!uw as usize].take().unwrap())
+    }
+    /// Same as [EulerTourTree::connect_with_policy], but the data of each direction is created by data(true) for uw
+    /// and data(false) for wu, only if they are connected, so it doesn't need to be built or cloned otherwise.
+    fn connect_with(
+        &mut self,
+        u: Idx,
+        w: Idx,
+        policy: MergePolicy,
+        data: impl FnMut(bool) -> Ag::Data,
+    ) -> Option<EdgeRef>;
     fn is_connected(&mut self, u: Idx, v: Idx) -> bool;
+    /// Whether there is a tree edge directly between u and v.
+    fn are_adjacent(&mut self, u: Idx, v: Idx) -> bool {
+        self.edge_ref_between(u, v).is_some()
+    }
+    /// The tree edge directly between u and v, in any direction, if it exists.
+    /// Takes O(d lg n), where d is the smaller number of tree edges of u and v.
+    fn edge_ref_between(&mut self, u: Idx, v: Idx) -> Option<EdgeRef>;
+    /// The endpoints [u, w] of the edge, in the order they were given to connect. Panics if the edge was removed.
+    fn endpoints(&self, e: EdgeRef) -> [Idx; 2];
     /// Number of nodes in the whole tree this node is contained in.
     fn tree_size(&mut self, u: Idx) -> usize;
+    /// Number of nodes in all trees, which is fixed on creation. The tours also have entries for edges.
+    fn node_count(&self) -> usize;
+    /// Aggregated data of the whole tour of the tree containing u, including edges.
+    fn tree_agg(&mut self, u: Idx) -> Ag;
     /// Finds an element in the tree containing this node and return it. It may be a node or an edge.
     fn find_element(
         &mut self,
         u: Idx,
         search_strategy: impl FnMut(SearchData<'_, Ag>) -> SearchDirection,
     ) -> Idx;
+    /// First element in the tour of the tree containing u whose data satisfies node_is, or EMPTY if there is none.
+    /// agg_can must return whether there is any element satisfying node_is among the ones in the aggregate, and is used to prune the search.
+    fn find_first_node(
+        &mut self,
+        u: Idx,
+        agg_can: impl Fn(&Ag) -> bool,
+        node_is: impl Fn(&Ag::Data) -> bool,
+    ) -> Idx {
+        self.find_element(u, |d| {
+            if agg_can(d.left_agg) {
+                SearchDirection::Left
+            } else if node_is(d.current_data) {
+                SearchDirection::Found
+            } else if agg_can(d.right_agg) {
+                SearchDirection::Right
+            } else {
+                SearchDirection::NotFound
+            }
+        })
+    }
+    /// Same as [EulerTourTree::find_first_node], but finds the last element.
+    fn find_last_node(
+        &mut self,
+        u: Idx,
+        agg_can: impl Fn(&Ag) -> bool,
+        node_is: impl Fn(&Ag::Data) -> bool,
+    ) -> Idx {
+        self.find_element(u, |d| {
+            if agg_can(d.right_agg) {
+                SearchDirection::Right
+            } else if node_is(d.current_data) {
+                SearchDirection::Found
+            } else if agg_can(d.left_agg) {
+                SearchDirection::Left
+            } else {
+                SearchDirection::NotFound
+            }
+        })
+    }
+    /// Calls f with every node of the tree containing u and its data, in the order they appear in the tour, skipping edges.
+    /// Takes O(tree size).
+    fn for_each_node(&mut self, u: Idx, f: impl FnMut(Idx, &Ag::Data));
+    /// Whether u is an ancestor of v, or v itself, in their tree rooted at its current root. False if not connected.
+    /// Takes O(d lg n), where d is the number of tree edges of u.
+    fn is_ancestor(&mut self, u: Idx, v: Idx) -> bool;
     /// Returns data of the node. Can be used for normal nodes, or from Idx of edges returned by find_element.
     fn data(&self, u: Idx) -> &Ag::Data;
     /// Modifies the data on a given node
     fn mutate_data(&mut self, u: Idx, f: impl FnOnce(&mut Ag::Data));
-    /// Returns the data of the edge.
+    /// Returns the data of the edge as [uw, wu], where [u, w] are its [EulerTourTree::endpoints].
     fn edata(&self, e: EdgeRef) -> [&Ag::Data; 2];
-    /// Modifies the data of the edge. The direction is given by a boolean.
+    /// Modifies the data of the edge in one direction: false for uw and true for wu, as in [EulerTourTree::edata].
     fn mutate_edata(&mut self, e: EdgeRef, direction: bool, f: impl FnOnce(&mut Ag::Data));
+    /// Counters of internal operations. Always zero unless the `metrics` feature is enabled.
+    fn counters(&self) -> Counters {
+        Counters::default()
+    }
+    /// Estimate of the memory used, in bytes, including this struct. By default only counts the struct itself.
+    fn memory_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
 }
 
-pub struct ETT<L, Ag>
+/// Euler Tour Tree over lists L. Each node may also hold a payload P, which is never aggregated.
+pub struct ETT<L, Ag, P = ()>
 where
     L: Lists<Ag>,
     Ag: AggregatedData,
 {
     l: L,
+    /// Payload of each node, indexed by node.
+    payload: Vec<P>,
+    /// Endpoints [u, w] of each edge ever created, indexed by edge, that is, by (uw entry - number of nodes) / 2.
+    /// Removed edges have [EMPTY, EMPTY].
+    endpoints: Vec<[Idx; 2]>,
     _phantom: PhantomData<Ag>,
 }
 
-impl<BST, Ag> std::fmt::Debug for ETT<BST, Ag>
+impl<BST, Ag, P> std::fmt::Debug for ETT<BST, Ag, P>
 where
     BST: Lists<Ag>,
     Ag: AggregatedData,
@@ -79,51 +220,146 @@ impl EdgeRef {
     }
 }
 
-impl<L, Ag> ETT<L, Ag>
+impl<L, Ag, P> ETT<L, Ag, P>
 where
     L: Lists<Ag>,
     Ag: AggregatedData,
 {
-    /// Adds an edge between the root of self and the root of other. Panics if they are on the same tree.
+    /// Same as [EulerTourTree::new], with the given payload for each node.
+    pub fn with_payloads(node_data: Vec<Ag::Data>, payload: Vec<P>) -> Self {
+        assert_eq!(node_data.len(), payload.len(), "one payload per node");
+        let mut l = L::new(node_data.len());
+        assert_eq!(l.create_many(node_data.into_iter()), 0..payload.len());
+        Self {
+            l,
+            payload,
+            endpoints: vec![],
+            _phantom: PhantomData,
+        }
+    }
+    /// Payload of node u. Payloads are kept by node, so they are not affected by any tree operation.
+    pub fn payload(&self, u: Idx) -> &P {
+        &self.payload[u]
+    }
+    pub fn payload_mut(&mut self, u: Idx) -> &mut P {
+        &mut self.payload[u]
+    }
+    /// Adds an edge between u and the root of other tree, using the already created edge nodes. Panics if they are on the same tree.
     fn link_root(
         &mut self,
         u: Idx,
         root_w: Idx, // w
-        uw_data: Ag::Data,
-        wu_data: Ag::Data,
-    ) -> EdgeRef {
+        uw: Idx,
+        wu: Idx,
+    ) {
         debug_assert!(!self.l.on_same_list(u, root_w));
         debug_assert!(self.l.is_first(root_w));
-        let uw = self.l.create(uw_data); // uw
-        let wu = self.l.create(wu_data); // wu
 
         // "AAA u BBB" and "w CCC" (it is root) becomes
         // AAA u uw w CCC wu BBB
         let order = self.l.order(u);
-        let (_, until_u, after_u) = self.l.split(u, 0..=order);
-        self.l.concat_all([until_u, uw, root_w, wu, after_u]);
-        EdgeRef(uw)
+        let tour_w = self.l.concat_all([uw, root_w, wu]);
+        self.l.splice(u, order + 1, tour_w);
+    }
+    /// Edge of the edge entry idx, and the entry as a step [from, to]. Edges entries are created in pairs after all
+    /// nodes, so the index of the entry gives its edge.
+    fn edge_step(&self, idx: Idx) -> (EdgeRef, [Idx; 2]) {
+        let k = idx - self.payload.len();
+        let [u, w] = self.endpoints[k / 2];
+        if k % 2 == 1 {
+            (EdgeRef(idx - 1), [w, u])
+        } else {
+            (EdgeRef(idx), [u, w])
+        }
+    }
+    /// The other entry of the same edge as the edge entry idx.
+    fn partner(&self, idx: Idx) -> Idx {
+        let (EdgeRef(uw), _) = self.edge_step(idx);
+        if idx == uw {
+            uw + 1
+        } else {
+            uw
+        }
+    }
+    /// Around each node u, the tour goes out and back through each edge of u, and passes its node entry once.
+    /// Given u's node entry or an entry arriving at u, returns the next entry leaving u, or EMPTY if the tour gets
+    /// back to u's node entry first, so starting from the node entry goes through all edges of u.
+    fn next_departure(&mut self, arrival: Idx) -> Idx {
+        let next = if self.l.is_last(arrival) {
+            self.l.first(arrival)
+        } else {
+            self.l.next(arrival)
+        };
+        if next < self.payload.len() {
+            L::EMPTY
+        } else {
+            next
+        }
+    }
+    /// Tour entry idx as a step [from, to]: [u, u] for node u, and [u, w] for the entry of an edge going from u to w.
+    #[cfg(debug_assertions)]
+    fn step(&self, idx: Idx) -> [Idx; 2] {
+        if idx < self.payload.len() {
+            [idx, idx]
+        } else {
+            self.edge_step(idx).1
+        }
+    }
+    /// Checks the tour of u's tree is a closed walk, that is, each entry starts where the previous one ended, so the
+    /// data of each edge direction is still on the entry going that way.
+    #[cfg(debug_assertions)]
+    fn check_tour(&mut self, u: Idx) {
+        let mut entries = vec![];
+        self.l.for_each(u, |idx, _| entries.push(idx));
+        for (i, &a) in entries.iter().enumerate() {
+            let b = entries[(i + 1) % entries.len()];
+            let ([_, to], [from, _]) = (self.step(a), self.step(b));
+            debug_assert_eq!(
+                to, from,
+                "tour of {u} is broken between entries {a} and {b}"
+            );
+        }
+    }
+    /// Lists holding the tours, with an entry for each node and two for each edge.
+    pub fn inner_lists(&self) -> &L {
+        &self.l
     }
-    pub fn inner_lists(&mut self) -> &mut L {
+    /// Mutable access to the lists holding the tours, for operations the ETT doesn't expose.
+    /// Changing data is fine, but anything that breaks the tours, like reordering, splitting or concatenating them,
+    /// leaves the ETT corrupted.
+    pub fn inner_lists_mut(&mut self) -> &mut L {
         &mut self.l
     }
 }
 
-impl<L, Ag> EulerTourTree<Ag> for ETT<L, Ag>
+impl<L, Ag, P> ETT<L, Ag, P>
 where
     L: Lists<Ag>,
     Ag: AggregatedData,
+    P: Default,
+{
+    /// Whether the root of u's tree should remain the root when merging with w's tree.
+    fn keeps_first_root(&mut self, u: Idx, w: Idx, policy: MergePolicy) -> bool {
+        match policy {
+            MergePolicy::KeepFirst => true,
+            MergePolicy::LargerTree => self.tree_size(u) >= self.tree_size(w),
+            MergePolicy::SmallerRoot => self.root(u) <= self.root(w),
+        }
+    }
+}
+
+impl<L, Ag, P> EulerTourTree<Ag> for ETT<L, Ag, P>
+where
+    L: Lists<Ag>,
+    Ag: AggregatedData,
+    P: Default,
 {
     const EMPTY: Idx = L::EMPTY;
     fn new(node_data: Vec<Ag::Data>) -> Self {
-        let mut l = L::new(node_data.len());
-        for (i, data) in node_data.into_iter().enumerate() {
-            assert_eq!(l.create(data), i);
-        }
-        Self {
-            l,
-            _phantom: PhantomData,
-        }
+        let payload = std::iter::repeat_with(P::default)
+            .take(node_data.len())
+            .collect();
+        Self::with_payloads(node_data, payload)
     }
     fn reroot(&mut self, u: Idx) {
         if !self.l.is_first(u) {
@@ -131,11 +367,22 @@ where
             let (before_u, u_and_after, _) = self.l.split(u, order..);
             self.l.concat(u_and_after, before_u);
         }
+        #[cfg(debug_assertions)]
+        self.check_tour(u);
     }
     fn root(&mut self, u: Idx) -> Idx {
-        self.l.first(u)
+        // After a disconnect, the tour may start on an edge entry, and then it starts from the node it leaves.
+        let first = self.l.first(u);
+        if first < self.payload.len() {
+            first
+        } else {
+            self.edge_step(first).1[0]
+        }
     }
     fn disconnect(&mut self, edge: EdgeRef) -> (Idx, Idx) {
+        let k = (edge.0 - self.payload.len()) / 2;
+        let [u, _] = std::mem::replace(&mut self.endpoints[k], [L::EMPTY; 2]);
+        assert_ne!(u, L::EMPTY, "edge is not in the tree");
         let (edge, other_e) = (edge.0, edge.0 + 1);
         debug_assert!(self.l.on_same_list(edge, other_e));
         let (a, b) = (self.l.order(edge), self.l.order(other_e));
@@ -146,19 +393,59 @@ where
         debug_assert_eq!(self.l.len(edge), 1);
         debug_assert_eq!(self.l.len(other_e), 1);
         let rest = self.l.concat(left, right);
-        (self.l.first(rest), self.l.first(middle))
+        (self.root(rest), self.root(middle))
     }
     fn is_connected(&mut self, u: Idx, v: Idx) -> bool {
         self.l.on_same_list(u, v)
     }
-    fn connect(&mut self, u: Idx, w: Idx, uw_data: Ag::Data, wu_data: Ag::Data) -> Option<EdgeRef> {
+    fn edge_ref_between(&mut self, u: Idx, v: Idx) -> Option<EdgeRef> {
+        if u == v {
+            return None;
+        }
+        // Go through the edges of both at the same pace, so it stops after the smaller degree.
+        let mut outs = [(v, self.next_departure(u)), (u, self.next_departure(v))];
+        loop {
+            for (to, out) in &mut outs {
+                if *out == L::EMPTY {
+                    return None;
+                }
+                let (e, [_, w]) = self.edge_step(*out);
+                if w == *to {
+                    return Some(e);
+                }
+                *out = self.next_departure(self.partner(*out));
+            }
+        }
+    }
+    fn endpoints(&self, e: EdgeRef) -> [Idx; 2] {
+        let uw = self.endpoints[(e.0 - self.payload.len()) / 2];
+        assert_ne!(uw[0], L::EMPTY, "edge is not in the tree");
+        uw
+    }
+    fn connect_with(
+        &mut self,
+        u: Idx,
+        w: Idx,
+        policy: MergePolicy,
+        mut data: impl FnMut(bool) -> Ag::Data,
+    ) -> Option<EdgeRef> {
         if self.l.on_same_list(u, w) {
             // Already connected
-            None
-        } else {
+            return None;
+        }
+        let keep_u = self.keeps_first_root(u, w, policy);
+        let uw = self.l.create(data(true));
+        let wu = self.l.create(data(false));
+        if keep_u {
             self.reroot(w);
-            Some(self.link_root(u, w, uw_data, wu_data))
+            self.link_root(u, w, uw, wu);
+        } else {
+            self.reroot(u);
+            self.link_root(w, u, wu, uw);
         }
+        debug_assert_eq!(uw, self.payload.len() + 2 * self.endpoints.len());
+        self.endpoints.push([u, w]);
+        Some(EdgeRef(uw))
     }
 
     fn find_element(
@@ -168,6 +455,37 @@ where
     ) -> Idx {
         self.l.find_element(u, search_strategy)
     }
+    fn for_each_node(&mut self, u: Idx, mut f: impl FnMut(Idx, &Ag::Data)) {
+        // Nodes are created before any edge, so they have the smallest indices.
+        let n = self.payload.len();
+        self.l.for_each(u, |v, d| {
+            if v < n {
+                f(v, d)
+            }
+        });
+    }
+    fn is_ancestor(&mut self, u: Idx, v: Idx) -> bool {
+        if !self.l.on_same_list(u, v) {
+            return false;
+        }
+        if u == v || self.l.is_first(u) {
+            return true;
+        }
+        // The subtree of u is the part of the tour between the two entries of the edge to its parent, which are the
+        // first and last entries of u's edges. Node u itself may be anywhere in it, as rerooting rotates the tour.
+        let (mut first, mut last) = (usize::MAX, 0);
+        let mut out = self.next_departure(u);
+        while out != L::EMPTY {
+            let back = self.partner(out);
+            for e in [out, back] {
+                let order = self.l.order(e);
+                (first, last) = (first.min(order), last.max(order));
+            }
+            out = self.next_departure(back);
+        }
+        let v = self.l.order(v);
+        first < v && v < last
+    }
     fn data(&self, u: Idx) -> &Ag::Data {
         self.l.data(u)
     }
@@ -186,4 +504,20 @@ where
     fn tree_size(&mut self, u: Idx) -> usize {
         (self.l.len(u) + 2) / 3
     }
+    fn node_count(&self) -> usize {
+        self.payload.len()
+    }
+    fn tree_agg(&mut self, u: Idx) -> Ag {
+        self.l.total_agg(u)
+    }
+    fn counters(&self) -> Counters {
+        self.l.counters()
+    }
+    fn memory_bytes(&self) -> usize {
+        // The lists count their own struct, which is already inside ours
+        std::mem::size_of::<Self>() - std::mem::size_of::<L>()
+            + self.l.memory_bytes()
+            + self.payload.capacity() * std::mem::size_of::<P>()
+            + self.endpoints.capacity() * std::mem::size_of::<[Idx; 2]>()
+    }
 }
@@ -0,0 +1,69 @@
+//! Generators of graphs, as edge lists, useful for testing and benchmarking the solvers.
+//! Graphs are simple (no self loops or repeated edges) and can be loaded with [crate::Dynamic2CoreSolver::from_edges].
+
+use std::collections::BTreeSet;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Random graph on n vertices with exactly m distinct edges, chosen uniformly.
+/// Panics if m is larger than the number of possible edges.
+pub fn random_graph(n: usize, m: usize, seed: u64) -> Vec<(usize, usize)> {
+    assert!(
+        m <= n * n.saturating_sub(1) / 2,
+        "can't have {m} edges on {n} vertices"
+    );
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut seen = BTreeSet::new();
+    let mut edges = Vec::with_capacity(m);
+    while edges.len() < m {
+        let (u, v) = (rng.gen_range(0..n), rng.gen_range(0..n));
+        if u != v && seen.insert((u.min(v), u.max(v))) {
+            edges.push((u, v));
+        }
+    }
+    edges
+}
+
+/// Grid with the given number of rows and columns, where vertex (r, c) is r * cols + c.
+pub fn grid(rows: usize, cols: usize) -> Vec<(usize, usize)> {
+    let id = |r: usize, c: usize| r * cols + c;
+    let mut edges = vec![];
+    for r in 0..rows {
+        for c in 0..cols {
+            if c + 1 < cols {
+                edges.push((id(r, c), id(r, c + 1)));
+            }
+            if r + 1 < rows {
+                edges.push((id(r, c), id(r + 1, c)));
+            }
+        }
+    }
+    edges
+}
+
+/// Barabási–Albert preferential attachment graph on n vertices. It starts with a clique on the first m + 1 vertices,
+/// then each new vertex is connected to m distinct earlier vertices, chosen with probability proportional to their degree.
+/// It has m(m + 1)/2 + (n - m - 1)m edges. Panics if n <= m.
+pub fn barabasi_albert(n: usize, m: usize, seed: u64) -> Vec<(usize, usize)> {
+    assert!(n > m, "need more than {m} vertices");
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut edges = vec![];
+    for u in 0..=m {
+        for v in 0..u {
+            edges.push((v, u));
+        }
+    }
+    // Each vertex appears once for each incident edge, so sampling from it is proportional to degree.
+    let mut endpoints: Vec<_> = edges.iter().flat_map(|&(u, v)| [u, v]).collect();
+    for u in (m + 1)..n {
+        let mut targets = BTreeSet::new();
+        while targets.len() < m {
+            targets.insert(endpoints[rng.gen_range(0..endpoints.len())]);
+        }
+        for v in targets {
+            edges.push((v, u));
+            endpoints.extend([u, v]);
+        }
+    }
+    edges
+}
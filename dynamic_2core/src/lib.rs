@@ -42,18 +42,54 @@
 //! ```
 //!
 //! Add `-- --ignored` to run the stress tests (which run indefinitely with random data until they fail). And use `cargo bench` to see the benchmarks.
+//!
+//! When a stress test fails, its operations are saved to a file in the temp directory, which can be loaded with [replay::ReplayLog::load] and replayed with [replay::replay].
 #![feature(get_many_mut)]
 pub mod dynamic_2core;
 pub mod euler_tour_tree;
+pub mod generators;
 pub mod link_cut_tree;
 pub mod lists;
+pub mod metrics;
+pub mod replay;
+pub mod static_graph;
 pub use dynamic_2core::Dynamic2CoreSolver;
 
 use dynamic_2core::{AgData, D2CSolver};
 use euler_tour_tree::ETT;
 use link_cut_tree::LCT;
-use lists::treap::Treaps;
+use lists::{splay::Splays, treap::Treaps};
 
 /// The fastest implemented solver for dynamic 2-core on this crate. It uses Link Cut Trees and Euler Tour Trees with Treaps.
 pub type FastDynamic2CoreSolver = D2CSolver<ETT<Treaps<AgData>, AgData>, LCT<Treaps>>;
 // Splays are faster for LCT in theory, but in practice the Treaps are better AFAICT.
+
+/// Lists used by the solver, in its Euler Tour Trees and in its Link Cut Tree. See [make_solver].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverKind {
+    TreapTreap,
+    TreapSplay,
+    SplayTreap,
+    SplaySplay,
+}
+
+impl SolverKind {
+    pub const ALL: [SolverKind; 4] = [
+        SolverKind::TreapTreap,
+        SolverKind::TreapSplay,
+        SolverKind::SplayTreap,
+        SolverKind::SplaySplay,
+    ];
+}
+
+/// Solver on n nodes using the given lists, chosen at runtime. [SolverKind::TreapTreap] is [FastDynamic2CoreSolver].
+pub fn make_solver(kind: SolverKind, n: usize) -> Box<dyn Dynamic2CoreSolver> {
+    type Treap = ETT<Treaps<AgData>, AgData>;
+    type Splay = ETT<Splays<AgData>, AgData>;
+    match kind {
+        SolverKind::TreapTreap => Box::new(D2CSolver::<Treap, LCT<Treaps>>::new(n)),
+        SolverKind::TreapSplay => Box::new(D2CSolver::<Treap, LCT<Splays>>::new(n)),
+        SolverKind::SplayTreap => Box::new(D2CSolver::<Splay, LCT<Treaps>>::new(n)),
+        SolverKind::SplaySplay => Box::new(D2CSolver::<Splay, LCT<Splays>>::new(n)),
+    }
+}
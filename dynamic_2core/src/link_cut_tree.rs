@@ -1,6 +1,11 @@
-//! Link Cut Tree implementation, without aggregated data.
+//! Link Cut Tree implementation, without aggregated data. [LCTSized] additionally keeps subtree sizes.
 
-use crate::lists::Lists;
+use std::marker::PhantomData;
+
+use crate::{
+    lists::{AggregatedData, Lists, Monoid},
+    metrics::{count, Counters},
+};
 
 pub type Node = usize;
 
@@ -9,6 +14,22 @@ pub type Node = usize;
 pub trait LinkCutTree {
     /// Create a new LinkCutTree with n vertices and no edges.
     fn new(n: usize) -> Self;
+    /// Create a LinkCutTree representing the rooted forest where u's parent is parents[u].
+    /// Panics if some parent is out of range or if parents has a cycle.
+    fn from_forest(parents: &[Option<Node>]) -> Self
+    where
+        Self: Sized,
+    {
+        let mut t = Self::new(parents.len());
+        for (u, &p) in parents.iter().enumerate() {
+            if let Some(p) = p {
+                assert!(p < parents.len(), "parent {p} of {u} out of range");
+                // u is still the root of its tree, so this keeps its subtree below it.
+                assert!(t.link(p, u), "parents has a cycle through {u}");
+            }
+        }
+        t
+    }
     /// Returns the root of the tree containing u.
     fn root(&mut self, u: Node) -> Node;
     /// Adds an edge between u and v. Returns false if they were in the same tree.
@@ -16,62 +37,226 @@ pub trait LinkCutTree {
     fn link(&mut self, u: Node, v: Node) -> bool;
     /// Cuts u from its immediate parent. Returns the parent of u.
     fn cut(&mut self, u: Node) -> Option<Node>;
+    /// Cuts the edge between u and v, which may be in any orientation. Returns false if they aren't adjacent,
+    /// in which case no edge is cut. Reroots u either way.
+    fn cut_edge(&mut self, u: Node, v: Node) -> bool {
+        self.reroot(u);
+        if u == v || self.root(v) != u || self.depth(v) != 1 {
+            return false;
+        }
+        assert_eq!(self.cut(v), Some(u));
+        true
+    }
+    /// Vertices on the path from u to v, including both, or None if they are in different trees. Reroots u.
+    /// By default, it goes up from v by cutting and relinking each node, which takes O(path length) operations.
+    fn path(&mut self, u: Node, v: Node) -> Option<Vec<Node>> {
+        self.reroot(u);
+        if self.root(v) != u {
+            return None;
+        }
+        let mut path = vec![v];
+        let mut w = v;
+        while let Some(p) = self.cut(w) {
+            assert!(self.link(p, w));
+            path.push(p);
+            w = p;
+        }
+        path.reverse();
+        Some(path)
+    }
     /// Makes u the root of its current tree.
     fn reroot(&mut self, u: Node);
+    /// Number of edges between u and the root of its tree.
+    fn depth(&mut self, u: Node) -> usize;
     /// The lowest common ancestor of u and v. None if they are in different trees.
     fn lca(&mut self, u: Node, v: Node) -> Option<Node>;
+    /// Counters of internal operations. Always zero unless the `metrics` feature is enabled.
+    fn counters(&self) -> Counters {
+        Counters::default()
+    }
+    /// Estimate of the memory used, in bytes, including this struct. By default only counts the struct itself.
+    fn memory_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+/// Data kept on the nodes of a [LCT], which may depend on the trees hanging from each node by path parents.
+/// `()` keeps nothing, and [SubtreeSize] keeps the size of those trees.
+pub trait VirtualData: AggregatedData {
+    /// Data of a node with nothing hanging from it.
+    fn empty() -> Self::Data;
+    /// Called when the tree whose topmost path is the list containing `child` starts (`hang = true`) or stops
+    /// hanging from u. The LCT only changes the aggregate of u's list when nothing above it depends on it, or
+    /// when the change is undone before the next use.
+    fn on_hang<L: Lists<Self>>(_l: &mut L, _u: Node, _child: Node, _hang: bool) {}
+}
+
+impl VirtualData for () {
+    fn empty() -> Self::Data {}
+}
+
+/// Aggregate of [LCTSized]. The data of each node is the number of nodes in the trees hanging from it by path
+/// parents, and the aggregate of a preferred path is the number of nodes in it or hanging below it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubtreeSize(pub usize);
+
+impl Monoid for SubtreeSize {
+    type Data = usize;
+    fn from(virt: &usize) -> Self {
+        Self(virt + 1)
+    }
+    fn merge(self, right: Self) -> Self {
+        Self(self.0 + right.0)
+    }
+}
+
+impl AggregatedData for SubtreeSize {
+    fn reverse(self) -> Self {
+        self
+    }
+}
+
+impl VirtualData for SubtreeSize {
+    fn empty() -> usize {
+        0
+    }
+    fn on_hang<L: Lists<Self>>(l: &mut L, u: Node, child: Node, hang: bool) {
+        let size = l.total_agg(child).0;
+        l.mutate_data(u, |virt| if hang { *virt += size } else { *virt -= size });
+    }
 }
 
 #[derive(Debug)]
-pub struct LCT<L>
+pub struct LCT<L, Ag = ()>
 where
-    L: Lists<()>,
+    L: Lists<Ag>,
+    Ag: VirtualData,
 {
     l: L,
     // Non-EMPTY iff the node is a root of a preferred path that is not the topmost.
     parent: Vec<usize>,
+    counters: Counters,
+    _ag: PhantomData<Ag>,
 }
 
-impl<L> LCT<L>
+/// [LCT] that also maintains the size of every subtree, see [LCT::subtree_size].
+pub type LCTSized<L> = LCT<L, SubtreeSize>;
+
+impl<L, Ag> LCT<L, Ag>
 where
-    L: Lists<()>,
+    L: Lists<Ag>,
+    Ag: VirtualData,
 {
     /// Returns the point where the access operation entered the topmost preferred path.
     /// That is, returns the LCA of u with the last node that called access.
     fn access(&mut self, mut u: Node) -> Node {
+        count!(self.counters.accesses);
         let mut prev_topmost = L::EMPTY;
         let mut last_u = u;
+        #[cfg(debug_assertions)]
+        let mut hops = 0;
         while u != L::EMPTY {
-            let order = self.l.order(u);
-            let (_, _, after) = self.l.split(u, ..=order);
-            assert!(self.l.is_last(u));
+            // A valid tree has at most n preferred paths above u.
+            #[cfg(debug_assertions)]
+            {
+                hops += 1;
+                assert!(
+                    hops <= self.parent.len(),
+                    "LCT invariant violated: parent cycle detected"
+                );
+            }
+            // The path below u becomes a new preferred path, and the one we came from takes its place.
+            // The tree below u is the same, so its aggregate is unchanged once both are done.
+            if prev_topmost != L::EMPTY {
+                Ag::on_hang(&mut self.l, u, prev_topmost, false);
+            }
+            let after = self.l.replace_after(u, prev_topmost);
             if after != L::EMPTY {
-                self.parent[self.l.first(after)] = u;
+                self.parent[after] = u;
+                Ag::on_hang(&mut self.l, u, after, true);
             }
-            self.l.concat(u, prev_topmost);
             last_u = u;
             u = self.l.first(u);
             (u, prev_topmost) = (std::mem::replace(&mut self.parent[u], L::EMPTY), u);
         }
         last_u
     }
+    /// Overwrites the path parent of u, without any checks. Only used to test invariant checks.
+    #[cfg(feature = "debug-hooks")]
+    #[doc(hidden)]
+    pub fn set_path_parent_unchecked(&mut self, u: Node, p: Node) {
+        self.parent[u] = p;
+    }
 }
 
-impl<L> LinkCutTree for LCT<L>
+impl<L> LCT<L, SubtreeSize>
 where
-    L: Lists,
+    L: Lists<SubtreeSize>,
+{
+    /// Number of nodes in the subtree of u, including u, in the tree rooted at the current root.
+    pub fn subtree_size(&mut self, u: Node) -> usize {
+        self.access(u);
+        // Nothing is below u in its preferred path, so its whole subtree hangs from it.
+        *self.l.data(u) + 1
+    }
+}
+
+impl<L, Ag> LinkCutTree for LCT<L, Ag>
+where
+    L: Lists<Ag>,
+    Ag: VirtualData,
 {
     fn new(n: usize) -> Self {
         let mut l = L::new(n);
-        for i in 0..n {
-            assert_eq!(l.create(()), i);
-        }
+        assert_eq!(l.create_many(std::iter::repeat_n(Ag::empty(), n)), 0..n);
         Self {
             l,
             parent: vec![L::EMPTY; n],
+            counters: Counters::default(),
+            _ag: PhantomData,
         }
     }
 
+    fn from_forest(parents: &[Option<Node>]) -> Self {
+        let n = parents.len();
+        // 0 = unvisited, 1 = on current path, 2 = done
+        let mut state = vec![0u8; n];
+        let mut path = vec![];
+        let mut children = vec![0usize; n];
+        for start in 0..n {
+            let mut u = start;
+            while state[u] == 0 {
+                state[u] = 1;
+                path.push(u);
+                match parents[u] {
+                    Some(p) => {
+                        assert!(p < n, "parent {p} of {u} out of range");
+                        assert!(state[p] != 1, "parents has a cycle through {p}");
+                        children[p] += 1;
+                        u = p;
+                    }
+                    None => break,
+                }
+            }
+            path.drain(..).for_each(|v| state[v] = 2);
+        }
+        // Each node is its own preferred path, hanging from its parent. Children are hung before their parents,
+        // so their whole subtree is already below them.
+        let mut t = Self::new(n);
+        let mut ready: Vec<Node> = (0..n).filter(|&u| children[u] == 0).collect();
+        while let Some(u) = ready.pop() {
+            if let Some(p) = parents[u] {
+                t.parent[u] = p;
+                Ag::on_hang(&mut t.l, p, u, true);
+                children[p] -= 1;
+                if children[p] == 0 {
+                    ready.push(p);
+                }
+            }
+        }
+        t
+    }
+
     fn root(&mut self, u: Node) -> Node {
         self.access(u);
         self.l.first(u)
@@ -82,7 +267,9 @@ where
             return false;
         }
         self.reroot(v);
+        // u was accessed by root(u) and v is in another tree, so u's path is still the topmost.
         self.parent[v] = u;
+        Ag::on_hang(&mut self.l, u, v, true);
         true
     }
 
@@ -104,6 +291,24 @@ where
         self.l.reverse(u);
     }
 
+    fn path(&mut self, u: Node, v: Node) -> Option<Vec<Node>> {
+        self.reroot(u);
+        self.access(v);
+        // The preferred path of v now goes from the root to v
+        if self.l.first(v) != u {
+            return None;
+        }
+        let mut path = vec![];
+        self.l.for_each(v, |w, _| path.push(w));
+        Some(path)
+    }
+
+    fn depth(&mut self, u: Node) -> usize {
+        self.access(u);
+        // The preferred path of u now goes from the root to u
+        self.l.order(u)
+    }
+
     fn lca(&mut self, u: Node, v: Node) -> Option<Node> {
         self.access(u);
         let ru = self.l.first(u);
@@ -111,4 +316,15 @@ where
         let rv = self.l.first(v);
         (ru == rv).then_some(lca)
     }
+
+    fn counters(&self) -> Counters {
+        self.counters + self.l.counters()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        // The lists count their own struct, which is already inside ours
+        std::mem::size_of::<Self>() - std::mem::size_of::<L>()
+            + self.l.memory_bytes()
+            + self.parent.capacity() * std::mem::size_of::<Node>()
+    }
 }
@@ -1,17 +1,24 @@
 //! Implementation of order-based list data structures, with aggregated data.
 
 use std::fmt::Debug;
-use std::ops::RangeBounds;
+use std::marker::PhantomData;
+use std::ops::{Range, RangeBounds};
 
+use crate::metrics::Counters;
+
+pub mod small;
 pub mod splay;
 pub mod treap;
 
 pub type Idx = usize;
 
 /// This data structure stores multiple ordered lists of values. Use keys in 0..n.
+///
+/// The length of a list must fit in a usize, which in practice can't be exceeded, as all nodes are kept in memory.
+/// [Lists::try_concat_checked] checks it anyway.
 pub trait Lists<Ag = ()>
 where
-    Ag: AggregatedData,
+    Ag: Monoid,
     Self: Debug,
 {
     /// Returned when the node doesn't exist.
@@ -25,18 +32,24 @@ where
     {
         let data = data.into_iter();
         let mut lists = Self::new(data.size_hint().0);
-        for (i, data) in data.enumerate() {
-            lists.create(data);
-            if i > 0 {
-                lists.concat(i - 1, i);
-            }
-        }
+        lists.build_balanced(data);
         lists
     }
     /// Create a new node with given data. Returns its index, which increases from 0.
     fn create(&mut self, data: Ag::Data) -> Idx;
-    /// Number of nodes in all lists.
+    /// Create a new node for each data, with the same indices as calling [Lists::create] for each in order.
+    /// Returns the range of indices created.
+    fn create_many(&mut self, data: impl ExactSizeIterator<Item = Ag::Data>) -> Range<Idx> {
+        let start = self.total_size();
+        for data in data {
+            self.create(data);
+        }
+        start..self.total_size()
+    }
+    /// Number of nodes in all lists. See [Lists::num_lists] for the number of lists.
     fn total_size(&self) -> usize;
+    /// Number of distinct lists, that is, of roots. Each node starts in its own list.
+    fn num_lists(&self) -> usize;
 
     // OPERATIONS
     // They panic if the node doesn't exist.
@@ -84,7 +97,17 @@ where
         search_strategy: impl FnMut(SearchData<'_, Ag>) -> SearchDirection,
     ) -> Idx;
     /// K-th element in the list containing u. (0-indexed)
+    /// Returns EMPTY if k is out of range (k >= len) or if u is EMPTY, which [Lists::next] relies on.
     fn find_kth(&mut self, u: Idx, k: usize) -> Idx;
+    /// K-th element counting only within the given range of the list containing u. (0-indexed)
+    /// Returns EMPTY if k is past the end of the range or the list.
+    fn find_kth_in_range(&mut self, u: Idx, range: impl RangeBounds<usize>, k: usize) -> Idx {
+        let [l, r] = range_to_lr(range, || self.len(u));
+        match l.checked_add(k) {
+            Some(pos) if pos < r => self.find_kth(u, pos),
+            _ => Self::EMPTY,
+        }
+    }
     /// First element in the list containing u.
     fn first(&mut self, u: Idx) -> Idx {
         self.find_kth(u, 0)
@@ -95,16 +118,74 @@ where
     fn total_agg(&mut self, u: Idx) -> Ag {
         self.range_agg(u, ..)
     }
+    /// Same as [Lists::total_agg], for a [Fallible] aggregate. Returns the first error found when merging.
+    fn try_total_agg<T>(&mut self, u: Idx) -> Result<T, T::Error>
+    where
+        T: TryAggregatedData,
+        Ag: Into<Result<T, T::Error>>,
+    {
+        self.total_agg(u).into()
+    }
     /// Aggregated data of a range of the list containing u. (0-indexed)
     fn range_agg(&mut self, u: Idx, range: impl RangeBounds<usize>) -> Ag {
         let [l, r] = range_to_lr(range, || self.len(u));
         self.range_agg_lr(u, l, r)
     }
+    /// Aggregated data of the first k elements of the list containing u. The whole list if k is at least its length.
+    fn prefix_agg(&mut self, u: Idx, k: usize) -> Ag {
+        self.range_agg(u, ..k)
+    }
+    /// Aggregated data of the last k elements of the list containing u. The whole list if k is at least its length.
+    fn suffix_agg(&mut self, u: Idx, k: usize) -> Ag {
+        let len = self.len(u);
+        self.range_agg(u, len.saturating_sub(k)..)
+    }
     /// XXX: Use range_agg(u, l..r) instead.
     fn range_agg_lr(&mut self, u: Idx, l: usize, r: usize) -> Ag;
 
+    /// Applies f to the data of every element of the list containing u, in order.
+    /// Implementations should take O(n), instead of a separate O(lg n) [Lists::mutate_data] for each element.
+    fn map_in_place(&mut self, u: Idx, mut f: impl FnMut(&mut Ag::Data)) {
+        for k in 0..self.len(u) {
+            let v = self.find_kth(u, k);
+            self.mutate_data(v, &mut f);
+        }
+    }
+
+    /// Calls f with the index and data of every element of the list containing u, in order.
+    /// Implementations should take O(n), instead of walking the list with [Lists::next].
+    fn for_each(&mut self, u: Idx, mut f: impl FnMut(Idx, &Ag::Data)) {
+        let mut v = self.first(u);
+        while v != Self::EMPTY {
+            f(v, self.data(v));
+            v = self.next(v);
+        }
+    }
+
     /// Concats the lists containing u and v. Returns the new root.
+    /// The first element of the result is the first of u's list, or of v's if u is EMPTY, whatever the new root is.
+    /// [crate::euler_tour_tree::ETT] relies on this to keep the root of a tree when linking.
     fn concat(&mut self, u: Idx, v: Idx) -> Idx;
+    /// Same as [Lists::concat], but fails without changing anything if the length of the result wouldn't fit in a usize.
+    fn try_concat_checked(&mut self, u: Idx, v: Idx) -> Result<Idx, LengthOverflow> {
+        if u != Self::EMPTY && v != Self::EMPTY && !self.on_same_list(u, v) {
+            let (left, right) = (self.len(u), self.len(v));
+            if left.checked_add(right).is_none() {
+                return Err(LengthOverflow { left, right });
+            }
+        }
+        Ok(self.concat(u, v))
+    }
+    /// Inserts the whole list containing src into the list containing dst, so it starts at position pos (0 <= pos <= len).
+    /// Panics if src and dst are on the same list. Returns the new root.
+    fn splice(&mut self, dst: Idx, pos: usize, src: Idx) -> Idx {
+        assert!(
+            !self.on_same_list(dst, src),
+            "Can't splice a list into itself"
+        );
+        let (_, left, right) = self.split(dst, ..pos);
+        self.concat_all([left, src, right])
+    }
     /// Concats all given lists. Returns the new root.
     fn concat_all(&mut self, all: impl IntoIterator<Item = Idx>) -> Idx {
         let mut u = Self::EMPTY;
@@ -113,16 +194,166 @@ where
         }
         u
     }
+    /// Same as [Lists::concat_all], but with a new node with data sep() between each two consecutive lists. EMPTY parts
+    /// are skipped, so they don't get separators. Returns the new root.
+    fn join_with_separator(
+        &mut self,
+        parts: impl IntoIterator<Item = Idx>,
+        mut sep: impl FnMut() -> Ag::Data,
+    ) -> Idx {
+        let mut u = Self::EMPTY;
+        for v in parts.into_iter().filter(|&v| v != Self::EMPTY) {
+            if u != Self::EMPTY {
+                let s = self.create(sep());
+                u = self.concat(u, s);
+            }
+            u = self.concat(u, v);
+        }
+        u
+    }
+    /// Removes from the list containing u each element equal to the one before it, as given by eq, so each run of equal
+    /// elements is collapsed into its first one. Nodes are never freed, so the removed elements of each run are left as
+    /// a list of their own. Returns the new root. Takes O(n + k lg n), where k is the number of runs removed.
+    fn dedup_adjacent(&mut self, u: Idx, eq: impl Fn(&Ag::Data, &Ag::Data) -> bool) -> Idx {
+        if u == Self::EMPTY {
+            return Self::EMPTY;
+        }
+        // Runs of removed elements, as (element before the run, last element of the run)
+        let mut runs: Vec<(Idx, Idx)> = vec![];
+        let (mut prev, mut prev_idx) = (None::<Ag::Data>, Self::EMPTY);
+        self.for_each(u, |v, data| {
+            if prev.as_ref().is_some_and(|p| eq(p, data)) {
+                match runs.last_mut() {
+                    Some((_, last)) if *last == prev_idx => *last = v,
+                    _ => runs.push((prev_idx, v)),
+                }
+            }
+            prev = Some(data.clone());
+            prev_idx = v;
+        });
+        let mut root = self.root(u);
+        for (before, last) in runs {
+            let (left, _) = self.split_after(before);
+            let (_, right) = self.split_after(last);
+            root = self.concat(left, right);
+        }
+        root
+    }
+    /// Creates a single list with the given elements, in order, and returns its root, or EMPTY if there are none.
+    /// Nodes get consecutive indices, as with [Lists::create].
+    /// Implementations should take O(n), instead of concatenating the elements one by one.
+    fn build_balanced(&mut self, elements: impl IntoIterator<Item = Ag::Data>) -> Idx {
+        let mut u = Self::EMPTY;
+        for data in elements {
+            let v = self.create(data);
+            u = self.concat(u, v);
+        }
+        u
+    }
     /// Splits the list containing u with the given range from the left and right parts. Returns (left, range, right), which may be EMPTY.
     fn split(&mut self, u: Idx, range: impl RangeBounds<usize>) -> (Idx, Idx, Idx) {
         let [l, r] = range_to_lr(range, || self.len(u));
         self.split_lr(u, l, r)
     }
+    /// Splits the list containing u right after u. Returns (left, right), where left contains u and everything before it.
+    fn split_after(&mut self, u: Idx) -> (Idx, Idx) {
+        let order = self.order(u);
+        let (_, left, right) = self.split(u, ..=order);
+        (left, right)
+    }
+    /// Splits the list containing u into its longest prefix whose aggregate satisfies pred, and the rest.
+    /// Returns (left, right), which may be EMPTY. pred must be monotone: once false for a prefix, it is false for all
+    /// longer ones. The split point is found with a single descent.
+    fn split_where(&mut self, u: Idx, pred: impl Fn(&Ag) -> bool) -> (Idx, Idx) {
+        let mut k = 0;
+        if pred(&Ag::default()) {
+            // Aggregate of everything before the current subtree, which always satisfies pred.
+            let mut before = Ag::default();
+            let found = self.find_element(u, |d| {
+                let left = before.clone().merge(d.left_agg.clone());
+                if !pred(&left) {
+                    return SearchDirection::Left;
+                }
+                let current = left.merge(Ag::from(d.current_data));
+                if !pred(&current) {
+                    SearchDirection::Found
+                } else {
+                    before = current;
+                    SearchDirection::Right
+                }
+            });
+            k = if found == Self::EMPTY {
+                self.len(u)
+            } else {
+                self.order(found)
+            };
+        }
+        let (_, left, right) = self.split(u, ..k);
+        (left, right)
+    }
+    /// Replaces everything after u in its list with the list starting at v, which must be the first of a different
+    /// list, or EMPTY. Returns the first node of the part that was removed, or EMPTY if u was the last.
+    fn replace_after(&mut self, u: Idx, v: Idx) -> Idx {
+        let (_, after) = self.split_after(u);
+        let after = self.first(after);
+        self.concat(u, v);
+        after
+    }
     /// Returns range from l (inclusive) to r (exclusive)
     /// XXX: Use range_agg(u, l..r) instead.
     fn split_lr(&mut self, u: Idx, l: usize, r: usize) -> (Idx, Idx, Idx);
-    /// Reverse the whole list containing u.
-    fn reverse(&mut self, u: Idx);
+    /// Reverse the whole list containing u. Note that u can be any node of the list, and the whole list is
+    /// reversed regardless of its position. To reverse only part of the list, use [Lists::reverse_range].
+    /// Only available if the aggregate can be reversed.
+    fn reverse(&mut self, u: Idx)
+    where
+        Ag: AggregatedData;
+    /// Same as [Lists::reverse], named to make clear the whole list containing any_node is reversed.
+    fn reverse_list(&mut self, any_node: Idx)
+    where
+        Ag: AggregatedData,
+    {
+        self.reverse(any_node);
+    }
+    /// Reverse only the given range of the list containing u. Returns the new root.
+    fn reverse_range(&mut self, u: Idx, range: impl RangeBounds<usize>) -> Idx
+    where
+        Ag: AggregatedData,
+    {
+        let (left, middle, right) = self.split(u, range);
+        if middle != Self::EMPTY {
+            self.reverse(middle);
+        }
+        self.concat_all([left, middle, right])
+    }
+    /// View of the list containing u, which can be iterated in order as (index, data) pairs.
+    /// Finding the order needs mutable access, as some lists restructure on reads, but the view itself only borrows.
+    /// Takes O(n lg n) to build.
+    fn view(&mut self, u: Idx) -> ListView<'_, Self, Ag>
+    where
+        Self: Sized,
+    {
+        let mut nodes = Vec::with_capacity(self.len(u));
+        let mut v = self.first(u);
+        while v != Self::EMPTY {
+            nodes.push(v);
+            v = self.next(v);
+        }
+        ListView {
+            lists: self,
+            nodes,
+            _phantom: PhantomData,
+        }
+    }
+    /// Counters of internal operations. Always zero unless the `metrics` feature is enabled.
+    fn counters(&self) -> Counters {
+        Counters::default()
+    }
+    /// Estimate of the memory used, in bytes, including this struct. Heap memory owned by the data is not counted.
+    fn memory_bytes(&self) -> usize {
+        std::mem::size_of_val(self)
+            + self.total_size() * (std::mem::size_of::<Ag::Data>() + std::mem::size_of::<Ag>())
+    }
     /// Optional function that might verify the structure is correctly built. Used for testing.
     fn check_all(&self)
     where
@@ -131,19 +362,124 @@ where
     }
 }
 
-pub trait AggregatedData: Debug + Clone + Default {
-    // Should Data::reverse exist? Not necessary for us, but more generic.
+/// Data aggregated over ranges of a list. The default value is the identity, the aggregate of an empty range.
+/// This is enough for lists that are never reversed, see [AggregatedData] for the ones that are. [treap::Treaps] and
+/// [splay::Splays] accept it with the [Irreversible] reversal.
+pub trait Monoid: Debug + Clone + Default {
+    /// Data of a single item. For positional data, like a digit, the data doesn't change when the list is reversed,
+    /// only the aggregate does. Directional data, like an edge from a to b, should implement
+    /// [AggregatedData::reverse_data].
     type Data: Debug + Clone;
     /// Create aggregated data from a single data item
     fn from(data: &Self::Data) -> Self;
     /// Merge two aggregated data items. The other item contains data of some (not necessarily all) items to the right.
     fn merge(self, right: Self) -> Self;
+}
+
+/// [Monoid] that can also be reversed, which is needed by [Lists::reverse] and by the trees built on lists,
+/// as rerooting reverses paths.
+pub trait AggregatedData: Monoid {
     /// Reverses the aggregated data. Used for reversing the list.
     fn reverse(self) -> Self;
+    /// Reverses the data of a single item, called when the list containing it is reversed. Must be consistent with
+    /// [AggregatedData::reverse], that is, `from(reversed data) == from(data).reverse()`. By default data isn't changed.
+    ///
+    /// Reversals are lazy, so [Lists::data] may return data in its old orientation. [Lists::mutate_data],
+    /// [Lists::map_in_place] and [Lists::find_element] always see the current one.
+    fn reverse_data(_data: &mut Self::Data) {}
+}
+
+/// How a list implementation reverses aggregates and data when flipping a subtree. It is a type parameter, so lists
+/// of a plain [Monoid] don't carry any reversing code.
+pub trait Reversal<Ag: Monoid> {
+    /// Whether lists can be reversed, checked by [Lists::reverse].
+    const REVERSIBLE: bool;
+    /// Same as [AggregatedData::reverse].
+    fn reverse(ag: Ag) -> Ag;
+    /// Same as [AggregatedData::reverse_data].
+    fn reverse_data(data: &mut Ag::Data);
+}
+
+/// [Reversal] of [AggregatedData], used by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reversible;
+
+impl<Ag: AggregatedData> Reversal<Ag> for Reversible {
+    const REVERSIBLE: bool = true;
+    fn reverse(ag: Ag) -> Ag {
+        ag.reverse()
+    }
+    fn reverse_data(data: &mut Ag::Data) {
+        Ag::reverse_data(data)
+    }
+}
+
+/// [Reversal] of any [Monoid], for lists that are never reversed. Nothing is flipped, so it is never called.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Irreversible;
+
+impl<Ag: Monoid> Reversal<Ag> for Irreversible {
+    const REVERSIBLE: bool = false;
+    fn reverse(_: Ag) -> Ag {
+        unreachable!("Irreversible lists are never flipped")
+    }
+    fn reverse_data(_: &mut Ag::Data) {
+        unreachable!("Irreversible lists are never flipped")
+    }
+}
+
+/// Same as [AggregatedData], but merging may fail, for example to detect invalid configurations.
+/// Lists keep aggregates of every subtree, so they can't fail on changes. Use [Fallible] as the aggregate instead,
+/// which keeps the error until it is queried, for example with [Lists::try_total_agg].
+pub trait TryAggregatedData: Debug + Clone + Default {
+    type Data: Debug + Clone;
+    type Error: Debug + Clone;
+    /// Create aggregated data from a single data item
+    fn from(data: &Self::Data) -> Self;
+    /// Merge two aggregated data items, or fail if they can't be merged.
+    fn try_merge(self, right: Self) -> Result<Self, Self::Error>;
+    /// Reverses the aggregated data. Used for reversing the list.
+    fn reverse(self) -> Self;
+}
+
+/// Aggregate of a [TryAggregatedData], which is either the aggregate or the error of the first merge that failed.
+/// Merging with an error keeps it, so an error in any part of a list is the error of the whole list.
+#[derive(Debug, Clone)]
+pub struct Fallible<T: TryAggregatedData>(pub Result<T, T::Error>);
+
+impl<T: TryAggregatedData> Default for Fallible<T> {
+    fn default() -> Self {
+        Self(Ok(T::default()))
+    }
+}
+
+impl<T: TryAggregatedData> From<Fallible<T>> for Result<T, T::Error> {
+    fn from(ag: Fallible<T>) -> Self {
+        ag.0
+    }
+}
+
+impl<T: TryAggregatedData> Monoid for Fallible<T> {
+    type Data = T::Data;
+    fn from(data: &Self::Data) -> Self {
+        Self(Ok(T::from(data)))
+    }
+    fn merge(self, right: Self) -> Self {
+        Self(match (self.0, right.0) {
+            (Ok(left), Ok(right)) => left.try_merge(right),
+            (Err(e), _) | (_, Err(e)) => Err(e),
+        })
+    }
+}
+
+impl<T: TryAggregatedData> AggregatedData for Fallible<T> {
+    fn reverse(self) -> Self {
+        Self(self.0.map(T::reverse))
+    }
 }
 
 #[derive(Debug)]
-pub struct SearchData<'a, Ag: AggregatedData> {
+pub struct SearchData<'a, Ag: Monoid> {
     /// Data of the current node being looked at.
     pub current_data: &'a Ag::Data,
     /// Aggregated data of the left subtree.
@@ -152,6 +488,53 @@ pub struct SearchData<'a, Ag: AggregatedData> {
     pub right_agg: &'a Ag,
 }
 
+/// Borrowed list, see [Lists::view].
+#[derive(Debug)]
+pub struct ListView<'a, L, Ag> {
+    lists: &'a L,
+    nodes: Vec<Idx>,
+    _phantom: PhantomData<Ag>,
+}
+
+impl<L, Ag> ListView<'_, L, Ag> {
+    /// Indices of the nodes of the list, in order.
+    pub fn nodes(&self) -> &[Idx] {
+        &self.nodes
+    }
+}
+
+impl<'a, L: Lists<Ag>, Ag: Monoid + 'a> IntoIterator for ListView<'a, L, Ag> {
+    type Item = (Idx, &'a Ag::Data);
+    type IntoIter = ListViewIter<'a, L, Ag>;
+    fn into_iter(self) -> Self::IntoIter {
+        ListViewIter {
+            lists: self.lists,
+            nodes: self.nodes.into_iter(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Iterator over a [ListView].
+pub struct ListViewIter<'a, L, Ag> {
+    lists: &'a L,
+    nodes: std::vec::IntoIter<Idx>,
+    _phantom: PhantomData<Ag>,
+}
+
+impl<'a, L: Lists<Ag>, Ag: Monoid + 'a> Iterator for ListViewIter<'a, L, Ag> {
+    type Item = (Idx, &'a Ag::Data);
+    fn next(&mut self) -> Option<Self::Item> {
+        let u = self.nodes.next()?;
+        Some((u, self.lists.data(u)))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.nodes.size_hint()
+    }
+}
+
+impl<'a, L: Lists<Ag>, Ag: Monoid + 'a> ExactSizeIterator for ListViewIter<'a, L, Ag> {}
+
 #[derive(Debug)]
 pub enum SearchDirection {
     Found,
@@ -160,6 +543,118 @@ pub enum SearchDirection {
     Right,
 }
 
+/// Returned by [Lists::try_concat_checked] when the total length of the lists doesn't fit in a usize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthOverflow {
+    pub left: usize,
+    pub right: usize,
+}
+
+/// A broken invariant of the trees in [treap::Treaps] or [splay::Splays].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// node and parent disagree on whether node is a child of parent, or one of them points outside the structure.
+    BrokenParent { node: Idx, parent: Idx },
+    /// node is on a loop of pointers, so it is either reached twice or not reachable from any root.
+    Cycle { node: Idx },
+    /// The stored subtree size of node is not the number of nodes in its subtree.
+    WrongSize {
+        node: Idx,
+        stored: usize,
+        actual: usize,
+    },
+    /// The stored aggregate of node is not the aggregate of its subtree.
+    WrongAggregate { node: Idx },
+    /// The lazy flip of node is wrong, as the stored aggregate of its parent only matches if its subtree is reversed.
+    LazyFlip { node: Idx },
+    /// node has a larger priority than its parent. Only for treaps.
+    HeapOrder { node: Idx, parent: Idx },
+}
+
+/// All invariant violations found in a structure, see [treap::Treaps::invariants] and [splay::Splays::invariants].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InvariantReport {
+    pub violations: Vec<Violation>,
+}
+
+impl InvariantReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+    /// Checks the pointers of the trees on nodes 0..n, given by parent and child. Returns the nodes reachable from the
+    /// roots with children before their parents, so the stored sizes and aggregates can be checked bottom up.
+    pub(crate) fn check_pointers(
+        &mut self,
+        n: usize,
+        parent: impl Fn(Idx) -> Idx,
+        child: impl Fn(Idx) -> [Idx; 2],
+    ) -> Vec<Idx> {
+        let mut stack = vec![];
+        for u in 0..n {
+            let p = parent(u);
+            if p == usize::MAX {
+                stack.push(u);
+            } else if p >= n || !child(p).contains(&u) {
+                self.violations
+                    .push(Violation::BrokenParent { node: u, parent: p });
+            }
+        }
+        let mut seen = vec![false; n];
+        let mut order = vec![];
+        while let Some(u) = stack.pop() {
+            if seen[u] {
+                self.violations.push(Violation::Cycle { node: u });
+                continue;
+            }
+            seen[u] = true;
+            order.push(u);
+            for c in child(u) {
+                if c == usize::MAX {
+                    continue;
+                }
+                if c >= n {
+                    self.violations
+                        .push(Violation::BrokenParent { node: c, parent: u });
+                    continue;
+                }
+                if parent(c) != u {
+                    self.violations
+                        .push(Violation::BrokenParent { node: c, parent: u });
+                }
+                stack.push(c);
+            }
+        }
+        self.violations.extend(
+            (0..n)
+                .filter(|&u| !seen[u])
+                .map(|node| Violation::Cycle { node }),
+        );
+        order.reverse();
+        order
+    }
+    /// Compares the stored aggregate of node with the actual one, given by agg(flip_l, flip_r) where the flags reverse
+    /// the aggregates of the children. If it only matches with one of them reversed, that child's flip is wrong.
+    pub(crate) fn check_agg<Ag: Monoid + Eq>(
+        &mut self,
+        node: Idx,
+        stored: &Ag,
+        [l, r]: [Idx; 2],
+        agg: impl Fn(bool, bool) -> Ag,
+    ) {
+        if *stored == agg(false, false) {
+            return;
+        }
+        self.violations
+            .push(if l != usize::MAX && *stored == agg(true, false) {
+                Violation::LazyFlip { node: l }
+            } else if r != usize::MAX && *stored == agg(false, true) {
+                Violation::LazyFlip { node: r }
+            } else {
+                Violation::WrongAggregate { node }
+            });
+    }
+}
+
 fn range_to_lr(range: impl RangeBounds<usize>, len: impl FnOnce() -> usize) -> [usize; 2] {
     use std::ops::Bound::*;
     let start = match range.start_bound() {
@@ -175,9 +670,12 @@ fn range_to_lr(range: impl RangeBounds<usize>, len: impl FnOnce() -> usize) -> [
     [start, end]
 }
 
-impl AggregatedData for () {
+impl Monoid for () {
     type Data = ();
     fn from(_: &Self::Data) -> Self {}
     fn merge(self, _: Self) -> Self {}
+}
+
+impl AggregatedData for () {
     fn reverse(self) -> Self {}
 }
@@ -0,0 +1,275 @@
+//! Lists stored inline in a fixed-size array, without any heap allocation, for structures with few nodes.
+//! Nodes are kept in doubly linked lists, so most operations take time linear on the size of the list.
+
+use std::fmt::{Debug, Formatter};
+
+use super::{AggregatedData, Idx, Lists, Monoid, SearchData, SearchDirection};
+
+#[derive(Debug, Clone)]
+struct Node<Ag: Monoid> {
+    data: Ag::Data,
+    prev: Idx,
+    next: Idx,
+}
+
+/// Data structure that maintains multiple lists of at most N nodes in total, stored inline.
+/// The root of a list is its first node. Creating more than N nodes panics.
+pub struct SmallLists<Ag: Monoid = (), const N: usize = 16> {
+    nodes: [Option<Node<Ag>>; N],
+    len: usize,
+}
+
+impl<Ag: Monoid, const N: usize> Debug for SmallLists<Ag, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SmallLists:")?;
+        for u in (0..self.len).filter(|&u| self.node(u).prev == Self::EMPTY) {
+            write!(f, " [")?;
+            let mut v = u;
+            while v != Self::EMPTY {
+                write!(f, "{v}({:?}) ", self.node(v).data)?;
+                v = self.node(v).next;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+impl<Ag: Monoid, const N: usize> SmallLists<Ag, N> {
+    fn node(&self, u: Idx) -> &Node<Ag> {
+        self.nodes[u].as_ref().expect("node doesn't exist")
+    }
+    fn node_mut(&mut self, u: Idx) -> &mut Node<Ag> {
+        self.nodes[u].as_mut().expect("node doesn't exist")
+    }
+    fn last(&self, mut u: Idx) -> Idx {
+        while self.node(u).next != Self::EMPTY {
+            u = self.node(u).next;
+        }
+        u
+    }
+    /// Nodes of the list starting at first, in order.
+    fn iter_from(&self, first: Idx) -> impl Iterator<Item = Idx> + '_ {
+        std::iter::successors((first != Self::EMPTY).then_some(first), |&v| {
+            let next = self.node(v).next;
+            (next != Self::EMPTY).then_some(next)
+        })
+    }
+    /// Unlinks u from the node before it, if any.
+    fn cut_before(&mut self, u: Idx) {
+        if u != Self::EMPTY {
+            let prev = std::mem::replace(&mut self.node_mut(u).prev, Self::EMPTY);
+            if prev != Self::EMPTY {
+                self.node_mut(prev).next = Self::EMPTY;
+            }
+        }
+    }
+}
+
+impl<Ag: Monoid, const N: usize> Lists<Ag> for SmallLists<Ag, N> {
+    const EMPTY: Idx = usize::MAX;
+
+    fn new(capacity: usize) -> Self {
+        assert!(capacity <= N, "SmallLists can hold at most {N} nodes");
+        Self {
+            nodes: std::array::from_fn(|_| None),
+            len: 0,
+        }
+    }
+
+    fn create(&mut self, data: Ag::Data) -> Idx {
+        assert!(self.len < N, "SmallLists can hold at most {N} nodes");
+        self.nodes[self.len] = Some(Node {
+            data,
+            prev: Self::EMPTY,
+            next: Self::EMPTY,
+        });
+        self.len += 1;
+        self.len - 1
+    }
+
+    fn total_size(&self) -> usize {
+        self.len
+    }
+
+    fn num_lists(&self) -> usize {
+        (0..self.len)
+            .filter(|&u| self.node(u).prev == Self::EMPTY)
+            .count()
+    }
+
+    fn root(&mut self, mut u: Idx) -> Idx {
+        if u == Self::EMPTY {
+            return Self::EMPTY;
+        }
+        while self.node(u).prev != Self::EMPTY {
+            u = self.node(u).prev;
+        }
+        u
+    }
+
+    fn data(&self, u: Idx) -> &Ag::Data {
+        &self.node(u).data
+    }
+
+    fn mutate_data(&mut self, u: Idx, f: impl FnOnce(&mut Ag::Data)) {
+        f(&mut self.node_mut(u).data)
+    }
+
+    fn order(&mut self, u: Idx) -> usize {
+        if u == Self::EMPTY {
+            return 0;
+        }
+        std::iter::successors(Some(u), |&v| {
+            let prev = self.node(v).prev;
+            (prev != Self::EMPTY).then_some(prev)
+        })
+        .count()
+            - 1
+    }
+
+    fn is_first(&mut self, u: Idx) -> bool {
+        u == Self::EMPTY || self.node(u).prev == Self::EMPTY
+    }
+
+    fn next(&mut self, u: Idx) -> Idx {
+        if u == Self::EMPTY {
+            return Self::EMPTY;
+        }
+        self.node(u).next
+    }
+
+    fn prev(&mut self, u: Idx) -> Idx {
+        if u == Self::EMPTY {
+            return Self::EMPTY;
+        }
+        self.node(u).prev
+    }
+
+    fn find_element(
+        &mut self,
+        u: Idx,
+        mut search_strategy: impl FnMut(SearchData<'_, Ag>) -> SearchDirection,
+    ) -> Idx {
+        // As in a tree where every node is the right child of the previous one.
+        let left_agg = Ag::default();
+        // Aggregate of each node and all nodes after it, filled in a single backward pass.
+        let mut suffix: [Ag; N] = std::array::from_fn(|_| Ag::default());
+        let first = self.root(u);
+        let last = if first == Self::EMPTY {
+            Self::EMPTY
+        } else {
+            self.last(first)
+        };
+        let (mut w, mut agg) = (last, Ag::default());
+        while w != Self::EMPTY {
+            agg = Ag::from(&self.node(w).data).merge(agg);
+            suffix[w] = agg.clone();
+            w = self.node(w).prev;
+        }
+        let mut v = first;
+        while v != Self::EMPTY {
+            let next = self.node(v).next;
+            let right_agg = if next == Self::EMPTY {
+                &left_agg
+            } else {
+                &suffix[next]
+            };
+            match search_strategy(SearchData {
+                current_data: &self.node(v).data,
+                left_agg: &left_agg,
+                right_agg,
+            }) {
+                SearchDirection::Found => return v,
+                SearchDirection::NotFound => return Self::EMPTY,
+                SearchDirection::Left => panic!("Should never go left"),
+                SearchDirection::Right => v = self.node(v).next,
+            }
+        }
+        Self::EMPTY
+    }
+
+    fn find_kth(&mut self, u: Idx, k: usize) -> Idx {
+        let first = self.root(u);
+        self.iter_from(first).nth(k).unwrap_or(Self::EMPTY)
+    }
+
+    fn len(&mut self, u: Idx) -> usize {
+        let first = self.root(u);
+        self.iter_from(first).count()
+    }
+
+    fn range_agg_lr(&mut self, u: Idx, l: usize, r: usize) -> Ag {
+        let first = self.root(u);
+        self.iter_from(first)
+            .take(r)
+            .skip(l)
+            .fold(Ag::default(), |agg, v| {
+                agg.merge(Ag::from(&self.node(v).data))
+            })
+    }
+
+    fn map_in_place(&mut self, u: Idx, mut f: impl FnMut(&mut Ag::Data)) {
+        let mut v = self.root(u);
+        while v != Self::EMPTY {
+            f(&mut self.node_mut(v).data);
+            v = self.node(v).next;
+        }
+    }
+
+    fn for_each(&mut self, u: Idx, mut f: impl FnMut(Idx, &Ag::Data)) {
+        let first = self.root(u);
+        for v in self.iter_from(first) {
+            f(v, &self.node(v).data);
+        }
+    }
+
+    fn concat(&mut self, u: Idx, v: Idx) -> Idx {
+        let (u, v) = (self.root(u), self.root(v));
+        if u == Self::EMPTY || u == v {
+            return v;
+        } else if v == Self::EMPTY {
+            return u;
+        }
+        let last = self.last(u);
+        self.node_mut(last).next = v;
+        self.node_mut(v).prev = last;
+        u
+    }
+
+    fn split_lr(&mut self, u: Idx, l: usize, r: usize) -> (Idx, Idx, Idx) {
+        if u == Self::EMPTY {
+            return (Self::EMPTY, Self::EMPTY, Self::EMPTY);
+        }
+        let len = self.len(u);
+        assert!(
+            l <= r && r <= len,
+            "Invalid range {l}..{r} of list of size {len}"
+        );
+        let first = self.root(u);
+        let (at_l, at_r) = (self.find_kth(u, l), self.find_kth(u, r));
+        self.cut_before(at_r);
+        self.cut_before(at_l);
+        let left = if l > 0 { first } else { Self::EMPTY };
+        let mid = if l < r { at_l } else { Self::EMPTY };
+        (left, mid, at_r)
+    }
+
+    fn reverse(&mut self, u: Idx)
+    where
+        Ag: AggregatedData,
+    {
+        let mut v = self.root(u);
+        while v != Self::EMPTY {
+            let n = self.node_mut(v);
+            std::mem::swap(&mut n.prev, &mut n.next);
+            Ag::reverse_data(&mut n.data);
+            v = n.prev;
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        // Everything is inline.
+        std::mem::size_of::<Self>()
+    }
+}
@@ -1,12 +1,19 @@
-use std::fmt::{Debug, Formatter};
+use std::{
+    fmt::{Debug, Formatter},
+    marker::PhantomData,
+    ops::Range,
+};
 
 use derivative::Derivative;
 
-use crate::lists::SearchData;
+use crate::{
+    lists::SearchData,
+    metrics::{count, Counters},
+};
 
 use super::{
     treap::{node2_fmt, node_fmt},
-    AggregatedData, Idx, Lists,
+    AggregatedData, Idx, InvariantReport, Lists, Monoid, Reversal, Reversible, Violation,
 };
 
 // Used for debugging
@@ -15,7 +22,7 @@ use super::treap::PrettyIdx as I;
 
 #[derive(Derivative)]
 #[derivative(Debug)]
-struct Node<Ag: AggregatedData> {
+struct Node<Ag: Monoid> {
     /// Data in this node
     data: Ag::Data,
     #[derivative(Debug(format_with = "node2_fmt"))]
@@ -30,22 +37,9 @@ struct Node<Ag: AggregatedData> {
     subtree_size: usize,
 }
 
-impl<Ag: AggregatedData> Node<Ag> {
+impl<Ag: Monoid> Node<Ag> {
     const EMPTY: Idx = usize::MAX;
 
-    /// To use this, guarantee: you don't look at data, you don't modify anything.
-    unsafe fn null() -> Self {
-        Self {
-            // We never use this
-            data: std::mem::zeroed(),
-            child: [Node::<Ag>::EMPTY; 2],
-            parent: Node::<Ag>::EMPTY,
-            d_flip: false,
-            subtree_agg: Ag::default(),
-            subtree_size: 0,
-        }
-    }
-
     fn new(data: Ag::Data) -> Self {
         Self {
             child: [Self::EMPTY; 2],
@@ -74,34 +68,74 @@ impl<Ag: AggregatedData> Node<Ag> {
         }
     }
 
-    fn agg(&self) -> Ag {
+    fn agg<V: Reversal<Ag>>(&self) -> Ag {
         let mut ag = self.subtree_agg.clone();
         if self.d_flip {
-            ag = ag.reverse()
+            ag = V::reverse(ag)
         }
         ag
     }
 }
 
-pub struct Splays<Ag: AggregatedData = ()> {
+/// Data structure that maintains multiple splay trees.
+/// Every operation, even reads, may splay and restructure the trees, so positions held across operations
+/// may be invalidated. See [super::treap::Cursor] for a cursor over [super::treap::Treaps].
+/// V says how flipped subtrees are reversed, use [super::Irreversible] if Ag is only a [Monoid].
+pub struct Splays<Ag: Monoid = (), V = Reversible> {
     n: Vec<Node<Ag>>,
-    null: Node<Ag>,
+    /// Aggregate of an empty subtree, so it can be borrowed like the aggregate of any node.
+    empty_agg: Ag,
+    counters: Counters,
+    _reversal: PhantomData<V>,
 }
 
-impl<Ag: AggregatedData> Debug for Splays<Ag> {
+impl<Ag: Monoid, V: Reversal<Ag>> Debug for Splays<Ag, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut seen = vec![false; self.n.len()];
-        for u in 0..self.n.len() {
-            if self.n[u].parent == Self::EMPTY {
-                self.print_rec(f, u, false, &mut seen)?;
-                write!(f, " --- ")?;
-            }
+        for u in self.roots_by_min_index() {
+            self.print_rec(f, u, false, &mut seen)?;
+            write!(f, " --- ")?;
         }
         Ok(())
     }
 }
 
-impl<Ag: AggregatedData> Splays<Ag> {
+impl<Ag: Monoid, V: Reversal<Ag>> Splays<Ag, V> {
+    /// Number of node slots ever created.
+    pub fn allocated_nodes(&self) -> usize {
+        self.n.len()
+    }
+    /// Number of nodes in use. Nodes are never freed, so this is the same as [Splays::allocated_nodes].
+    pub fn live_nodes(&self) -> usize {
+        self.allocated_nodes()
+    }
+    /// Toggles the lazy flip of u, without updating anything else. Only used to test invariant checks.
+    #[cfg(feature = "debug-hooks")]
+    #[doc(hidden)]
+    pub fn toggle_flip_unchecked(&mut self, u: Idx) {
+        self.n[u].d_flip ^= true;
+    }
+    /// Roots of all trees, ordered by the smallest index in each tree, so
+    /// that debug output doesn't depend on which node happens to be the root.
+    fn roots_by_min_index(&self) -> Vec<Idx> {
+        let mut listed = vec![false; self.n.len()];
+        let mut roots = vec![];
+        for u in 0..self.n.len() {
+            let mut r = u;
+            // Bounded so a broken parent loop doesn't hang; print_rec reports it.
+            for _ in 0..self.n.len() {
+                if self.n[r].parent == Self::EMPTY {
+                    break;
+                }
+                r = self.n[r].parent;
+            }
+            if !listed[r] {
+                listed[r] = true;
+                roots.push(r);
+            }
+        }
+        roots
+    }
     fn print_rec(
         &self,
         f: &mut Formatter<'_>,
@@ -132,10 +166,25 @@ impl<Ag: AggregatedData> Splays<Ag> {
         write!(f, ")")
     }
 
-    fn n(&self, u: Idx) -> &Node<Ag> {
-        (u == Self::EMPTY)
-            .then_some(&self.null)
-            .unwrap_or_else(|| &self.n[u])
+    fn node(&self, u: Idx) -> Option<&Node<Ag>> {
+        (u != Self::EMPTY).then(|| &self.n[u])
+    }
+
+    fn parent(&self, u: Idx) -> Idx {
+        self.node(u).map_or(Self::EMPTY, |nu| nu.parent)
+    }
+
+    fn size(&self, u: Idx) -> usize {
+        self.node(u).map_or(0, |nu| nu.subtree_size)
+    }
+
+    /// Aggregate of the subtree of u, not taking into account its lazy flip.
+    fn subtree_agg(&self, u: Idx) -> &Ag {
+        self.node(u).map_or(&self.empty_agg, |nu| &nu.subtree_agg)
+    }
+
+    fn agg(&self, u: Idx) -> Ag {
+        self.node(u).map_or_else(Ag::default, |nu| nu.agg::<V>())
     }
 
     fn unlaze_flip(&mut self, u: Idx) {
@@ -146,7 +195,8 @@ impl<Ag: AggregatedData> Splays<Ag> {
         if nu.d_flip {
             nu.d_flip = false;
             nu.child.swap(0, 1);
-            nu.subtree_agg = nu.subtree_agg.clone().reverse();
+            nu.subtree_agg = V::reverse(nu.subtree_agg.clone());
+            V::reverse_data(&mut nu.data);
             for v in nu.child {
                 if v != Self::EMPTY {
                     self.n[v].d_flip ^= true;
@@ -155,16 +205,54 @@ impl<Ag: AggregatedData> Splays<Ag> {
         }
     }
 
+    /// First element of the list containing u, found without splaying so checks don't change the structure.
+    #[cfg(debug_assertions)]
+    fn first_without_splay(&self, mut u: Idx) -> Idx {
+        if u == Self::EMPTY {
+            return Self::EMPTY;
+        }
+        while self.n[u].parent != Self::EMPTY {
+            u = self.n[u].parent;
+        }
+        let mut flip = false;
+        loop {
+            let [l, _] = self.n[u].child(flip);
+            if l == Self::EMPTY {
+                return u;
+            }
+            flip ^= self.n[u].d_flip;
+            u = l;
+        }
+    }
+
+    /// Pushes down all flips in the subtree of root. Returns its nodes with parents before children.
+    fn unlaze_all(&mut self, root: Idx) -> Vec<Idx> {
+        let mut top_down = vec![root];
+        let mut i = 0;
+        while i < top_down.len() {
+            self.unlaze_flip(top_down[i]);
+            top_down.extend(
+                self.n[top_down[i]]
+                    .child
+                    .into_iter()
+                    .filter(|&c| c != Self::EMPTY),
+            );
+            i += 1;
+        }
+        top_down
+    }
+
     fn update(&mut self, u: Idx) {
         if u == Self::EMPTY {
             return;
         }
         self.unlaze_flip(u);
-        let nu = self.n(u);
-        let [l, r] = nu.child;
-        let [nl, nr] = [self.n(l), self.n(r)];
-        let agg = nl.agg().merge(Ag::from(&nu.data)).merge(nr.agg());
-        let size = nl.subtree_size + 1 + nr.subtree_size;
+        let [l, r] = self.n[u].child;
+        let agg = self
+            .agg(l)
+            .merge(Ag::from(&self.n[u].data))
+            .merge(self.agg(r));
+        let size = self.size(l) + 1 + self.size(r);
         self.n[u].subtree_agg = agg;
         self.n[u].subtree_size = size;
     }
@@ -195,6 +283,7 @@ impl<Ag: AggregatedData> Splays<Ag> {
     }
 
     fn rotate_up(&mut self, u: Idx) {
+        count!(self.counters.rotations);
         let p = self.n[u].parent;
         assert!(p != Self::EMPTY, "Can't rotate_up root");
         self.unlaze_flip(p);
@@ -231,6 +320,7 @@ impl<Ag: AggregatedData> Splays<Ag> {
 
     // Directly doing the two rotations is faster than doing them one at a time, though it works exactly the same.
     fn zig_zig(&mut self, u: Idx, p: Idx, pp: Idx, u_side: usize) {
+        count!(self.counters.rotations, 2);
         // Same as:
         // self.rotate_up(p);
         // self.rotate_up(u);
@@ -247,6 +337,7 @@ impl<Ag: AggregatedData> Splays<Ag> {
         }
     }
     fn zig_zag(&mut self, u: Idx, p: Idx, pp: Idx, u_side: usize) {
+        count!(self.counters.rotations, 2);
         // Same as:
         // self.rotate_up(u);
         // self.rotate_up(u);
@@ -270,7 +361,7 @@ impl<Ag: AggregatedData> Splays<Ag> {
         }
         loop {
             let p = self.n[u].parent;
-            let pp = self.n(p).parent;
+            let pp = self.parent(p);
             self.unlaze_flip(pp);
             self.unlaze_flip(p);
             self.unlaze_flip(u);
@@ -293,60 +384,64 @@ impl<Ag: AggregatedData> Splays<Ag> {
         }
     }
 
-    fn check_rec(&self, u: Idx, seen: &mut Vec<bool>) -> (usize, Ag)
+    /// Checks the structure of all trees, returning every violation found instead of panicking, in O(n).
+    pub fn invariants(&self) -> InvariantReport
     where
         Ag: Eq,
     {
-        if u == Self::EMPTY {
-            return (0, Ag::default());
-        }
-        assert!(!seen[u], "Loop including vx {u}");
-        seen[u] = true;
-        let [l, r] = self.n[u].child;
-        let (mut tot_sz, mut tot_agg) = self.check_rec(l, seen);
-        for x in [l, r] {
-            if x != Self::EMPTY {
-                assert_eq!(self.n[x].parent, u, "parent of {x} wrong (not {u})");
+        let mut report = InvariantReport::default();
+        let order = report.check_pointers(self.n.len(), |u| self.n[u].parent, |u| self.n[u].child);
+        // Size and aggregate of each subtree, taking into account the node's own flip.
+        let mut sub = vec![(0, Ag::default()); self.n.len()];
+        let get = |sub: &[(usize, Ag)], u: Idx| sub.get(u).cloned().unwrap_or_default();
+        for u in order {
+            let [l, r] = self.n[u].child;
+            let ((sl, al), (sr, ar)) = (get(&sub, l), get(&sub, r));
+            let size = sl + 1 + sr;
+            let rev = |ag: &Ag, flip: bool| {
+                if flip {
+                    V::reverse(ag.clone())
+                } else {
+                    ag.clone()
+                }
+            };
+            let agg = |fl, fr| {
+                rev(&al, fl)
+                    .merge(Ag::from(&self.n[u].data))
+                    .merge(rev(&ar, fr))
+            };
+            if size != self.n[u].subtree_size {
+                report.violations.push(Violation::WrongSize {
+                    node: u,
+                    stored: self.n[u].subtree_size,
+                    actual: size,
+                });
             }
+            report.check_agg(u, &self.n[u].subtree_agg, [l, r], agg);
+            sub[u] = (size, rev(&agg(false, false), self.n[u].d_flip));
         }
-        tot_sz += 1;
-        tot_agg = tot_agg.merge(Ag::from(&self.n[u].data));
-        let (sz, agg) = self.check_rec(r, seen);
-        tot_sz += sz;
-        tot_agg = tot_agg.merge(agg);
-        assert_eq!(tot_sz, self.n[u].subtree_size, "size calculated wrong");
-        assert_eq!(tot_agg, self.n[u].subtree_agg, "agg calculated wrong");
-        if self.n[u].d_flip {
-            tot_agg = tot_agg.reverse();
-        }
-        seen[u] = false;
-        (tot_sz, tot_agg)
+        report
     }
     /// Used for debugging, makes sure the structure of the tree is correct.
-    #[allow(dead_code)]
     fn check_all(&self)
     where
         Ag: Eq,
     {
         log::trace!("Checking {self:?}");
-        let mut seen = vec![false; self.n.len()];
-        for u in 0..self.n.len() {
-            let p = self.n[u].parent;
-            if p != Self::EMPTY {
-                assert!(self.n[p].child.contains(&u));
-            }
-            self.check_rec(u, &mut seen);
-        }
+        let report = self.invariants();
+        assert!(report.is_ok(), "{report:?}");
     }
 }
 
-impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
+impl<Ag: Monoid, V: Reversal<Ag>> Lists<Ag> for Splays<Ag, V> {
     const EMPTY: Idx = Node::<Ag>::EMPTY;
 
     fn new(capacity: usize) -> Self {
         Self {
             n: Vec::with_capacity(capacity),
-            null: unsafe { Node::null() },
+            empty_agg: Ag::default(),
+            counters: Counters::default(),
+            _reversal: PhantomData,
         }
     }
 
@@ -355,9 +450,20 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
         self.n.len() - 1
     }
 
+    fn create_many(&mut self, data: impl ExactSizeIterator<Item = Ag::Data>) -> Range<Idx> {
+        let start = self.n.len();
+        self.n.reserve(data.len());
+        self.n.extend(data.map(Node::new));
+        start..self.n.len()
+    }
+
     fn total_size(&self) -> usize {
         self.n.len()
     }
+    fn num_lists(&self) -> usize {
+        // Not maintained, so it takes O(n).
+        self.n.iter().filter(|n| n.parent == Self::EMPTY).count()
+    }
     fn check_all(&self)
     where
         Ag: Eq,
@@ -365,6 +471,10 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
         self.check_all();
     }
 
+    fn counters(&self) -> Counters {
+        self.counters
+    }
+
     fn root(&mut self, mut u: Idx) -> Idx {
         if u == Self::EMPTY {
             return Self::EMPTY;
@@ -392,7 +502,7 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
         self.splay(u);
         self.splay(v);
         // Splaying v made u not the root
-        self.n(u).parent != Self::EMPTY
+        self.parent(u) != Self::EMPTY
     }
 
     fn data(&self, u: Idx) -> &Ag::Data {
@@ -406,8 +516,11 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
     }
 
     fn order(&mut self, u: Idx) -> usize {
+        if u == Self::EMPTY {
+            return 0;
+        }
         self.splay(u);
-        self.n(self.n(u).child(false)[0]).subtree_size
+        self.size(self.n[u].child(false)[0])
     }
 
     fn find_element(
@@ -425,8 +538,8 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
             let [l, r] = self.n[u].child;
             let st = SearchData {
                 current_data: &self.n[u].data,
-                left_agg: &self.n(l).subtree_agg,
-                right_agg: &self.n(r).subtree_agg,
+                left_agg: self.subtree_agg(l),
+                right_agg: self.subtree_agg(r),
             };
             use super::SearchDirection::*;
             prev_u = u;
@@ -444,13 +557,13 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
 
     fn find_kth(&mut self, mut u: Idx, mut k: usize) -> Idx {
         self.splay(u);
-        if self.n(u).subtree_size <= k {
+        if self.size(u) <= k {
             return Self::EMPTY;
         }
         loop {
             self.unlaze_flip(u);
             let [l, r] = self.n[u].child;
-            let szl = self.n(l).subtree_size;
+            let szl = self.size(l);
             if szl == k {
                 break;
             } else if szl > k {
@@ -471,22 +584,96 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
 
     fn len(&mut self, u: Idx) -> usize {
         self.splay(u);
-        self.n(u).subtree_size
+        self.size(u)
     }
 
     fn range_agg_lr(&mut self, u: Idx, l: usize, r: usize) -> Ag {
         let (nl, nm, nr) = self.split_lr(u, l, r);
-        let ans = self.n(nm).subtree_agg.clone();
+        let ans = self.subtree_agg(nm).clone();
         self.concat_all([nl, nm, nr]);
         ans
     }
 
     fn total_agg(&mut self, u: Idx) -> Ag {
         self.splay(u);
-        self.n(u).subtree_agg.clone()
+        self.subtree_agg(u).clone()
+    }
+
+    fn suffix_agg(&mut self, u: Idx, mut k: usize) -> Ag {
+        self.splay(u);
+        // Descend from the root, taking whole right subtrees until k elements are covered, so the length isn't needed.
+        let mut ans = Ag::default();
+        let mut cur = u;
+        let mut last = u;
+        while cur != Self::EMPTY && k > 0 {
+            self.unlaze_flip(cur);
+            last = cur;
+            let [l, r] = self.n[cur].child;
+            let szr = self.size(r);
+            if k <= szr {
+                cur = r;
+            } else {
+                ans = Ag::from(&self.n[cur].data).merge(self.agg(r)).merge(ans);
+                k -= szr + 1;
+                cur = l;
+            }
+        }
+        // Splay last seen vertex
+        self.splay(last);
+        ans
+    }
+
+    fn map_in_place(&mut self, u: Idx, mut f: impl FnMut(&mut Ag::Data)) {
+        if u == Self::EMPTY {
+            return;
+        }
+        self.splay(u);
+        let top_down = self.unlaze_all(u);
+        // In order traversal, no flips are left
+        let mut stack = vec![];
+        let mut cur = u;
+        loop {
+            while cur != Self::EMPTY {
+                stack.push(cur);
+                cur = self.n[cur].child[0];
+            }
+            let Some(v) = stack.pop() else {
+                break;
+            };
+            f(&mut self.n[v].data);
+            cur = self.n[v].child[1];
+        }
+        // And update in reverse
+        for v in top_down.into_iter().rev() {
+            self.update(v);
+        }
+    }
+
+    fn for_each(&mut self, u: Idx, mut f: impl FnMut(Idx, &Ag::Data)) {
+        if u == Self::EMPTY {
+            return;
+        }
+        self.splay(u);
+        // Unlazing keeps the aggregates correct, so nothing needs to be updated
+        self.unlaze_all(u);
+        let mut stack = vec![];
+        let mut cur = u;
+        loop {
+            while cur != Self::EMPTY {
+                stack.push(cur);
+                cur = self.n[cur].child[0];
+            }
+            let Some(v) = stack.pop() else {
+                break;
+            };
+            f(v, &self.n[v].data);
+            cur = self.n[v].child[1];
+        }
     }
 
     fn concat(&mut self, u: Idx, v: Idx) -> Idx {
+        #[cfg(debug_assertions)]
+        let first = self.first_without_splay(if u == Self::EMPTY { v } else { u });
         let v = self.first(v);
         self.splay(u);
         // v == null or they are in the same list already
@@ -494,12 +681,21 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
             return u;
         }
         assert_eq!(self.replace_child(v, false, u), Self::EMPTY);
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            self.first_without_splay(v),
+            first,
+            "concat must keep the first element of the left list"
+        );
         v
     }
 
     fn split_lr(&mut self, u: Idx, l: usize, r: usize) -> (Idx, Idx, Idx) {
+        debug_assert!(l <= r, "invalid range {l}..{r}");
+        // middle is splayed to the root, so its left child is exactly the first l nodes, which may be none.
         let middle = self.find_kth(u, l);
         if middle == Self::EMPTY {
+            // l >= len, everything is on the left
             return (u, Self::EMPTY, Self::EMPTY);
         }
         let left = self.replace_child(middle, false, Self::EMPTY);
@@ -513,7 +709,29 @@ impl<Ag: AggregatedData> Lists<Ag> for Splays<Ag> {
         (left, middle, right)
     }
 
-    fn reverse(&mut self, u: Idx) {
+    fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.n.capacity() * std::mem::size_of::<Node<Ag>>()
+    }
+
+    fn split_after(&mut self, u: Idx) -> (Idx, Idx) {
+        self.splay(u);
+        let right = self.replace_child(u, true, Self::EMPTY);
+        (u, right)
+    }
+
+    fn replace_after(&mut self, u: Idx, v: Idx) -> Idx {
+        // v is first, so it is already the root if it was the last node found with first.
+        self.splay(v);
+        self.splay(u);
+        let after = self.replace_child(u, true, v);
+        self.first(after)
+    }
+
+    fn reverse(&mut self, u: Idx)
+    where
+        Ag: AggregatedData,
+    {
+        assert!(V::REVERSIBLE, "Irreversible lists can't be reversed");
         self.splay(u);
         if u != Self::EMPTY {
             self.n[u].d_flip ^= true;
@@ -1,13 +1,21 @@
 //! A Treap, or Cartesian Tree, is a BST that is also a heap for randomized priorities.
 //! It is expected to be balanced and have logarithmic time complexity for all operations.
 
-use std::fmt::{Debug, Display, Formatter};
+use std::{
+    fmt::{Debug, Display, Formatter},
+    marker::PhantomData,
+    ops::{Range, RangeBounds},
+};
 
 use debug_tree::{add_branch_to, add_leaf_to, AsTree, TreeBuilder};
 use derivative::Derivative;
 use rand::{rngs, Rng, SeedableRng};
 
-use super::{AggregatedData, Idx, Lists, SearchData, SearchDirection};
+use super::{
+    range_to_lr, AggregatedData, Idx, InvariantReport, Lists, Monoid, Reversal, Reversible,
+    SearchData, SearchDirection, Violation,
+};
+use crate::metrics::{count, Counters};
 
 pub(crate) fn node_fmt(u: &Idx, f: &mut Formatter) -> std::fmt::Result {
     if *u == usize::MAX {
@@ -44,7 +52,7 @@ use PrettyIdx as I;
 
 #[derive(Derivative)]
 #[derivative(Debug)]
-struct Node<Ag: AggregatedData> {
+struct Node<Ag: Monoid> {
     #[derivative(Debug(format_with = "node_fmt"))]
     parent: Idx,
     /// Left and right child
@@ -61,13 +69,15 @@ struct Node<Ag: AggregatedData> {
     priority: u32,
 }
 
-impl<Ag: AggregatedData> Node<Ag> {
+impl<Ag: Monoid> Node<Ag> {
+    const EMPTY: Idx = usize::MAX;
+
     fn new(data: Ag::Data, priority: u32) -> Self {
         Self {
             ag_data: Ag::from(&data),
             data,
-            child: [Treaps::<Ag>::EMPTY; 2],
-            parent: Treaps::<Ag>::EMPTY,
+            child: [Self::EMPTY; 2],
+            parent: Self::EMPTY,
             size: 1,
             priority,
             flip_subtree: false,
@@ -79,39 +89,46 @@ impl<Ag: AggregatedData> Node<Ag> {
 }
 
 /// Data structure that maintains multiple treaps.
-pub struct Treaps<Ag: AggregatedData = ()> {
+/// V says how flipped subtrees are reversed, use [super::Irreversible] if Ag is only a [Monoid].
+pub struct Treaps<Ag: Monoid = (), R = rngs::StdRng, V = Reversible> {
     nodes: Vec<Node<Ag>>,
-    rng: rngs::StdRng,
+    /// Number of distinct treaps, kept up to date by create, concat and the splits.
+    lists: usize,
+    /// Generates the priorities of new nodes.
+    rng: R,
+    counters: Counters,
+    _reversal: PhantomData<V>,
 }
 
-impl<Ag: AggregatedData> Debug for Treaps<Ag> {
+impl<Ag: Monoid, R: Rng + SeedableRng, V: Reversal<Ag>> Debug for Treaps<Ag, R, V> {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
         let builder = TreeBuilder::new();
         add_branch_to!(builder, "Treaps");
+        // Lists are printed ordered by their smallest index, independently of
+        // which node is the root.
+        let mut listed = vec![false; self.nodes.len()];
         for u in 0..self.nodes.len() {
-            if self.nodes[u].parent == Self::EMPTY {
-                self.tree_inorder_dbg(u, &builder);
+            let r = self.root(u);
+            if !listed[r] {
+                listed[r] = true;
+                self.tree_inorder_dbg(r, &builder);
             }
         }
         writeln!(f, "{}", builder.string())
     }
 }
 
-trait ReverseF: AggregatedData {
-    fn reverseif(self, flipped: bool) -> Self;
-}
-
-impl<T: AggregatedData> ReverseF for T {
-    fn reverseif(self, flipped: bool) -> Self {
-        if flipped {
-            self.reverse()
-        } else {
-            self
+impl<Ag: Monoid, R: Rng + SeedableRng, V: Reversal<Ag>> Treaps<Ag, R, V> {
+    /// New Treaps with given capacity, using rng to generate priorities.
+    pub fn with_rng(capacity: usize, rng: R) -> Self {
+        Self {
+            nodes: Vec::with_capacity(capacity),
+            lists: 0,
+            rng,
+            counters: Counters::default(),
+            _reversal: PhantomData,
         }
     }
-}
-
-impl<Ag: AggregatedData> Treaps<Ag> {
     #[allow(dead_code)]
     fn tree_preorder_dbg<T: AsTree>(&self, u: Idx, tree: &T) {
         let nu = &self.nodes[u];
@@ -139,6 +156,14 @@ impl<Ag: AggregatedData> Treaps<Ag> {
             self.tree_inorder_dbg(nu.child[1], tree);
         }
     }
+    /// Reverses ag if flipped is set.
+    fn reverse_if(ag: Ag, flipped: bool) -> Ag {
+        if flipped {
+            V::reverse(ag)
+        } else {
+            ag
+        }
+    }
     fn n(&self, u: Idx) -> Option<&Node<Ag>> {
         // Even safer than just self.nodes.get(u)
         if u == Self::EMPTY {
@@ -191,11 +216,7 @@ impl<Ag: AggregatedData> Treaps<Ag> {
     }
     fn ag_data(&self, u: Idx, flipped: bool) -> Ag {
         self.n(u).map_or_else(Ag::default, |n| {
-            if n.flip(flipped) {
-                n.ag_data.clone().reverse()
-            } else {
-                n.ag_data.clone()
-            }
+            Self::reverse_if(n.ag_data.clone(), n.flip(flipped))
         })
     }
     /// Call when children are changed. Not necessary for flip_subtree if using the methods above.
@@ -205,13 +226,33 @@ impl<Ag: AggregatedData> Treaps<Ag> {
         }
         let f = self.nodes[u].flip_subtree;
         let [l, r] = self.child(u, false);
-        self.nodes[u].size = self.size(l) + 1 + self.size(r);
+        debug_assert!(
+            self.size(l)
+                .checked_add(self.size(r))
+                .and_then(|s| s.checked_add(1))
+                .is_some(),
+            "Treap invariant violated: size of {u} overflows"
+        );
+        let size = self.size(l) + 1 + self.size(r);
+        // Catch corrupted structure early, before it spreads to the ancestors
+        debug_assert!(
+            size <= self.nodes.len(),
+            "Treap invariant violated: size {size} of {u} is larger than the {} nodes",
+            self.nodes.len()
+        );
+        debug_assert!(
+            [l, r]
+                .iter()
+                .all(|&c| c == Self::EMPTY || self.nodes[c].parent == u),
+            "Treap invariant violated: children of {u} don't point back to it"
+        );
+        self.nodes[u].size = size;
         let ag = self
             .ag_data(l, f)
-            .merge(Ag::from(&self.nodes[u].data))
-            .merge(self.ag_data(r, f))
-            // agg may actually stored reverse if the flip bit is set.
-            .reverseif(f);
+            .merge(Self::reverse_if(Ag::from(&self.nodes[u].data), f))
+            .merge(self.ag_data(r, f));
+        // agg may actually stored reverse if the flip bit is set.
+        let ag = Self::reverse_if(ag, f);
         self.nodes[u].ag_data = ag;
         u
     }
@@ -222,8 +263,9 @@ impl<Ag: AggregatedData> Treaps<Ag> {
         let n = &mut self.nodes[u];
         if n.flip_subtree {
             n.flip_subtree = false;
-            n.ag_data = n.ag_data.clone().reverse();
+            n.ag_data = V::reverse(n.ag_data.clone());
             n.child.swap(0, 1);
+            V::reverse_data(&mut n.data);
             for c in n.child {
                 if c != Self::EMPTY {
                     self.nodes[c].flip_subtree ^= true;
@@ -231,8 +273,25 @@ impl<Ag: AggregatedData> Treaps<Ag> {
             }
         }
     }
+    /// Pushes down all flips in the subtree of root. Returns its nodes with parents before children.
+    fn unlaze_all(&mut self, root: Idx) -> Vec<Idx> {
+        let mut top_down = vec![root];
+        let mut i = 0;
+        while i < top_down.len() {
+            self.unlaze_flip(top_down[i]);
+            top_down.extend(
+                self.nodes[top_down[i]]
+                    .child
+                    .into_iter()
+                    .filter(|&c| c != Self::EMPTY),
+            );
+            i += 1;
+        }
+        top_down
+    }
     /// (First k, rest)
     fn split_k(&mut self, u: Idx, k: usize) -> (Idx, Idx) {
+        count!(self.counters.splits);
         self.unlaze_flip(u);
         if u == Self::EMPTY || k == 0 {
             // If k == 0 the node is fully returned on the right
@@ -243,13 +302,18 @@ impl<Ag: AggregatedData> Treaps<Ag> {
         if k <= szl {
             self.change_left(u, Self::EMPTY, false);
             let (ll, lr) = self.split_k(l, k);
-            (ll, self.concat(lr, u))
+            (ll, self.concat_inner(lr, u))
         } else {
             self.change_right(u, Self::EMPTY, false);
             let (rl, rr) = self.split_k(r, k - szl - 1);
-            (self.concat(u, rl), rr)
+            (self.concat_inner(u, rl), rr)
         }
     }
+    /// Updates the number of lists after a list was split into the given parts, some of which may be EMPTY.
+    fn count_split(&mut self, parts: impl IntoIterator<Item = Idx>) {
+        let parts = parts.into_iter().filter(|&p| p != Self::EMPTY).count();
+        self.lists += parts.saturating_sub(1);
+    }
     #[allow(dead_code)]
     fn dbg_node(&self, u: Idx) {
         if u == Self::EMPTY {
@@ -261,6 +325,7 @@ impl<Ag: AggregatedData> Treaps<Ag> {
         }
     }
     fn concat_inner(&mut self, u: Idx, v: Idx) -> Idx {
+        count!(self.counters.concats);
         self.unlaze_flip(u);
         self.unlaze_flip(v);
         if u == Self::EMPTY {
@@ -301,35 +366,326 @@ impl<Ag: AggregatedData> Treaps<Ag> {
             let rag = self.range_agg_lr_inner(r, ql.saturating_sub(szl + 1), qr - (szl + 1));
             ag = ag.merge(rag);
         }
-        ag.reverseif(self.nodes[u].flip_subtree)
+        Self::reverse_if(ag, self.nodes[u].flip_subtree)
     }
 }
 
-impl<Ag: AggregatedData> Lists<Ag> for Treaps<Ag> {
+/// Queries that don't restructure the treap, so they only need shared access.
+impl<Ag: Monoid, R: Rng + SeedableRng, V: Reversal<Ag>> Treaps<Ag, R, V> {
+    /// Same as [Lists::root], but through a shared reference.
+    pub fn root(&self, mut u: Idx) -> Idx {
+        while self.parent(u) != Self::EMPTY {
+            u = self.nodes[u].parent;
+        }
+        u
+    }
+    /// Same as [Lists::total_agg], but through a shared reference.
+    pub fn total_agg(&self, u: Idx) -> Ag {
+        self.ag_data(self.root(u), false)
+    }
+    /// Same as [Lists::range_agg], but through a shared reference.
+    pub fn range_agg(&self, u: Idx, range: impl RangeBounds<usize>) -> Ag {
+        let u = self.root(u);
+        let [l, r] = range_to_lr(range, || self.size(u));
+        self.range_agg_lr_inner(u, l, r)
+    }
+    /// Same as [Treaps::range_agg], but also returns the parts that were merged, in list order, for debugging.
+    /// Each part is a node and the positions of the list it covers, which are either all of its subtree, whose
+    /// aggregate is used as is, or only the node itself. There are O(lg n) parts.
+    pub fn range_agg_explain(
+        &self,
+        u: Idx,
+        range: impl RangeBounds<usize>,
+    ) -> (Ag, Vec<(Idx, Range<usize>)>) {
+        let u = self.root(u);
+        let [l, r] = range_to_lr(range, || self.size(u));
+        let mut parts = vec![];
+        self.range_parts(u, l, r, false, 0, &mut parts);
+        (self.range_agg_lr_inner(u, l, r), parts)
+    }
+    /// Same descent as [Treaps::range_agg_lr_inner], but in list order. The subtree of u starts at position offset.
+    fn range_parts(
+        &self,
+        u: Idx,
+        ql: usize,
+        qr: usize,
+        flipped: bool,
+        offset: usize,
+        parts: &mut Vec<(Idx, Range<usize>)>,
+    ) {
+        if u == Self::EMPTY || ql >= qr {
+            return;
+        }
+        if ql == 0 && qr >= self.size(u) {
+            parts.push((u, offset..offset + self.size(u)));
+            return;
+        }
+        let [l, r] = self.child(u, flipped);
+        let flipped = self.nodes[u].flip(flipped);
+        let szl = self.size(l);
+        if ql < szl {
+            self.range_parts(l, ql, qr.min(szl), flipped, offset, parts);
+        }
+        if ql <= szl && qr > szl {
+            parts.push((u, offset + szl..offset + szl + 1));
+        }
+        if qr > szl + 1 {
+            let ql = ql.saturating_sub(szl + 1);
+            self.range_parts(r, ql, qr - (szl + 1), flipped, offset + szl + 1, parts);
+        }
+    }
+    /// Same as [Lists::order], but through a shared reference.
+    pub fn order(&self, u: Idx) -> usize {
+        if u == Self::EMPTY {
+            return 0;
+        }
+        let mut path = vec![];
+        let mut cur = u;
+        while cur != Self::EMPTY {
+            path.push(cur);
+            cur = self.parent(cur);
+        }
+        path.reverse();
+        let mut flipped = false;
+        let mut ord = 0;
+        for i in 0..(path.len() - 1) {
+            let [p, u] = [path[i], path[i + 1]];
+            let [l, r] = self.child(p, flipped);
+            if u == r {
+                ord += self.size(l) + 1
+            }
+            flipped = self.nodes[p].flip(flipped);
+        }
+        let [ul, _] = self.child(u, flipped);
+        let ord = ord + self.size(ul);
+        #[cfg(debug_assertions)]
+        if self.size(path[0]) <= 64 {
+            assert_eq!(ord, self.order_bottom_up(u), "order of {u} is inconsistent");
+        }
+        ord
+    }
+    /// Same as [Treaps::order], but going up from u instead, to cross-check flip handling.
+    #[cfg(debug_assertions)]
+    fn order_bottom_up(&self, u: Idx) -> usize {
+        // Parity of the flips strictly above the current node
+        let mut above = false;
+        let mut p = self.parent(u);
+        while p != Self::EMPTY {
+            above ^= self.nodes[p].flip_subtree;
+            p = self.parent(p);
+        }
+        let [ul, _] = self.child(u, above);
+        let mut ord = self.size(ul);
+        let mut cur = u;
+        while self.parent(cur) != Self::EMPTY {
+            let p = self.parent(cur);
+            above ^= self.nodes[p].flip_subtree;
+            let [l, r] = self.child(p, above);
+            if cur == r {
+                ord += self.size(l) + 1;
+            }
+            cur = p;
+        }
+        ord
+    }
+    /// Same as [Lists::find_kth], but through a shared reference.
+    pub fn find_kth(&self, mut u: Idx, mut k: usize) -> Idx {
+        let mut flipped = false;
+        u = self.root(u);
+        while u != Self::EMPTY {
+            let [l, r] = self.child(u, flipped);
+            flipped = self.nodes[u].flip(flipped);
+            let sl = self.size(l);
+            if sl > k {
+                u = l;
+            } else if sl == k {
+                return u;
+            } else {
+                k -= sl + 1;
+                u = r;
+            }
+        }
+        Self::EMPTY
+    }
+    /// Same as [Lists::len], but through a shared reference.
+    pub fn len(&self, u: Idx) -> usize {
+        if u == Self::EMPTY {
+            0
+        } else {
+            let u = self.root(u);
+            self.nodes[u].size
+        }
+    }
+    /// Cursor over the list containing u, starting at u.
+    pub fn cursor(&self, u: Idx) -> Cursor<'_, Ag, R, V> {
+        Cursor {
+            t: self,
+            u,
+            k: self.order(u),
+        }
+    }
+    /// Checks the structure of all treaps, returning every violation found instead of panicking, in O(n).
+    pub fn invariants(&self) -> InvariantReport
+    where
+        Ag: Eq,
+    {
+        let mut report = InvariantReport::default();
+        let order = report.check_pointers(
+            self.nodes.len(),
+            |u| self.nodes[u].parent,
+            |u| self.nodes[u].child,
+        );
+        // Size and aggregate of each subtree, as seen from a parent without flips, like ag_data(u, false).
+        let mut sub = vec![(0, Ag::default()); self.nodes.len()];
+        let get = |sub: &[(usize, Ag)], u: Idx| sub.get(u).cloned().unwrap_or_default();
+        for u in order {
+            let nu = &self.nodes[u];
+            let f = nu.flip_subtree;
+            for c in nu.child {
+                if c < self.nodes.len() && self.nodes[c].priority > nu.priority {
+                    report
+                        .violations
+                        .push(Violation::HeapOrder { node: c, parent: u });
+                }
+            }
+            // Same as recalc
+            let [l, r] = self.child(u, false);
+            let ((sl, al), (sr, ar)) = (get(&sub, l), get(&sub, r));
+            let size = sl + 1 + sr;
+            let agg = |fl: bool, fr: bool| {
+                let ag = Self::reverse_if(al.clone(), f ^ fl)
+                    .merge(Self::reverse_if(Ag::from(&nu.data), f))
+                    .merge(Self::reverse_if(ar.clone(), f ^ fr));
+                Self::reverse_if(ag, f)
+            };
+            if size != nu.size {
+                report.violations.push(Violation::WrongSize {
+                    node: u,
+                    stored: nu.size,
+                    actual: size,
+                });
+            }
+            report.check_agg(u, &nu.ag_data, [l, r], agg);
+            sub[u] = (size, Self::reverse_if(agg(false, false), f));
+        }
+        report
+    }
+    /// Overwrites the subtree size of u, without any checks. Only used to test invariant checks.
+    #[cfg(feature = "debug-hooks")]
+    #[doc(hidden)]
+    pub fn set_size_unchecked(&mut self, u: Idx, size: usize) {
+        self.nodes[u].size = size;
+    }
+    /// Number of node slots ever created.
+    pub fn allocated_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+    /// Number of nodes in use. Nodes are never freed, so this is the same as [Treaps::allocated_nodes].
+    pub fn live_nodes(&self) -> usize {
+        self.allocated_nodes()
+    }
+}
+
+/// Iterates over the nodes of a list of [Treaps], in order. Each step takes O(lg n).
+/// As treaps don't restructure on reads, it can be held while making other queries through shared references.
+/// There is no such cursor for [super::splay::Splays], as any operation on them, even reads, may splay and invalidate positions.
+pub struct Cursor<'a, Ag: Monoid, R = rngs::StdRng, V = Reversible> {
+    t: &'a Treaps<Ag, R, V>,
+    u: Idx,
+    k: usize,
+}
+
+impl<Ag: Monoid, R: Rng + SeedableRng, V: Reversal<Ag>> Iterator for Cursor<'_, Ag, R, V> {
+    type Item = Idx;
+
+    fn next(&mut self) -> Option<Idx> {
+        let v = self.t.find_kth(self.u, self.k);
+        (v != Treaps::<Ag, R, V>::EMPTY).then(|| {
+            self.k += 1;
+            v
+        })
+    }
+}
+
+impl<Ag: Monoid, R: Rng + SeedableRng, V: Reversal<Ag>> Lists<Ag> for Treaps<Ag, R, V> {
     const EMPTY: Idx = usize::MAX;
 
     fn new(capacity: usize) -> Self {
-        Self {
-            nodes: Vec::with_capacity(capacity),
-            rng: rand::rngs::StdRng::seed_from_u64(2012),
-        }
+        Self::with_rng(capacity, R::seed_from_u64(2012))
     }
 
     fn create(&mut self, data: Ag::Data) -> Idx {
         let idx = self.nodes.len();
         self.nodes.push(Node::new(data, self.rng.gen()));
+        self.lists += 1;
         idx
     }
 
+    fn create_many(&mut self, data: impl ExactSizeIterator<Item = Ag::Data>) -> Range<Idx> {
+        let start = self.nodes.len();
+        self.nodes.reserve(data.len());
+        self.nodes
+            .extend(data.map(|data| Node::new(data, self.rng.gen())));
+        self.lists += self.nodes.len() - start;
+        start..self.nodes.len()
+    }
+
     fn total_size(&self) -> usize {
         self.nodes.len()
     }
 
-    fn root(&mut self, mut u: Idx) -> Idx {
-        while self.parent(u) != Self::EMPTY {
-            u = self.nodes[u].parent;
+    fn build_balanced(&mut self, elements: impl IntoIterator<Item = Ag::Data>) -> Idx {
+        // Builds the treap of the priorities in O(n) by keeping its right spine, where each new element is inserted.
+        let mut spine: Vec<Idx> = vec![];
+        let mut created = 0;
+        for data in elements {
+            let u = self.create(data);
+            created += 1;
+            let mut left = Self::EMPTY;
+            while let Some(&top) = spine.last() {
+                if self.nodes[top].priority >= self.nodes[u].priority {
+                    break;
+                }
+                left = spine.pop().unwrap();
+            }
+            if left != Self::EMPTY {
+                self.nodes[u].child[0] = left;
+                self.nodes[left].parent = u;
+            }
+            if let Some(&top) = spine.last() {
+                self.nodes[top].child[1] = u;
+                self.nodes[u].parent = top;
+            }
+            spine.push(u);
         }
-        u
+        let Some(&root) = spine.first() else {
+            return Self::EMPTY;
+        };
+        self.lists -= created - 1;
+        // Children before parents
+        let mut order = vec![root];
+        let mut i = 0;
+        while i < order.len() {
+            order.extend(
+                self.nodes[order[i]]
+                    .child
+                    .into_iter()
+                    .filter(|&c| c != Self::EMPTY),
+            );
+            i += 1;
+        }
+        for u in order.into_iter().rev() {
+            self.recalc(u);
+        }
+        root
+    }
+
+    fn num_lists(&self) -> usize {
+        self.lists
+    }
+
+    fn root(&mut self, u: Idx) -> Idx {
+        Treaps::root(self, u)
     }
 
     fn data(&self, u: Idx) -> &Ag::Data {
@@ -337,6 +693,16 @@ impl<Ag: AggregatedData> Lists<Ag> for Treaps<Ag> {
     }
 
     fn mutate_data(&mut self, mut u: Idx, f: impl FnOnce(&mut Ag::Data)) {
+        // Push pending flips down to u, so f sees its data in the current orientation
+        let mut path = vec![];
+        let mut p = u;
+        while p != Self::EMPTY {
+            path.push(p);
+            p = self.parent(p);
+        }
+        for p in path.into_iter().rev() {
+            self.unlaze_flip(p);
+        }
         f(&mut self.nodes[u].data);
         while u != Self::EMPTY {
             self.recalc(u);
@@ -345,28 +711,7 @@ impl<Ag: AggregatedData> Lists<Ag> for Treaps<Ag> {
     }
 
     fn order(&mut self, u: Idx) -> usize {
-        if u == Self::EMPTY {
-            return 0;
-        }
-        let mut path = vec![];
-        let mut cur = u;
-        while cur != Self::EMPTY {
-            path.push(cur);
-            cur = self.parent(cur);
-        }
-        path.reverse();
-        let mut flipped = false;
-        let mut ord = 0;
-        for i in 0..(path.len() - 1) {
-            let [p, u] = [path[i], path[i + 1]];
-            let [l, r] = self.child(p, flipped);
-            if u == r {
-                ord += self.size(l) + 1
-            }
-            flipped = self.nodes[p].flip(flipped);
-        }
-        let [ul, _] = self.child(u, flipped);
-        ord + self.size(ul)
+        Treaps::order(self, u)
     }
 
     fn find_element(
@@ -375,81 +720,134 @@ impl<Ag: AggregatedData> Lists<Ag> for Treaps<Ag> {
         mut search_strategy: impl FnMut(SearchData<'_, Ag>) -> SearchDirection,
     ) -> Idx {
         let mut u = self.root(u);
-        let mut flipped = false;
         use SearchDirection::*;
         while u != Self::EMPTY {
-            let [l, r] = self.child(u, flipped);
-            let p = u;
+            self.unlaze_flip(u);
+            let [l, r] = self.nodes[u].child;
             match search_strategy(SearchData {
                 current_data: self.data(u),
-                left_agg: &self.ag_data(l, flipped),
-                right_agg: &self.ag_data(r, flipped),
+                left_agg: &self.ag_data(l, false),
+                right_agg: &self.ag_data(r, false),
             }) {
                 Found => return u,
                 NotFound => return Self::EMPTY,
                 Left => u = l,
                 Right => u = r,
             }
-            flipped = self.nodes[p].flip(flipped);
         }
         Self::EMPTY
     }
 
-    fn find_kth(&mut self, mut u: Idx, mut k: usize) -> Idx {
-        let mut flipped = false;
-        u = self.root(u);
-        while u != Self::EMPTY {
-            let [l, r] = self.child(u, flipped);
-            flipped = self.nodes[u].flip(flipped);
-            let sl = self.size(l);
-            if sl > k {
-                u = l;
-            } else if sl == k {
-                return u;
-            } else {
-                k -= sl + 1;
-                u = r;
-            }
-        }
-        Self::EMPTY
+    fn find_kth(&mut self, u: Idx, k: usize) -> Idx {
+        Treaps::find_kth(self, u, k)
     }
 
     fn len(&mut self, u: Idx) -> usize {
-        if u == Self::EMPTY {
-            0
-        } else {
-            let u = self.root(u);
-            self.nodes[u].size
-        }
+        Treaps::len(self, u)
     }
 
     fn total_agg(&mut self, u: Idx) -> Ag {
-        let u = self.root(u);
-        self.ag_data(u, false)
+        Treaps::total_agg(self, u)
     }
 
     fn range_agg_lr(&mut self, u: Idx, ql: usize, qr: usize) -> Ag {
-        let u = self.root(u);
-        self.range_agg_lr_inner(u, ql, qr)
+        Treaps::range_agg(self, u, ql..qr)
+    }
+
+    fn map_in_place(&mut self, u: Idx, mut f: impl FnMut(&mut Ag::Data)) {
+        let root = self.root(u);
+        if root == Self::EMPTY {
+            return;
+        }
+        let top_down = self.unlaze_all(root);
+        // In order traversal, no flips are left
+        let mut stack = vec![];
+        let mut cur = root;
+        loop {
+            while cur != Self::EMPTY {
+                stack.push(cur);
+                cur = self.nodes[cur].child[0];
+            }
+            let Some(v) = stack.pop() else {
+                break;
+            };
+            f(&mut self.nodes[v].data);
+            cur = self.nodes[v].child[1];
+        }
+        // And recalculate in reverse
+        for v in top_down.into_iter().rev() {
+            self.recalc(v);
+        }
+    }
+
+    fn for_each(&mut self, u: Idx, mut f: impl FnMut(Idx, &Ag::Data)) {
+        let root = self.root(u);
+        if root == Self::EMPTY {
+            return;
+        }
+        // Unlazing keeps the aggregates correct, so nothing needs to be recalculated
+        self.unlaze_all(root);
+        let mut stack = vec![];
+        let mut cur = root;
+        loop {
+            while cur != Self::EMPTY {
+                stack.push(cur);
+                cur = self.nodes[cur].child[0];
+            }
+            let Some(v) = stack.pop() else {
+                break;
+            };
+            f(v, &self.nodes[v].data);
+            cur = self.nodes[v].child[1];
+        }
     }
 
     fn concat(&mut self, u: Idx, v: Idx) -> Idx {
+        #[cfg(debug_assertions)]
+        let first = Treaps::find_kth(self, if u == Self::EMPTY { v } else { u }, 0);
         let (u, v) = (self.root(u), self.root(v));
         if u == v {
             self.unlaze_flip(u);
             return u;
         }
-        self.concat_inner(u, v)
+        if u != Self::EMPTY && v != Self::EMPTY {
+            self.lists -= 1;
+        }
+        let root = self.concat_inner(u, v);
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            Treaps::find_kth(self, root, 0),
+            first,
+            "concat must keep the first element of the left list"
+        );
+        root
     }
 
     fn split_lr(&mut self, u: Idx, ql: usize, qr: usize) -> (Idx, Idx, Idx) {
         let u = self.root(u);
         let (l, mr) = self.split_k(u, ql);
         let (m, r) = self.split_k(mr, qr - ql);
+        self.count_split([l, m, r]);
         (l, m, r)
     }
 
-    fn reverse(&mut self, u: Idx) {
+    fn memory_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.nodes.capacity() * std::mem::size_of::<Node<Ag>>()
+    }
+
+    fn split_after(&mut self, u: Idx) -> (Idx, Idx) {
+        let k = Treaps::order(self, u) + 1;
+        let root = Treaps::root(self, u);
+        let (l, r) = self.split_k(root, k);
+        self.count_split([l, r]);
+        (l, r)
+    }
+
+    fn reverse(&mut self, u: Idx)
+    where
+        Ag: AggregatedData,
+    {
+        assert!(V::REVERSIBLE, "Irreversible lists can't be reversed");
         let u = self.root(u);
         self.nodes[u].flip_subtree ^= true;
     }
@@ -457,4 +855,16 @@ impl<Ag: AggregatedData> Lists<Ag> for Treaps<Ag> {
     fn is_root(&mut self, u: Idx) -> bool {
         self.parent(u) == Self::EMPTY
     }
+
+    fn check_all(&self)
+    where
+        Ag: Eq,
+    {
+        let report = self.invariants();
+        assert!(report.is_ok(), "{report:?}");
+    }
+
+    fn counters(&self) -> Counters {
+        self.counters
+    }
 }
@@ -0,0 +1,54 @@
+//! Counters of internal operations, used to profile the amortized behavior of the data structures.
+//! They are only incremented when the `metrics` feature is enabled, otherwise they are always zero.
+//! Also has helpers for the memory estimates given by `memory_bytes` methods.
+
+use std::ops::{Add, AddAssign};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counters {
+    /// Rotations done while splaying.
+    pub rotations: u64,
+    /// Recursive steps of treap splits.
+    pub splits: u64,
+    /// Recursive steps of treap concats.
+    pub concats: u64,
+    /// Calls to access in link cut trees.
+    pub accesses: u64,
+}
+
+impl Add for Counters {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            rotations: self.rotations + rhs.rotations,
+            splits: self.splits + rhs.splits,
+            concats: self.concats + rhs.concats,
+            accesses: self.accesses + rhs.accesses,
+        }
+    }
+}
+
+impl AddAssign for Counters {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+/// Increments the counter by the given amount (default 1), only if the `metrics` feature is enabled.
+macro_rules! count {
+    ($counter:expr) => {
+        count!($counter, 1)
+    };
+    ($counter:expr, $amount:expr) => {
+        #[cfg(feature = "metrics")]
+        {
+            $counter += $amount;
+        }
+    };
+}
+pub(crate) use count;
+
+/// Rough size of a B-tree map with len entries. Ignores the internal node overhead, which is small for large maps.
+pub(crate) fn map_bytes<K, V>(len: usize) -> usize {
+    len * (std::mem::size_of::<K>() + std::mem::size_of::<V>())
+}
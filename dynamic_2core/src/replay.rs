@@ -0,0 +1,131 @@
+//! Recording of solver operations and their results, so a failing sequence can be replayed deterministically.
+//! Logs are saved as text, one operation per line, and can be turned into regression tests with [replay].
+
+use std::{
+    fmt::Display,
+    io::{self, BufRead, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::Dynamic2CoreSolver;
+
+/// An operation that modifies the solver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    AddEdge(usize, usize),
+    RemoveEdge(usize, usize),
+}
+
+impl Op {
+    /// Applies the operation to the solver, returning its result.
+    pub fn apply(self, solver: &mut impl Dynamic2CoreSolver) -> bool {
+        match self {
+            Op::AddEdge(u, v) => solver.add_edge(u, v),
+            Op::RemoveEdge(u, v) => solver.remove_edge(u, v),
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Op::AddEdge(u, v) => write!(f, "add {u} {v}"),
+            Op::RemoveEdge(u, v) => write!(f, "remove {u} {v}"),
+        }
+    }
+}
+
+/// Operations applied to a solver on n nodes, with the result each returned.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayLog {
+    n: usize,
+    steps: Vec<(Op, bool)>,
+}
+
+impl ReplayLog {
+    /// Empty log for a solver on n nodes.
+    pub fn new(n: usize) -> Self {
+        Self { n, steps: vec![] }
+    }
+    /// Number of nodes of the solver.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+    /// All recorded operations, in order, with their results.
+    pub fn steps(&self) -> &[(Op, bool)] {
+        &self.steps
+    }
+    /// Records an operation applied elsewhere.
+    pub fn record(&mut self, op: Op, result: bool) {
+        self.steps.push((op, result));
+    }
+    /// Applies the operation to the solver and records it. Returns its result.
+    pub fn apply(&mut self, solver: &mut impl Dynamic2CoreSolver, op: Op) -> bool {
+        let result = op.apply(solver);
+        self.record(op, result);
+        result
+    }
+    /// Writes the log as text: a line with n, then a line per operation followed by its result.
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "n {}", self.n)?;
+        for (op, result) in &self.steps {
+            writeln!(w, "{op} {result}")?;
+        }
+        Ok(())
+    }
+    /// Reads a log in the format of [ReplayLog::write_to].
+    pub fn read_from(r: impl BufRead) -> io::Result<Self> {
+        let invalid =
+            |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad line {line:?}"));
+        fn parse<T: FromStr>(word: Option<&str>) -> Option<T> {
+            word?.parse().ok()
+        }
+        let mut lines = r.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let n = match header.split_whitespace().collect::<Vec<_>>()[..] {
+            ["n", n] => n.parse().map_err(|_| invalid(&header))?,
+            _ => return Err(invalid(&header)),
+        };
+        let mut log = Self::new(n);
+        for line in lines {
+            let line = line?;
+            let mut words = line.split_whitespace();
+            let op = words.next();
+            let (u, v, result) = (
+                parse(words.next()),
+                parse(words.next()),
+                parse(words.next()),
+            );
+            let (Some(u), Some(v), Some(result), None) = (u, v, result, words.next()) else {
+                return Err(invalid(&line));
+            };
+            let op = match op {
+                Some("add") => Op::AddEdge(u, v),
+                Some("remove") => Op::RemoveEdge(u, v),
+                _ => return Err(invalid(&line)),
+            };
+            log.record(op, result);
+        }
+        Ok(log)
+    }
+    /// Saves the log to a file, see [ReplayLog::write_to].
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut w)?;
+        // Dropping the writer would silently ignore errors writing what is left in its buffer.
+        w.flush()
+    }
+    /// Loads a log from a file saved with [ReplayLog::save].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+/// Applies all operations of the log to the solver, which should be on the same number of nodes.
+/// Panics on the first operation whose result differs from the recorded one.
+pub fn replay(log: &ReplayLog, solver: &mut impl Dynamic2CoreSolver) {
+    for (i, &(op, result)) in log.steps.iter().enumerate() {
+        assert_eq!(op.apply(solver), result, "step {i}: {op}");
+    }
+}
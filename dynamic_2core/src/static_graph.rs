@@ -0,0 +1,35 @@
+//! Algorithms on static graphs given as edge lists, computed from scratch on each call. Unlike the solvers, nothing is
+//! maintained between calls, so these can be used on the edges of a solver, see [crate::dynamic_2core::D2CSolver::edges].
+
+/// Total weight of a minimum spanning forest of the graph on n vertices with the given edges, where the weight of
+/// edge (u, v) is weight(u, v). The forest is found with Kruskal's algorithm, taking O(m lg m).
+pub fn compute_msf_weight<W: Ord + std::iter::Sum>(
+    n: usize,
+    edges: impl IntoIterator<Item = (usize, usize)>,
+    weight: impl Fn(usize, usize) -> W,
+) -> W {
+    let mut edges: Vec<_> = edges
+        .into_iter()
+        .map(|(u, v)| (weight(u, v), u, v))
+        .collect();
+    edges.sort_by(|a, b| a.0.cmp(&b.0));
+    // Union-find with path halving
+    let mut parent: Vec<usize> = (0..n).collect();
+    let find = |parent: &mut Vec<usize>, mut u: usize| {
+        while parent[u] != u {
+            parent[u] = parent[parent[u]];
+            u = parent[u];
+        }
+        u
+    };
+    edges
+        .into_iter()
+        .filter_map(|(w, u, v)| {
+            let (ru, rv) = (find(&mut parent, u), find(&mut parent, v));
+            (ru != rv).then(|| {
+                parent[ru] = rv;
+                w
+            })
+        })
+        .sum()
+}
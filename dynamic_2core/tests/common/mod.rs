@@ -1,6 +1,6 @@
 use std::sync::{LazyLock, Mutex};
 
-use dynamic_2core::lists::AggregatedData;
+use dynamic_2core::lists::{AggregatedData, Monoid};
 use flexi_logger::{Logger, LoggerHandle};
 
 pub mod slow_lct;
@@ -9,7 +9,7 @@ pub mod slow_lists;
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct AggSum(pub i32);
 
-impl AggregatedData for AggSum {
+impl Monoid for AggSum {
     type Data = i32;
 
     fn from(data: &Self::Data) -> Self {
@@ -19,7 +19,9 @@ impl AggregatedData for AggSum {
     fn merge(self, right: Self) -> Self {
         Self(self.0 + right.0)
     }
+}
 
+impl AggregatedData for AggSum {
     fn reverse(self) -> Self {
         self
     }
@@ -37,7 +39,7 @@ pub struct AggDigit {
     size: u8,
 }
 
-impl AggregatedData for AggDigit {
+impl Monoid for AggDigit {
     type Data = i32;
 
     fn from(&data: &Self::Data) -> Self {
@@ -49,12 +51,19 @@ impl AggregatedData for AggDigit {
     }
 
     fn merge(self, right: Self) -> Self {
+        let number = 10_i32
+            .checked_pow(right.size.into())
+            .and_then(|shift| self.number.checked_mul(shift))
+            .and_then(|n| n.checked_add(right.number))
+            .expect("AggDigit only supports numbers that fit in an i32");
         Self {
-            number: self.number * 10_i32.pow(right.size.into()) + right.number,
+            number,
             size: self.size + right.size,
         }
     }
+}
 
+impl AggregatedData for AggDigit {
     fn reverse(mut self) -> Self {
         let mut new_number = 0;
         for _ in 0..self.size {
@@ -56,6 +56,10 @@ impl LinkCutTree for SlowLCT {
         }
     }
 
+    fn depth(&mut self, u: Node) -> usize {
+        self.path_from_root(u).len() - 1
+    }
+
     fn lca(&mut self, u: Node, v: Node) -> Option<Node> {
         let pu = self.path_from_root(u);
         let pv = self.path_from_root(v);
@@ -2,14 +2,14 @@ use std::fmt::Debug;
 
 use dynamic_2core::lists::*;
 
-/// Dummy implementation, most of the operations take linear time.
+/// Dummy implementation, most of the operations take linear time. Lists can only be reversed if Ag is [AggregatedData].
 #[derive(Clone)]
-pub struct SlowLists<Ag: AggregatedData = ()> {
+pub struct SlowLists<Ag: Monoid = ()> {
     lists: Vec<Vec<Entry<Ag>>>,
     u_to_list: Vec<usize>,
 }
 
-impl<Ag: AggregatedData> Debug for SlowLists<Ag> {
+impl<Ag: Monoid> Debug for SlowLists<Ag> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "SlowLists:")?;
         for l in &self.lists {
@@ -26,12 +26,12 @@ impl<Ag: AggregatedData> Debug for SlowLists<Ag> {
 }
 
 #[derive(Clone, Debug)]
-struct Entry<Ag: AggregatedData> {
+struct Entry<Ag: Monoid> {
     idx: Idx,
     data: Ag::Data,
 }
 
-impl<Ag: AggregatedData> SlowLists<Ag> {
+impl<Ag: Monoid> SlowLists<Ag> {
     fn list(&self, u: Idx) -> &Vec<Entry<Ag>> {
         if u == Self::EMPTY {
             &self.lists[0]
@@ -57,7 +57,7 @@ impl<Ag: AggregatedData> SlowLists<Ag> {
     }
 }
 
-impl<Ag: AggregatedData> Lists<Ag> for SlowLists<Ag> {
+impl<Ag: Monoid> Lists<Ag> for SlowLists<Ag> {
     const EMPTY: Idx = usize::MAX;
 
     fn new(capacity: usize) -> Self {
@@ -77,10 +77,28 @@ impl<Ag: AggregatedData> Lists<Ag> for SlowLists<Ag> {
         idx
     }
 
+    fn create_many(
+        &mut self,
+        data: impl ExactSizeIterator<Item = Ag::Data>,
+    ) -> std::ops::Range<Idx> {
+        let start = self.total_size();
+        self.lists.reserve(data.len());
+        self.u_to_list.reserve(data.len());
+        for (idx, data) in (start..).zip(data) {
+            self.lists.push(vec![Entry { idx, data }]);
+            self.u_to_list.push(self.lists.len() - 1);
+        }
+        start..self.total_size()
+    }
+
     fn total_size(&self) -> usize {
         self.u_to_list.len()
     }
 
+    fn num_lists(&self) -> usize {
+        self.lists.iter().filter(|l| !l.is_empty()).count()
+    }
+
     fn root(&mut self, u: Idx) -> Idx {
         if u == Self::EMPTY {
             return Self::EMPTY;
@@ -197,8 +215,14 @@ impl<Ag: AggregatedData> Lists<Ag> for SlowLists<Ag> {
         )
     }
 
-    fn reverse(&mut self, u: Idx) {
+    fn reverse(&mut self, u: Idx)
+    where
+        Ag: AggregatedData,
+    {
         let l = self.u_to_list[u];
         self.lists[l].reverse();
+        for e in &mut self.lists[l] {
+            Ag::reverse_data(&mut e.data);
+        }
     }
 }
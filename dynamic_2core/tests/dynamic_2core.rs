@@ -2,16 +2,21 @@
 extern crate test;
 use rand::{thread_rng, Rng, SeedableRng};
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     time::{Duration, Instant},
 };
 
 use common::{init_logger, slow_lct::SlowLCT, slow_lists::SlowLists};
 use dynamic_2core::{
-    dynamic_2core::{AgData, D2CSolver, Dynamic2CoreSolver},
-    euler_tour_tree::ETT,
+    dynamic_2core::{
+        AgData, ComponentEvent, D2CSolver, Dynamic2CoreSolver, GraphDiff, GraphEvent,
+        GrowableSolver, PromoteSide, RemoveStats, SolverStats,
+    },
+    euler_tour_tree::{MergePolicy, ETT},
     link_cut_tree::LCT,
     lists::{splay::Splays, treap::Treaps},
+    replay::{Op, ReplayLog},
+    static_graph::compute_msf_weight,
 };
 
 mod common;
@@ -33,11 +38,11 @@ impl ToEdge for usize {
 
 struct D2CTests<T>(std::marker::PhantomData<T>)
 where
-    T: Dynamic2CoreSolver;
+    T: Dynamic2CoreSolver + ?Sized;
 
 impl<T> D2CTests<T>
 where
-    T: Dynamic2CoreSolver,
+    T: Dynamic2CoreSolver + ?Sized,
 {
     fn assert_all_connections(t: &mut T, groups: &[&[usize]]) {
         for g1 in groups {
@@ -51,7 +56,7 @@ where
         }
     }
 
-    fn map_core_numbers<T2: Dynamic2CoreSolver>(t: &mut T2, n: usize) -> Vec<usize> {
+    fn map_core_numbers<T2: Dynamic2CoreSolver + ?Sized>(t: &mut T2, n: usize) -> Vec<usize> {
         (0..n)
             .map(|u| match (t.is_in_1core(u), t.is_in_2core(u)) {
                 (true, true) => 2,
@@ -73,8 +78,15 @@ where
         }
     }
 
-    fn test_dyn_con() {
-        let t = &mut T::new(5);
+    fn test_dyn_con()
+    where
+        T: Sized,
+    {
+        Self::check_dyn_con(&mut T::new(5));
+    }
+
+    /// Runs on an existing solver, so it also works for trait objects. It must be empty and have 5 nodes.
+    fn check_dyn_con(t: &mut T) {
         Self::assert_all_connections(t, &[&[0], &[1], &[2], &[3], &[4]]);
         assert!(t.add_edge(0, 1));
         assert!(t.add_edge(0, 2));
@@ -87,7 +99,10 @@ where
         Self::assert_all_connections(t, &[&[0, 2], &[1, 4], &[3]]);
     }
 
-    fn test_2core() {
+    fn test_2core()
+    where
+        T: Sized,
+    {
         let mut t = T::new(11);
         Self::assert_core_numbers(&mut t, &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         Self::add_edges(&mut t, &[01, 02, 03, 14, 15, 26, 27, 58, 59]);
@@ -105,20 +120,107 @@ where
         Self::assert_core_numbers(&mut t, &[2, 1, 2, 2, 1, 1, 1, 2, 1, 1, 2]);
     }
 
-    fn test_all() {
+    fn test_path_and_cycle()
+    where
+        T: Sized,
+    {
+        let t = &mut T::new(12);
+        assert_eq!(t.add_path(&[0, 1, 2, 3]), 3);
+        Self::assert_core_numbers(t, &[1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // Repeated edges and self-loops are skipped
+        assert_eq!(t.add_path(&[3, 2, 2, 4]), 1);
+        assert_eq!(t.add_cycle(&[5, 6, 7, 8, 9]), 5);
+        Self::assert_core_numbers(t, &[1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 0, 0]);
+        assert_eq!(t.add_cycle(&[10, 11]), 1);
+        assert_eq!(t.add_cycle(&[10]), 0);
+        assert_eq!(t.add_cycle(&[]), 0);
+        assert_eq!(t.add_cycle(&[0, 1, 2, 3, 4]), 2);
+        Self::assert_core_numbers(t, &[2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1]);
+        Self::assert_all_connections(t, &[&[0, 1, 2, 3, 4], &[5, 6, 7, 8, 9], &[10, 11]]);
+    }
+
+    fn test_independent_cycles()
+    where
+        T: Sized,
+    {
+        let t = &mut T::new(8);
+        assert!(t.is_forest());
+        t.add_path(&[0, 1, 2, 3]);
+        t.add_edge(1, 4);
+        assert!(t.is_forest());
+        assert_eq!(t.num_independent_cycles(), 0);
+        t.add_cycle(&[5, 6, 7]);
+        assert!(!t.is_forest());
+        assert_eq!(t.num_independent_cycles(), 1);
+        // Chords of the cycle 0 1 2 3 4
+        t.add_edge(3, 4);
+        t.add_edge(0, 4);
+        t.add_edge(0, 2);
+        t.add_edge(0, 3);
+        assert_eq!(t.num_independent_cycles(), 5);
+        t.remove_edge(1, 2);
+        assert_eq!(t.num_independent_cycles(), 4);
+        t.remove_edge(6, 7);
+        assert_eq!(t.num_independent_cycles(), 3);
+    }
+
+    fn test_connected_components()
+    where
+        T: Sized,
+    {
+        let t = &mut T::new(7);
+        assert_eq!(
+            t.connected_components(),
+            (0..7).map(|u| vec![u]).collect::<Vec<_>>()
+        );
+        t.add_path(&[5, 1, 3]);
+        t.add_edge(4, 0);
+        t.add_cycle(&[6, 2, 4]);
+        let comps = t.connected_components();
+        assert_eq!(comps, vec![vec![0, 2, 4, 6], vec![1, 3, 5]]);
+        t.remove_edge(2, 4);
+        t.remove_edge(1, 3);
+        let comps = t.connected_components();
+        assert_eq!(comps, vec![vec![0, 2, 4, 6], vec![1, 5], vec![3]]);
+        // A partition of the vertices into connected groups
+        let mut all: Vec<_> = comps.concat();
+        all.sort_unstable();
+        assert_eq!(all, (0..7).collect::<Vec<_>>());
+        for c in &comps {
+            for &u in c {
+                assert!(c.iter().all(|&v| t.is_connected(u, v)));
+            }
+        }
+    }
+
+    fn test_all()
+    where
+        T: Sized,
+    {
         Self::test_dyn_con();
         Self::test_2core();
+        Self::test_path_and_cycle();
+        Self::test_independent_cycles();
+        Self::test_connected_components();
     }
 
     fn compare_with_slow(seed: u64)
     where
-        T: std::fmt::Debug,
+        T: std::fmt::Debug + Sized,
     {
         const N: usize = 25;
         let mut t1 = T::new(N);
         let mut t2 = Slow::new(N);
         let mut edges = vec![];
         let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        // On failure, the operations are saved so they can be replayed with dynamic_2core::replay::replay
+        let mut replay_log = scopeguard::guard_on_unwind(ReplayLog::new(N), move |ops| {
+            let path = std::env::temp_dir().join(format!("d2c_replay_{seed}.txt"));
+            match ops.save(&path) {
+                Ok(()) => log::error!("Operations saved to {path:?}"),
+                Err(e) => log::error!("Failed to save operations: {e}"),
+            }
+        });
         for q in 0..10000 {
             if q % 100 == 0 {
                 log::debug!("q {q}");
@@ -131,7 +233,7 @@ where
                 } else {
                     std::mem::swap(&mut u, &mut v);
                 }
-                let added = t1.add_edge(u, v);
+                let added = replay_log.apply(&mut t1, Op::AddEdge(u, v));
                 assert_eq!(added, t2.add_edge(u, v));
                 if added {
                     edges.push((u, v));
@@ -139,7 +241,10 @@ where
             } else {
                 let idx = rng.gen_range(0..edges.len());
                 let (u, v) = edges.swap_remove(idx);
-                assert_eq!(t1.remove_edge(u, v), t2.remove_edge(u, v));
+                assert_eq!(
+                    replay_log.apply(&mut t1, Op::RemoveEdge(u, v)),
+                    t2.remove_edge(u, v)
+                );
             }
             if q % 10 == 0 {
                 let gs = t2.groups();
@@ -152,6 +257,8 @@ where
                     Self::map_core_numbers(&mut t1, N),
                     Self::map_core_numbers(&mut t2, N)
                 );
+                assert_eq!(t1.num_independent_cycles(), t2.num_independent_cycles());
+                assert_eq!(t1.connected_components(), t2.connected_components());
             }
         }
     }
@@ -260,6 +367,21 @@ impl Dynamic2CoreSolver for Slow {
     fn is_in_1core(&mut self, u: usize) -> bool {
         !self.adj[u].is_empty()
     }
+
+    fn num_independent_cycles(&self) -> usize {
+        let edges = self.adj.iter().map(BTreeSet::len).sum::<usize>() / 2;
+        let components = self.groups().into_iter().max().unwrap_or(0);
+        edges + components - self.adj.len()
+    }
+
+    fn connected_components(&mut self) -> Vec<Vec<usize>> {
+        let groups = self.groups();
+        let mut comps = vec![vec![]; groups.iter().copied().max().unwrap_or(0)];
+        for (u, g) in groups.into_iter().enumerate() {
+            comps[g - 1].push(u);
+        }
+        comps
+    }
 }
 
 #[test]
@@ -295,6 +417,30 @@ fn test_lct_with_splay() {
     D2CTests::<D2CSolver<TreapETT, LCT<Splays>>>::test_all();
 }
 
+#[test]
+fn test_boxed_solver() {
+    init_logger();
+    let solvers: Vec<Box<dyn Dynamic2CoreSolver>> = vec![
+        Box::new(Slow::new(5)),
+        Box::new(D2CSolver::<TreapETT, LCT<Treaps>>::new(5)),
+        Box::new(D2CSolver::<SlowETT, LCT<Splays>>::new(5)),
+    ];
+    for mut t in solvers {
+        D2CTests::<dyn Dynamic2CoreSolver>::check_dyn_con(&mut *t);
+        assert_eq!(t.add_cycle(&[0, 1, 2]), 2);
+        assert!(t.is_in_2core(1));
+    }
+}
+
+#[test]
+fn test_make_solver() {
+    init_logger();
+    for kind in dynamic_2core::SolverKind::ALL {
+        let mut t = dynamic_2core::make_solver(kind, 5);
+        D2CTests::<dyn Dynamic2CoreSolver>::check_dyn_con(&mut *t);
+    }
+}
+
 #[test]
 fn test_cmp_slow() {
     init_logger();
@@ -314,6 +460,1462 @@ fn test_dyn2core_cmp3() {
     D2CTests::<D2CSolver<TreapETT, LCT<Splays>>>::compare_with_slow(3);
 }
 
+#[test]
+fn test_merge_policies_same_answers() {
+    init_logger();
+    const N: usize = 30;
+    let policies = [
+        MergePolicy::KeepFirst,
+        MergePolicy::LargerTree,
+        MergePolicy::SmallerRoot,
+    ];
+    let mut ts: Vec<_> = policies
+        .iter()
+        .map(|&p| {
+            let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+            t.set_merge_policy(p);
+            t
+        })
+        .collect();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(607);
+    let mut edges = vec![];
+    for _ in 0..2000 {
+        if edges.is_empty() || rng.gen_bool(0.6) {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            let added: Vec<_> = ts.iter_mut().map(|t| t.add_edge(u, v)).collect();
+            assert!(added.iter().all(|&a| a == added[0]));
+            if added[0] {
+                edges.push((u, v));
+            }
+        } else {
+            let (u, v) = edges.swap_remove(rng.gen_range(0..edges.len()));
+            assert!(ts.iter_mut().all(|t| t.remove_edge(u, v)));
+        }
+        let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+        let conn: Vec<_> = ts.iter_mut().map(|t| t.is_connected(u, v)).collect();
+        assert!(conn.iter().all(|&c| c == conn[0]));
+        let core: Vec<_> = ts.iter_mut().map(|t| t.is_in_2core(u)).collect();
+        assert!(core.iter().all(|&c| c == core[0]));
+    }
+}
+
+#[test]
+fn test_freeze() {
+    init_logger();
+    const N: usize = 20;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(610);
+    for _ in 0..20 {
+        for _ in 0..10 {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            if rng.gen_bool(0.7) {
+                t.add_edge(u, v);
+            } else {
+                t.remove_edge(u, v);
+            }
+        }
+        let frozen = t.freeze();
+        assert!(frozen.is_up_to_date(&t));
+        for u in 0..N {
+            assert_eq!(frozen.is_in_1core(u), t.is_in_1core(u));
+            assert_eq!(frozen.is_in_2core(u), t.is_in_2core(u));
+            for v in 0..N {
+                assert_eq!(frozen.is_connected(u, v), t.is_connected(u, v));
+            }
+        }
+        // Queries don't invalidate it
+        assert!(frozen.is_up_to_date(&t));
+    }
+    let frozen = t.freeze();
+    // Failed operations don't change the graph
+    t.add_edge(3, 3);
+    assert!(frozen.is_up_to_date(&t));
+    if !t.add_edge(1, 2) {
+        t.remove_edge(1, 2);
+    }
+    assert!(!frozen.is_up_to_date(&t));
+}
+
+#[test]
+fn test_extract_2core() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(12);
+    // Tadpole: cycle 0-1-2-3 with a tail 3-4-5
+    t.add_cycle(&[0, 1, 2, 3]);
+    t.add_path(&[3, 4, 5]);
+    // Triangle with two tails
+    t.add_cycle(&[6, 7, 8]);
+    t.add_path(&[8, 9, 10]);
+    t.add_edge(6, 11);
+    let cycle = (vec![(0, 1), (0, 3), (1, 2), (2, 3)], vec![0, 1, 2, 3]);
+    assert_eq!(t.extract_2core(5), cycle);
+    assert_eq!(t.extract_2core(0), cycle);
+    let triangle = (vec![(6, 7), (6, 8), (7, 8)], vec![6, 7, 8]);
+    assert_eq!(t.extract_2core(10), triangle);
+    t.remove_edge(2, 3);
+    assert_eq!(t.extract_2core(0), (vec![], vec![]));
+    t.add_edge(5, 1);
+    assert_eq!(
+        t.extract_2core(2),
+        (
+            vec![(0, 1), (0, 3), (1, 5), (3, 4), (4, 5)],
+            vec![0, 1, 3, 4, 5]
+        )
+    );
+}
+
+#[test]
+#[cfg(feature = "metrics")]
+fn test_solver_counters() {
+    let mut t = D2CSolver::<TreapETT, LCT<Splays>>::new(10);
+    assert_eq!(t.counters(), Default::default());
+    t.add_cycle(&[0, 1, 2, 3]);
+    t.is_in_2core(1);
+    let c = t.counters();
+    assert!(c.accesses > 0 && c.rotations > 0 && c.splits > 0 && c.concats > 0);
+}
+
+#[test]
+fn test_isolate() {
+    init_logger();
+    const N: usize = 25;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    let mut slow = Slow::new(N);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(618);
+    for _ in 0..60 {
+        let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+        if u != v {
+            t.add_edge(u, v);
+            slow.add_edge(u, v);
+        }
+    }
+    for _ in 0..8 {
+        let u = rng.gen_range(0..N);
+        let deg = slow.adj[u].len();
+        assert_eq!(t.degree(u), deg);
+        assert_eq!(t.isolate(u), deg);
+        for v in slow.adj[u].clone() {
+            slow.remove_edge(u, v);
+        }
+        assert_eq!(t.degree(u), 0);
+        assert!(!t.is_in_1core(u) && !t.is_in_2core(u));
+        let gs = slow.groups();
+        for a in 0..N {
+            assert_eq!(t.neighbors(a).collect::<BTreeSet<_>>(), slow.adj[a]);
+            for b in 0..N {
+                assert_eq!(t.is_connected(a, b), gs[a] == gs[b]);
+            }
+        }
+        assert_eq!(
+            D2CTests::<Slow>::map_core_numbers(&mut t, N),
+            D2CTests::<Slow>::map_core_numbers(&mut slow, N)
+        );
+        assert_eq!(t.isolate(u), 0);
+    }
+}
+
+#[test]
+fn test_merge_from() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(3);
+    t.add_cycle(&[0, 1, 2]);
+    let mut other = D2CSolver::<TreapETT, LCT<Splays>>::new(3);
+    other.add_cycle(&[0, 1, 2]);
+    let generation = t.generation();
+    // Grows to 8 vertices, 3 and 4 are isolated
+    t.merge_from(&other, 5);
+    assert!(t.generation() > generation);
+    assert_eq!(
+        t.edges().collect::<Vec<_>>(),
+        [(0, 1), (0, 2), (1, 2), (5, 6), (5, 7), (6, 7)]
+    );
+    for u in [0, 1, 2, 5, 6, 7] {
+        assert!(t.is_in_2core(u));
+    }
+    assert!(!t.is_in_1core(3) && !t.is_in_1core(4));
+    assert!(t.is_connected(0, 2) && t.is_connected(5, 7));
+    assert!(!t.is_connected(0, 5));
+    assert!(t.add_edge(2, 5));
+    assert!(t.is_connected(0, 7));
+    assert!(t.is_in_2core(2) && t.is_in_2core(5));
+    assert!(!t.is_in_2core(3));
+    // Fits without growing, existing edges are skipped
+    t.merge_from(&other, 0);
+    assert_eq!(t.edges().count(), 7);
+    assert_eq!(t.add_path(&[1, 3, 4, 6]), 3);
+    assert!(t.is_in_2core(3) && t.is_in_2core(4));
+}
+
+#[test]
+fn test_is_connected_within() {
+    init_logger();
+    const N: usize = 10;
+    let mut t = D2CSolver::<TreapETT, LCT<Splays>>::new(N + 1);
+    t.add_path(&(0..N).collect::<Vec<_>>());
+    for u in 0..N {
+        for v in 0..N {
+            let dist = u.abs_diff(v);
+            assert!(t.is_connected_within(u, v, dist), "{u} {v}");
+            if dist > 0 {
+                assert!(!t.is_connected_within(u, v, dist - 1), "{u} {v}");
+            }
+        }
+        assert!(!t.is_connected_within(u, N, N));
+    }
+    // Closing the cycle doesn't change the spanning forest
+    t.add_edge(0, N - 1);
+    assert!(t.is_connected(0, N - 1));
+    assert!(!t.is_connected_within(0, N - 1, 1));
+}
+
+/// Whether u and v stay connected after removing any single edge.
+fn slow_2edge_connected(slow: &mut Slow, u: usize, v: usize) -> bool {
+    if !slow.is_connected(u, v) {
+        return false;
+    }
+    let edges: Vec<_> = (0..slow.adj.len())
+        .flat_map(|a| {
+            slow.adj[a]
+                .range(a..)
+                .map(move |&b| (a, b))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    edges.into_iter().all(|(a, b)| {
+        slow.remove_edge(a, b);
+        let still = slow.is_connected(u, v);
+        slow.add_edge(a, b);
+        still
+    })
+}
+
+#[test]
+fn test_are_2edge_connected() {
+    init_logger();
+    const N: usize = 10;
+    // Blocks 0-1-2-3 and 4-5-6 joined by the bridge 3-4, with 7 hanging from 6 and 8, 9 isolated.
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    let mut slow = Slow::new(N);
+    for e in [
+        (0, 1),
+        (1, 2),
+        (2, 3),
+        (3, 0),
+        (0, 2),
+        (3, 4),
+        (4, 5),
+        (5, 6),
+        (6, 4),
+        (6, 7),
+    ] {
+        t.add_edge(e.0, e.1);
+        slow.add_edge(e.0, e.1);
+    }
+    assert!(t.are_2edge_connected(0, 3));
+    assert!(t.are_2edge_connected(4, 6));
+    assert!(t.are_2edge_connected(8, 8));
+    assert!(!t.are_2edge_connected(3, 4));
+    assert!(!t.are_2edge_connected(0, 5));
+    assert!(!t.are_2edge_connected(6, 7));
+    assert!(!t.are_2edge_connected(8, 9));
+    let check = |t: &mut D2CSolver<_, _>, slow: &mut Slow| {
+        for u in 0..N {
+            for v in 0..N {
+                assert_eq!(
+                    t.are_2edge_connected(u, v),
+                    slow_2edge_connected(slow, u, v),
+                    "{u} {v}"
+                );
+            }
+        }
+    };
+    check(&mut t, &mut slow);
+    // Covering the bridge joins the blocks
+    t.add_edge(2, 5);
+    slow.add_edge(2, 5);
+    assert!(t.are_2edge_connected(0, 5));
+    check(&mut t, &mut slow);
+    for seed in 0..5 {
+        let edges = dynamic_2core::generators::random_graph(N, 12, seed);
+        let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::from_edges(N, edges.iter().copied());
+        let mut slow = Slow::from_edges(N, edges);
+        check(&mut t, &mut slow);
+    }
+}
+
+/// Whether u and v lie on a common cycle, that is, they are connected and stay so after removing their edge, if
+/// any, or any other single vertex.
+fn slow_biconnected(slow: &mut Slow, u: usize, v: usize) -> bool {
+    if u == v {
+        return true;
+    }
+    if !slow.is_connected(u, v) {
+        return false;
+    }
+    if slow.adj[u].contains(&v) {
+        slow.remove_edge(u, v);
+        let still = slow.is_connected(u, v);
+        slow.add_edge(u, v);
+        return still;
+    }
+    (0..slow.adj.len()).filter(|&w| w != u && w != v).all(|w| {
+        let neighbors: Vec<_> = slow.adj[w].iter().copied().collect();
+        for &x in &neighbors {
+            slow.remove_edge(w, x);
+        }
+        let still = slow.is_connected(u, v);
+        for &x in &neighbors {
+            slow.add_edge(w, x);
+        }
+        still
+    })
+}
+
+#[test]
+fn test_same_biconnected() {
+    init_logger();
+    const N: usize = 10;
+    // Triangle 0-1-2 and square 2-3-4-5 sharing the cut vertex 2, with 6 hanging from 5 and 7 isolated.
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    t.add_cycle(&[0, 1, 2]);
+    t.add_cycle(&[2, 3, 4, 5]);
+    t.add_edge(5, 6);
+    assert!(t.same_biconnected(0, 1));
+    assert!(t.same_biconnected(1, 2));
+    assert!(t.same_biconnected(2, 4));
+    assert!(t.same_biconnected(3, 5));
+    assert!(t.same_biconnected(7, 7));
+    assert!(!t.same_biconnected(0, 3));
+    assert!(!t.same_biconnected(1, 4));
+    assert!(!t.same_biconnected(5, 6));
+    assert!(!t.same_biconnected(2, 6));
+    assert!(!t.same_biconnected(0, 7));
+    // They are still 2-edge-connected, as only a vertex separates them
+    assert!(t.are_2edge_connected(0, 3));
+    // Another path between the blocks merges them
+    t.add_edge(1, 3);
+    assert!(t.same_biconnected(0, 4));
+    assert!(!t.same_biconnected(0, 6));
+    let mut rng = rand::rngs::StdRng::seed_from_u64(694);
+    for m in [8, 12, 16] {
+        let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+        let mut slow = Slow::new(N);
+        for _ in 0..m {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            t.add_edge(u, v);
+            slow.add_edge(u, v);
+        }
+        for u in 0..N {
+            for v in 0..N {
+                assert_eq!(
+                    t.same_biconnected(u, v),
+                    slow_biconnected(&mut slow, u, v),
+                    "{u} {v}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_two_edge_connected_components() {
+    init_logger();
+    // Dumbbell: triangles 0-1-2 and 3-4-5 joined by the path 2-6-3, and 7 isolated.
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(8);
+    t.add_cycle(&[0, 1, 2]);
+    t.add_cycle(&[3, 4, 5]);
+    t.add_path(&[2, 6, 3]);
+    assert_eq!(t.two_edge_connected_components(), [0, 0, 0, 1, 1, 1, 2, 3]);
+    // Theta: paths 0-2-1, 0-3-4-1 and 0-1, with 5 hanging from 4.
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(6);
+    t.add_path(&[0, 2, 1]);
+    t.add_path(&[0, 3, 4, 1]);
+    t.add_edge(0, 1);
+    t.add_edge(4, 5);
+    assert_eq!(t.two_edge_connected_components(), [0, 0, 0, 0, 0, 1]);
+    // Removing the middle path leaves only a cycle
+    t.remove_edge(3, 4);
+    assert_eq!(t.two_edge_connected_components(), [0, 0, 0, 1, 2, 3]);
+    const N: usize = 10;
+    for seed in 0..5 {
+        let edges = dynamic_2core::generators::random_graph(N, 12, seed);
+        let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::from_edges(N, edges.iter().copied());
+        let mut slow = Slow::from_edges(N, edges);
+        let label = t.two_edge_connected_components();
+        for u in 0..N {
+            for v in 0..N {
+                assert_eq!(label[u] == label[v], slow_2edge_connected(&mut slow, u, v));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_memory_bytes() {
+    init_logger();
+    type Solver = D2CSolver<TreapETT, LCT<Treaps>>;
+    let ratio = |a: usize, b: usize| b as f64 / a as f64;
+    // Each of the lg n levels has all nodes
+    let (small, big) = (
+        Solver::new(512).memory_bytes(),
+        Solver::new(1024).memory_bytes(),
+    );
+    assert!((2.0..2.5).contains(&ratio(small, big)), "{small} {big}");
+    // Memory used by the edges grows linearly
+    let n = 1024;
+    let base = Solver::new(n).memory_bytes();
+    let with_cycle = |len: usize| {
+        let mut t = Solver::new(n);
+        t.add_cycle(&(0..len).collect::<Vec<_>>());
+        t.memory_bytes() - base
+    };
+    let (small, big) = (with_cycle(300), with_cycle(600));
+    assert!(small > 0);
+    assert!((1.5..3.0).contains(&ratio(small, big)), "{small} {big}");
+}
+
+#[test]
+fn test_2core_low_degree() {
+    init_logger();
+    // Caterpillar: a cycle 0..5 with two leaves on each vertex, plus isolated vertices
+    const N: usize = 20;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    let mut slow = Slow::new(N);
+    let mut edges = vec![(0, 1), (1, 2), (2, 3), (3, 4), (4, 0)];
+    edges.extend((0..5).flat_map(|u| [(u, 5 + 2 * u), (u, 6 + 2 * u)]));
+    for &(u, v) in &edges {
+        t.add_edge(u, v);
+        slow.add_edge(u, v);
+    }
+    let counters = t.counters();
+    for u in 5..N {
+        assert!(t.degree(u) < 2);
+        assert!(!t.is_in_2core(u));
+        assert!(!slow.is_in_2core(u));
+    }
+    // No tree operations needed for them
+    assert_eq!(t.counters(), counters);
+    for u in 0..5 {
+        assert!(t.is_in_2core(u));
+    }
+    // A leaf that gets a second edge may join the 2-core
+    t.add_edge(5, 7);
+    slow.add_edge(5, 7);
+    for u in 0..N {
+        assert_eq!(t.is_in_2core(u), slow.is_in_2core(u), "{u}");
+    }
+}
+
+#[test]
+fn test_remove_edge_split() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(6);
+    t.add_cycle(&[0, 1, 2]);
+    t.add_path(&[2, 3, 4]);
+    assert_eq!(t.remove_edge_split(0, 5), None);
+    // Has a replacement
+    let (a, b) = t.remove_edge_split(1, 0).unwrap();
+    assert_eq!(a, b);
+    assert!(t.is_connected(0, 4));
+    // Bridges
+    let (a, b) = t.remove_edge_split(3, 2).unwrap();
+    assert_ne!(a, b);
+    assert!([3, 4].contains(&a) && [0, 1, 2].contains(&b));
+    let (a, b) = t.remove_edge_split(4, 3).unwrap();
+    assert_eq!((a, b), (4, 3));
+    assert_eq!(t.remove_edge_split(3, 4), None);
+}
+
+#[test]
+fn test_add_edge_tracked() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(6);
+    assert_eq!(t.add_edge_tracked(0, 1), (true, false));
+    assert_eq!(t.add_edge_tracked(1, 2), (true, false));
+    assert_eq!(t.add_edge_tracked(1, 0), (false, false));
+    assert_eq!(t.add_edge_tracked(2, 0), (true, true));
+    // Chord of a cycle already in the 2-core
+    t.add_path(&[2, 3, 4]);
+    assert_eq!(t.add_edge_tracked(0, 4), (true, true));
+    assert_eq!(t.add_edge_tracked(1, 3), (true, false));
+    const N: usize = 12;
+    let core = |slow: &mut Slow| (0..N).map(|u| slow.is_in_2core(u)).collect::<Vec<_>>();
+    for seed in 0..5 {
+        let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+        let mut slow = Slow::new(N);
+        for (u, v) in dynamic_2core::generators::random_graph(N, 25, seed) {
+            let before = core(&mut slow);
+            let added = slow.add_edge(u, v);
+            assert_eq!(t.add_edge_tracked(u, v), (added, before != core(&mut slow)));
+        }
+    }
+}
+
+#[test]
+fn test_degree_histogram() {
+    init_logger();
+    assert!(D2CSolver::<TreapETT, LCT<Treaps>>::new(0)
+        .degree_histogram()
+        .is_empty());
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(10);
+    assert_eq!(t.degree_histogram(), [10]);
+    // Star centered at 0, cycle 5-6-7-8 and 9 isolated
+    for leaf in 1..5 {
+        t.add_edge(0, leaf);
+    }
+    t.add_cycle(&[5, 6, 7, 8]);
+    assert_eq!(t.degree_histogram(), [1, 4, 4, 0, 1]);
+    t.add_edge(0, 5);
+    let histogram = t.degree_histogram();
+    assert_eq!(histogram, [1, 4, 3, 1, 0, 1]);
+    assert_eq!(histogram.iter().sum::<usize>(), 10);
+    t.isolate(0);
+    assert_eq!(t.degree_histogram(), [6, 0, 4]);
+}
+
+#[test]
+fn test_edges_in_2core() {
+    init_logger();
+    // Dumbbell: cycles 0-1-2 and 4-5-6 joined by the path 2-3-4, with 7-8 hanging from 0
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(10);
+    t.add_cycle(&[0, 1, 2]);
+    t.add_cycle(&[4, 5, 6]);
+    t.add_path(&[2, 3, 4]);
+    t.add_path(&[0, 7, 8]);
+    // The path between the cycles is made of bridges, but still in the 2-core
+    let expected = vec![
+        (0, 1),
+        (0, 2),
+        (1, 2),
+        (2, 3),
+        (3, 4),
+        (4, 5),
+        (4, 6),
+        (5, 6),
+    ];
+    assert_eq!(t.edges_in_2core(), expected);
+    assert_eq!(t.extract_2core(0).0, expected);
+    t.remove_edge(5, 6);
+    assert_eq!(t.edges_in_2core(), [(0, 1), (0, 2), (1, 2)]);
+    t.add_cycle(&[7, 8, 9]);
+    assert_eq!(
+        t.edges_in_2core(),
+        [(0, 1), (0, 2), (0, 7), (1, 2), (7, 8), (7, 9), (8, 9)]
+    );
+}
+
+#[test]
+fn test_with_capacity() {
+    init_logger();
+    const N: usize = 30;
+    let mut t1 = D2CSolver::<TreapETT, LCT<Splays>>::new(N);
+    let mut t2 = D2CSolver::<TreapETT, LCT<Splays>>::with_capacity(N, 200);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(628);
+    for _ in 0..2000 {
+        let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+        if rng.gen_bool(0.6) {
+            assert_eq!(t1.add_edge(u, v), t2.add_edge(u, v));
+        } else {
+            assert_eq!(t1.remove_edge(u, v), t2.remove_edge(u, v));
+        }
+        assert_eq!(t1.is_connected(u, v), t2.is_connected(u, v));
+        assert_eq!(t1.is_in_2core(u), t2.is_in_2core(u));
+    }
+    assert_eq!(format!("{t1:?}"), format!("{t2:?}"));
+    assert_eq!(t1.total_promotions(), t2.total_promotions());
+}
+
+#[test]
+fn test_promotions_bound() {
+    init_logger();
+    const N: usize = 40;
+    let mut t = D2CSolver::<TreapETT, LCT<Splays>>::new(N);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(625);
+    let mut edges = vec![];
+    let mut added = 0;
+    for _ in 0..5000 {
+        if edges.is_empty() || rng.gen_bool(0.6) {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            if t.add_edge(u, v) {
+                added += 1;
+                edges.push((u, v));
+            }
+        } else {
+            let (u, v) = edges.swap_remove(rng.gen_range(0..edges.len()));
+            assert!(t.remove_edge(u, v));
+        }
+    }
+    // Each edge can be promoted up to the last level, and there are lg n + 1 levels.
+    let max_level = N.next_power_of_two().trailing_zeros() as u64;
+    assert!(t.total_promotions() > 0);
+    assert!(
+        t.total_promotions() <= added * max_level,
+        "{} promotions for {added} edges",
+        t.total_promotions()
+    );
+}
+
+#[test]
+fn test_validate() {
+    init_logger();
+    fn run<T: Dynamic2CoreSolver>(seed: u64) {
+        const N: usize = 30;
+        let mut t = T::new(N);
+        assert_eq!(t.validate(), Ok(()));
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut edges = vec![];
+        for i in 0..1500 {
+            if edges.is_empty() || rng.gen_bool(0.55) {
+                let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+                if t.add_edge(u, v) {
+                    edges.push((u, v));
+                }
+            } else {
+                let (u, v) = edges.swap_remove(rng.gen_range(0..edges.len()));
+                assert!(t.remove_edge(u, v));
+            }
+            if i % 10 == 0 {
+                assert_eq!(t.validate(), Ok(()), "after {i} operations");
+            }
+        }
+        for (u, v) in edges {
+            assert!(t.remove_edge(u, v));
+        }
+        assert_eq!(t.validate(), Ok(()));
+    }
+    run::<D2CSolver<TreapETT, LCT<Treaps>>>(657);
+    run::<D2CSolver<ETT<Splays<AgData>, AgData>, LCT<Splays>>>(658);
+}
+
+#[test]
+fn test_event_sender() {
+    use GraphEvent::*;
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(8);
+    t.add_edge(6, 7);
+    let (tx, rx) = std::sync::mpsc::channel();
+    t.set_event_sender(tx);
+    t.add_path(&[0, 1, 2]);
+    t.add_edge(2, 0);
+    t.add_edge(3, 2);
+    assert!(!t.add_edge(0, 1));
+    assert!(!t.remove_edge(4, 5));
+    // The cycle breaks, but 0-2 replaces 0-1 in the spanning tree
+    t.remove_edge(0, 1);
+    t.remove_edge(2, 3);
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [
+            EdgeAdded(0, 1),
+            ComponentMerged(0, 1),
+            EdgeAdded(1, 2),
+            ComponentMerged(1, 2),
+            EdgeAdded(0, 2),
+            CoreChanged {
+                vertex: 0,
+                in_2core: true
+            },
+            CoreChanged {
+                vertex: 1,
+                in_2core: true
+            },
+            CoreChanged {
+                vertex: 2,
+                in_2core: true
+            },
+            EdgeAdded(2, 3),
+            ComponentMerged(2, 3),
+            EdgeRemoved(0, 1),
+            CoreChanged {
+                vertex: 0,
+                in_2core: false
+            },
+            CoreChanged {
+                vertex: 1,
+                in_2core: false
+            },
+            CoreChanged {
+                vertex: 2,
+                in_2core: false
+            },
+            EdgeRemoved(2, 3),
+            ComponentSplit(2, 3),
+        ]
+    );
+    // Dropping the receiver doesn't affect the solver
+    drop(rx);
+    assert!(t.add_edge(5, 6));
+    let (tx, rx) = std::sync::mpsc::channel();
+    t.set_event_sender(tx);
+    t.add_edge(5, 7);
+    assert_eq!(rx.try_iter().count(), 4);
+    assert_eq!(t.validate(), Ok(()));
+}
+
+#[test]
+fn test_watch_component() {
+    use ComponentEvent::*;
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(10);
+    t.add_path(&[0, 1, 2]);
+    t.add_edge(5, 6);
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+    let log = seen.clone();
+    t.watch_component(1, Box::new(move |e| log.lock().unwrap().push(e)));
+    let take = || std::mem::take(&mut *seen.lock().unwrap());
+    // Other components don't fire it
+    t.add_edge(6, 7);
+    t.add_edge(8, 9);
+    t.remove_edge(5, 6);
+    assert_eq!(take(), []);
+    t.add_edge(2, 0);
+    t.remove_edge(1, 2);
+    assert_eq!(take(), [EdgeAdded(0, 2), EdgeRemoved(1, 2)]);
+    // It follows the component through merges
+    t.add_edge(2, 6);
+    t.add_edge(7, 9);
+    assert_eq!(
+        take(),
+        [EdgeAdded(2, 6), Merged(2, 6), EdgeAdded(7, 9), Merged(7, 9)]
+    );
+    // And stays on the side of 1 after a split
+    t.remove_edge(2, 6);
+    assert_eq!(take(), [EdgeRemoved(2, 6), Split(2, 6)]);
+    t.remove_edge(6, 7);
+    t.add_edge(3, 4);
+    assert_eq!(take(), []);
+    t.batch(|b| {
+        b.add_edge(0, 3);
+        b.add_edge(9, 9);
+    });
+    assert_eq!(take(), [EdgeAdded(0, 3), Merged(0, 3)]);
+    assert_eq!(t.validate(), Ok(()));
+}
+
+#[test]
+fn test_tree_diameter() {
+    init_logger();
+    const N: usize = 12;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(2 * N);
+    t.add_path(&(0..N).collect::<Vec<_>>());
+    for u in 0..N {
+        assert_eq!(t.tree_diameter(u), N - 1);
+    }
+    // Star centered at N
+    for leaf in N + 1..2 * N {
+        t.add_edge(N, leaf);
+    }
+    assert_eq!(t.tree_diameter(N), 2);
+    assert_eq!(t.tree_diameter(2 * N - 1), 2);
+    // Joining the path and the star through a leaf
+    t.add_edge(N - 1, N + 1);
+    assert_eq!(t.tree_diameter(0), N - 1 + 3);
+    // Closing a cycle doesn't change the spanning tree
+    t.add_edge(0, N + 2);
+    assert_eq!(t.tree_diameter(0), N - 1 + 3);
+    let mut single = D2CSolver::<TreapETT, LCT<Treaps>>::new(1);
+    assert_eq!(single.tree_diameter(0), 0);
+    single.add_edge(0, 0);
+    assert_eq!(single.tree_diameter(0), 0);
+}
+
+#[test]
+fn test_tree_path() {
+    init_logger();
+    const N: usize = 8;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N + 1);
+    t.add_path(&(0..N).collect::<Vec<_>>());
+    assert_eq!(t.tree_path(1, 5), Some(vec![1, 2, 3, 4, 5]));
+    assert_eq!(t.tree_path(6, 2), Some(vec![6, 5, 4, 3, 2]));
+    assert_eq!(t.tree_path(3, 3), Some(vec![3]));
+    assert_eq!(t.tree_path(0, N), None);
+    // Closing the cycle adds a chord that isn't in the spanning tree, so the path still goes the long way
+    t.add_edge(N - 1, 0);
+    assert_eq!(t.tree_path(0, N - 1), Some((0..N).collect()));
+    assert_eq!(t.tree_path(N - 1, 1), Some((1..N).rev().collect()));
+    // Removing a tree edge makes the chord a tree edge
+    t.remove_edge(3, 4);
+    assert_eq!(t.tree_path(3, 4), Some(vec![3, 2, 1, 0, 7, 6, 5, 4]));
+}
+
+#[test]
+fn test_clone_component() {
+    init_logger();
+    const N: usize = 30;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(666);
+    for _ in 0..25 {
+        t.add_edge(rng.gen_range(0..N), rng.gen_range(0..N));
+    }
+    for u in [0, 7, 19] {
+        let (mut c, original) = t.clone_component(u);
+        assert!(original.contains(&u));
+        assert!(original.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            original,
+            (0..N).filter(|&w| t.is_connected(u, w)).collect::<Vec<_>>()
+        );
+        let component_edges = original.iter().map(|&a| t.degree(a)).sum::<usize>() / 2;
+        assert_eq!(c.edges().count(), component_edges);
+        for (i, &a) in original.iter().enumerate() {
+            assert!(c.is_connected(0, i));
+            assert_eq!(c.is_in_2core(i), t.is_in_2core(a));
+            assert_eq!(c.is_in_1core(i), t.is_in_1core(a));
+            assert_eq!(c.degree(i), t.degree(a));
+            for (j, &b) in original.iter().enumerate() {
+                assert_eq!(c.are_2edge_connected(i, j), t.are_2edge_connected(a, b));
+            }
+        }
+        assert_eq!(c.validate(), Ok(()));
+    }
+}
+
+#[test]
+fn test_two_core_bitset() {
+    init_logger();
+    const N: usize = 150;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    assert_eq!(t.two_core_bitset(), [0, 0, 0]);
+    let mut rng = rand::rngs::StdRng::seed_from_u64(667);
+    for round in 0..4 {
+        for _ in 0..[120, 60][round % 2] {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            if round % 2 == 0 {
+                t.add_edge(u, v);
+            } else {
+                t.remove_edge(u, v);
+            }
+        }
+        let bits = t.two_core_bitset();
+        assert_eq!(bits.len(), N.div_ceil(64));
+        let in_2core: Vec<bool> = (0..bits.len() * 64)
+            .map(|u| bits[u / 64] >> (u % 64) & 1 == 1)
+            .collect();
+        assert!(in_2core[N..].iter().all(|&b| !b));
+        assert!(in_2core.contains(&true));
+        for (u, &b) in in_2core[..N].iter().enumerate() {
+            assert_eq!(b, t.is_in_2core(u), "vertex {u}");
+        }
+    }
+}
+
+#[test]
+fn test_batch() {
+    init_logger();
+    const N: usize = 30;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(669);
+    let ops: Vec<(bool, usize, usize)> = (0..300)
+        .map(|_| (rng.gen_bool(0.6), rng.gen_range(0..N), rng.gen_range(0..N)))
+        .collect();
+    let (mut t1, mut t2) = (
+        D2CSolver::<TreapETT, LCT<Treaps>>::new(N),
+        D2CSolver::<TreapETT, LCT<Treaps>>::new(N),
+    );
+    t1.add_cycle(&[0, 1, 2]);
+    t2.add_cycle(&[0, 1, 2]);
+    let core_before: Vec<_> = (0..N).map(|u| t2.is_in_2core(u)).collect();
+    let ((tx1, rx1), (tx2, rx2)) = (std::sync::mpsc::channel(), std::sync::mpsc::channel());
+    t1.set_event_sender(tx1);
+    t2.set_event_sender(tx2);
+    let mut results = vec![];
+    for &(add, u, v) in &ops {
+        results.push(if add {
+            t1.add_edge(u, v)
+        } else {
+            t1.remove_edge(u, v)
+        });
+    }
+    let mut batch_results = vec![];
+    t2.batch(|b| {
+        for &(add, u, v) in &ops {
+            batch_results.push(if add {
+                b.add_edge(u, v)
+            } else {
+                b.remove_edge(u, v)
+            });
+        }
+    });
+    assert_eq!(results, batch_results);
+    assert_eq!(format!("{t1:?}"), format!("{t2:?}"));
+    assert_eq!(t2.validate(), Ok(()));
+    for u in 0..N {
+        assert_eq!(t1.is_in_2core(u), t2.is_in_2core(u));
+        for v in 0..N {
+            assert_eq!(t1.is_connected(u, v), t2.is_connected(u, v));
+        }
+    }
+    // The same events, except 2-core changes are only sent at the end, for the vertices that changed overall
+    let is_core_event = |e: &GraphEvent| matches!(e, GraphEvent::CoreChanged { .. });
+    let (events1, events2): (Vec<_>, Vec<_>) = (rx1.try_iter().collect(), rx2.try_iter().collect());
+    let (core2, others2): (Vec<_>, Vec<_>) = events2.into_iter().partition(is_core_event);
+    assert_eq!(
+        events1
+            .into_iter()
+            .filter(|e| !is_core_event(e))
+            .collect::<Vec<_>>(),
+        others2
+    );
+    let expected_core: Vec<_> = (0..N)
+        .filter(|&u| t2.is_in_2core(u) != core_before[u])
+        .map(|vertex| GraphEvent::CoreChanged {
+            vertex,
+            in_2core: !core_before[vertex],
+        })
+        .collect();
+    assert!(!expected_core.is_empty());
+    assert_eq!(core2, expected_core);
+}
+
+#[test]
+fn test_remove_vertex() {
+    init_logger();
+    const N: usize = 20;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(672);
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    for _ in 0..40 {
+        t.add_edge(rng.gen_range(0..N), rng.gen_range(0..N));
+    }
+    t.add_cycle(&[3, 4, 5]);
+    t.remove_vertex(3);
+    t.remove_vertex(7);
+    assert_eq!(t.degree(3), 0);
+    // Removed vertices reject edges until they are added back.
+    assert!(!t.add_edge(0, 3));
+    assert!(!t.remove_edge(7, 8));
+    assert_eq!(t.validate(), Ok(()));
+    // The rest of the graph behaves as if it was built without the removed vertices.
+    let mut fresh =
+        D2CSolver::<TreapETT, LCT<Treaps>>::from_edges(N, t.edges().collect::<Vec<_>>());
+    for u in (0..N).filter(|&u| u != 3 && u != 7) {
+        assert_eq!(t.is_in_2core(u), fresh.is_in_2core(u), "2-core of {u}");
+        for v in (0..N).filter(|&v| v != 3 && v != 7) {
+            assert_eq!(t.is_connected(u, v), fresh.is_connected(u, v));
+        }
+    }
+    // Freed indices are reused smallest first, and only then the graph grows.
+    assert_eq!(t.add_vertex(), 3);
+    assert_eq!(t.add_vertex(), 7);
+    assert_eq!(t.add_vertex(), N);
+    // Growing makes room for more vertices, but only the added one is part of the graph.
+    let comps = t.connected_components();
+    assert_eq!(comps.iter().map(Vec::len).sum::<usize>(), N + 1);
+    assert!(comps.iter().flatten().all(|&u| u <= N));
+    assert_eq!(t.degree_histogram().iter().sum::<usize>(), N + 1);
+    assert!(!t.is_connected(3, 4));
+    t.add_cycle(&[3, 7, N]);
+    assert!(t.is_in_2core(N));
+    assert_eq!(t.validate(), Ok(()));
+    // Room allocated ahead isn't counted until its vertices are added.
+    assert_eq!(t.stats().n, N + 1);
+    assert_eq!(t.add_vertex(), N + 1);
+    assert_eq!(t.stats().n, N + 2);
+    assert_eq!(t.validate(), Ok(()));
+}
+
+#[test]
+fn test_diff() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(8);
+    t.add_path(&[0, 1, 2]);
+    t.add_edge(3, 4);
+    t.add_cycle(&[5, 6, 7]);
+    let prev = t.freeze();
+    assert_eq!(t.diff(&prev), GraphDiff::default());
+    t.add_edge(2, 3);
+    t.add_edge(0, 2);
+    t.remove_edge(6, 7);
+    assert_eq!(
+        t.diff(&prev),
+        GraphDiff {
+            component_changed: vec![0, 1, 2, 3, 4],
+            entered_2core: vec![0, 1, 2],
+            left_2core: vec![5, 6, 7],
+        }
+    );
+    let prev = t.freeze();
+    t.remove_edge(2, 3);
+    t.remove_edge(5, 6);
+    // Adding back an edge that was removed leaves its component as it was.
+    t.remove_edge(0, 1);
+    t.add_edge(0, 1);
+    assert_eq!(
+        t.diff(&prev),
+        GraphDiff {
+            component_changed: vec![0, 1, 2, 3, 4, 5, 6, 7],
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn test_growable_solver() {
+    init_logger();
+    D2CTests::<GrowableSolver<TreapETT, LCT<Treaps>>>::test_all();
+    const N: usize = 200;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(678);
+    let mut t = GrowableSolver::<TreapETT, LCT<Treaps>>::new(0);
+    let mut edges = vec![];
+    // Each new vertex is attached to a random older one, sometimes closing a cycle too.
+    for u in 1..N {
+        let v = rng.gen_range(0..u);
+        assert!(t.add_edge(v, u));
+        edges.push((v, u));
+        if rng.gen_bool(0.1) {
+            let w = rng.gen_range(0..u);
+            if t.add_edge(u, w) {
+                edges.push((u, w));
+            }
+        }
+    }
+    assert!(!t.is_connected(0, N));
+    assert!(!t.is_in_1core(N + 10));
+    assert!(!t.remove_edge(N, N + 1));
+    assert_eq!(t.validate(), Ok(()));
+    let mut fresh = D2CSolver::<TreapETT, LCT<Treaps>>::from_edges(N, edges);
+    for u in 0..N {
+        assert_eq!(t.is_in_2core(u), fresh.is_in_2core(u), "2-core of {u}");
+        assert!(t.is_connected(0, u));
+    }
+    assert_eq!(t.num_independent_cycles(), fresh.num_independent_cycles());
+}
+
+#[test]
+fn test_remove_edge_profiled() {
+    init_logger();
+    const N: usize = 64;
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    t.add_path(&(0..N).collect::<Vec<_>>());
+    t.add_edge(N - 1, 0);
+    // The only replacement for an edge of the path 0..N is the closing edge, which is only found after moving a whole
+    // half of the path up a level.
+    let (mid_l, mid_r) = (N / 2 - 1, N / 2);
+    assert_eq!(
+        t.remove_edge_profiled(mid_l, mid_r),
+        (
+            true,
+            RemoveStats {
+                tree_promotions: N / 2 - 1,
+                extra_promotions: 0,
+                levels_searched: 1,
+                replacement_found: true,
+            }
+        )
+    );
+    // Now it is an extra edge, which does no work to remove
+    t.add_edge(mid_l, mid_r);
+    assert_eq!(
+        t.remove_edge_profiled(mid_l, mid_r),
+        (true, RemoveStats::default())
+    );
+    assert_eq!(
+        t.remove_edge_profiled(mid_l, mid_r),
+        (false, RemoveStats::default())
+    );
+    t.add_edge(mid_l, mid_r);
+    // Cut the cycle all around a few times. Each edge goes up at most lg N levels, which bounds the total work.
+    let before = t.total_promotions();
+    let mut promotions = 0;
+    for i in 0..4 * N {
+        let (u, v) = (i % N, (i + 1) % N);
+        let (removed, stats) = t.remove_edge_profiled(u, v);
+        assert!(removed);
+        assert!(stats.replacement_found || stats == RemoveStats::default());
+        assert!(stats.levels_searched <= N.ilog2() as usize + 1);
+        promotions += stats.tree_promotions + stats.extra_promotions;
+        t.add_edge(u, v);
+    }
+    assert_eq!(promotions as u64, t.total_promotions() - before);
+    assert!(promotions > 0 && promotions <= N * N.ilog2() as usize);
+}
+
+#[test]
+fn test_promote_side() {
+    init_logger();
+    const N: usize = 40;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(689);
+    let sides = [PromoteSide::Smaller, PromoteSide::Larger, PromoteSide::Root];
+    let mut ts: Vec<_> = sides
+        .iter()
+        .map(|&side| {
+            let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+            t.set_promote_side(side);
+            t
+        })
+        .collect();
+    for q in 0..3000 {
+        let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+        let add = rng.gen_bool(0.6);
+        for t in &mut ts {
+            if add {
+                t.add_edge(u, v);
+            } else {
+                t.remove_edge(u, v);
+            }
+        }
+        if q % 50 == 0 {
+            let expected = ts[0].freeze();
+            for t in &mut ts[1..] {
+                t.validate().unwrap();
+                assert_eq!(t.diff(&expected), GraphDiff::default());
+            }
+        }
+    }
+    // Promoting the larger side costs more
+    assert!(ts[1].total_promotions() > ts[0].total_promotions());
+}
+
+#[test]
+fn test_adjacency_matrix() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(6);
+    assert_eq!(t.adjacency_matrix(), vec![vec![false; 6]; 6]);
+    t.add_cycle(&[0, 1, 2]);
+    t.add_path(&[2, 3, 5]);
+    t.add_edge(1, 2);
+    t.remove_edge(0, 1);
+    let matrix = t.adjacency_matrix();
+    for (u, row) in matrix.iter().enumerate() {
+        for (v, &adjacent) in row.iter().enumerate() {
+            assert_eq!(adjacent, t.neighbors(u).any(|w| w == v), "({u}, {v})");
+        }
+    }
+    assert_eq!(matrix.iter().flatten().filter(|&&b| b).count(), 2 * 4);
+}
+
+#[test]
+fn test_path_min_edge() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(8);
+    let weights = BTreeMap::from([
+        ((0, 1), 5),
+        ((1, 2), 3),
+        ((2, 3), 7),
+        ((3, 4), 2),
+        ((4, 5), 9),
+        ((6, 7), 1),
+    ]);
+    for &(u, v) in weights.keys() {
+        t.add_edge(u, v);
+    }
+    let w = |a: usize, b: usize| weights[&(a.min(b), a.max(b))];
+    assert_eq!(t.path_min_edge(0, 5, w), Some((3, 4, 2)));
+    assert_eq!(t.path_min_edge(5, 0, w), Some((4, 3, 2)));
+    assert_eq!(t.path_min_edge(0, 3, w), Some((1, 2, 3)));
+    assert_eq!(t.path_min_edge(4, 5, w), Some((4, 5, 9)));
+    assert_eq!(t.path_min_edge(2, 2, w), None);
+    assert_eq!(t.path_min_edge(0, 7, w), None);
+    // The first of equal weights on the path
+    assert_eq!(t.path_min_edge(1, 3, |_, _| 0), Some((1, 2, 0)));
+    // Only tree edges are on the path, so the result follows the spanning forest
+    t.add_edge(0, 5);
+    let w = |a: usize, b: usize| {
+        if (a.min(b), a.max(b)) == (0, 5) {
+            0
+        } else {
+            w(a, b)
+        }
+    };
+    assert_eq!(t.path_min_edge(0, 5, w), Some((3, 4, 2)));
+    t.remove_edge(3, 4);
+    assert_eq!(t.path_min_edge(0, 4, w), Some((0, 5, 0)));
+}
+
+#[test]
+fn test_stats() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(12);
+    assert_eq!(
+        t.stats(),
+        SolverStats {
+            n: 12,
+            num_components: 12,
+            ..SolverStats::default()
+        }
+    );
+    // Triangle 0-1-2 with the tail 2-3-4, square 5-6-7-8, the edge 9-10, 11 removed
+    t.add_cycle(&[0, 1, 2]);
+    t.add_path(&[2, 3, 4]);
+    t.add_cycle(&[5, 6, 7, 8]);
+    t.add_edge(9, 10);
+    t.remove_vertex(11);
+    assert_eq!(
+        t.stats(),
+        SolverStats {
+            n: 11,
+            total_edges: 10,
+            num_tree_edges: 8,
+            num_components: 3,
+            two_core_size: 7,
+            max_level: 0,
+            total_promotions: 0,
+        }
+    );
+    // 7-8 is the extra edge, as add_cycle adds the closing edge first. It replaces 5-6, after the tree edge on the
+    // side searched is promoted.
+    t.remove_edge(5, 6);
+    let stats = t.stats();
+    assert_eq!(
+        (
+            stats.total_edges,
+            stats.num_tree_edges,
+            stats.num_components
+        ),
+        (9, 8, 3)
+    );
+    assert_eq!(stats.two_core_size, 3);
+    assert!(stats.max_level >= 1);
+    assert_eq!(stats.total_promotions, t.total_promotions());
+    assert!(stats.total_promotions >= 1);
+}
+
+/// Weight of a minimum spanning forest with Prim's algorithm from each unvisited vertex.
+fn slow_msf_weight(n: usize, weights: &BTreeMap<(usize, usize), i64>) -> i64 {
+    let w = |a: usize, b: usize| weights.get(&(a.min(b), a.max(b))).copied();
+    let mut in_forest = vec![false; n];
+    let mut total = 0;
+    for start in 0..n {
+        if in_forest[start] {
+            continue;
+        }
+        in_forest[start] = true;
+        let mut tree = vec![start];
+        // Cheapest edge leaving the tree, while there is one
+        while let Some((c, v)) = tree
+            .iter()
+            .flat_map(|&u| (0..n).filter_map(move |v| Some((w(u, v)?, v))))
+            .filter(|&(_, v)| !in_forest[v])
+            .min()
+        {
+            total += c;
+            in_forest[v] = true;
+            tree.push(v);
+        }
+    }
+    total
+}
+
+#[test]
+fn test_compute_msf_weight() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(6);
+    // Square 0-1-2-3 with a diagonal, and the separate edge 4-5
+    let mut weights = BTreeMap::from([
+        ((0, 1), 4),
+        ((1, 2), 1),
+        ((2, 3), 3),
+        ((0, 3), 2),
+        ((0, 2), 5),
+        ((4, 5), 10),
+    ]);
+    for &(u, v) in weights.keys() {
+        t.add_edge(u, v);
+    }
+    let msf = |n: usize, t: &D2CSolver<_, _>, weights: &BTreeMap<(usize, usize), i64>| {
+        compute_msf_weight(n, t.edges(), |u, v| weights[&(u, v)])
+    };
+    assert_eq!(msf(6, &t, &weights), 1 + 3 + 2 + 10);
+    t.remove_edge(2, 3);
+    weights.remove(&(2, 3));
+    assert_eq!(msf(6, &t, &weights), 1 + 2 + 4 + 10);
+    assert_eq!(compute_msf_weight(3, [], |_, _| 1), 0);
+    // Compare with Prim's algorithm as edges come and go
+    const N: usize = 12;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(698);
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    let mut weights = BTreeMap::new();
+    for _ in 0..300 {
+        let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+        let e = (u.min(v), u.max(v));
+        if t.remove_edge(u, v) {
+            weights.remove(&e);
+        } else if t.add_edge(u, v) {
+            weights.insert(e, rng.gen_range(-5..20));
+        }
+        assert_eq!(msf(N, &t, &weights), slow_msf_weight(N, &weights));
+    }
+}
+
+#[test]
+#[cfg(feature = "serde_json")]
+fn test_to_json() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Vertex {
+        id: usize,
+        core: u8,
+    }
+    #[derive(serde::Deserialize, Debug)]
+    struct Edge {
+        u: usize,
+        v: usize,
+        tree: bool,
+        level: usize,
+    }
+    #[derive(serde::Deserialize, Debug)]
+    struct Graph {
+        vertices: Vec<Vertex>,
+        edges: Vec<Edge>,
+    }
+    init_logger();
+    // Cycle 0..6 with 6 hanging from 5, 7 isolated and 8 removed
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(9);
+    t.add_path(&[0, 1, 2, 3, 4, 5, 6]);
+    t.add_edge(5, 0);
+    t.add_edge(8, 0);
+    t.remove_vertex(8);
+    let graph: Graph = serde_json::from_str(&t.to_json()).unwrap();
+    let core = |id, core| Vertex { id, core };
+    assert_eq!(
+        graph.vertices,
+        [
+            core(0, 2),
+            core(1, 2),
+            core(2, 2),
+            core(3, 2),
+            core(4, 2),
+            core(5, 2),
+            core(6, 1),
+            core(7, 0)
+        ]
+    );
+    let edges: Vec<_> = graph.edges.iter().map(|e| (e.u, e.v)).collect();
+    assert_eq!(edges, t.edges().collect::<Vec<_>>());
+    assert_eq!(graph.edges.iter().filter(|e| !e.tree).count(), 1);
+    assert!(graph.edges.iter().all(|e| e.level == 0));
+    // The replacement search promotes edges, but never the removed one
+    t.remove_edge(2, 3);
+    let graph: Graph = serde_json::from_str(&t.to_json()).unwrap();
+    assert!(graph.edges.iter().all(|e| e.tree));
+    assert_eq!(
+        graph.edges.iter().map(|e| e.level as u64).sum::<u64>(),
+        t.total_promotions()
+    );
+    assert!(graph.vertices.iter().all(|v| v.core == 1 || v.id == 7));
+}
+
+/// Checks edges_by_level is a grouping of edges() consistent with stats().
+fn check_edges_by_level(t: &D2CSolver<TreapETT, LCT<Treaps>>) -> Vec<Vec<(usize, usize, bool)>> {
+    let levels = t.edges_by_level();
+    let stats = t.stats();
+    let mut all: Vec<_> = levels.iter().flatten().map(|&(u, v, _)| (u, v)).collect();
+    all.sort_unstable();
+    assert_eq!(all, t.edges().collect::<Vec<_>>());
+    assert_eq!(
+        levels.iter().flatten().filter(|e| e.2).count(),
+        stats.num_tree_edges
+    );
+    assert_eq!(
+        levels.len(),
+        stats.max_level + (stats.total_edges > 0) as usize
+    );
+    assert!(levels.last().is_none_or(|l| !l.is_empty()));
+    levels
+}
+
+#[test]
+fn test_edges_by_level() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(8);
+    assert!(check_edges_by_level(&t).is_empty());
+    // Cycle 0..6 with 6 hanging from 5. 5-0 is the only extra edge.
+    t.add_path(&[0, 1, 2, 3, 4, 5, 6]);
+    t.add_edge(5, 0);
+    let levels = check_edges_by_level(&t);
+    assert_eq!(levels.len(), 1);
+    assert_eq!(
+        levels[0].iter().filter(|e| !e.2).collect::<Vec<_>>(),
+        [&(0, 5, false)]
+    );
+    // Replacing 2-3 promotes the searched side, so the sum of levels is the number of promotions
+    t.remove_edge(2, 3);
+    let levels = check_edges_by_level(&t);
+    assert!(levels.len() > 1);
+    let level_sum: usize = levels.iter().enumerate().map(|(i, l)| i * l.len()).sum();
+    assert_eq!(level_sum as u64, t.total_promotions());
+    assert!(levels.iter().flatten().all(|e| e.2));
+    // Compare with edges() as edges come and go
+    const N: usize = 15;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(705);
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    for _ in 0..300 {
+        let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+        if !t.remove_edge(u, v) {
+            t.add_edge(u, v);
+        }
+        check_edges_by_level(&t);
+    }
+    assert!(t.total_promotions() > 0);
+}
+
+#[test]
+fn test_bridges() {
+    init_logger();
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(12);
+    // Two triangles joined by a path, with a tail, and a separate edge
+    t.add_cycle(&[0, 1, 2]);
+    t.add_path(&[2, 3, 4]);
+    t.add_cycle(&[4, 5, 6]);
+    t.add_path(&[6, 7, 8]);
+    t.add_edge(10, 11);
+    assert_eq!(t.bridges(), [(2, 3), (3, 4), (6, 7), (7, 8), (10, 11)]);
+    t.add_edge(1, 5);
+    assert_eq!(t.bridges(), [(6, 7), (7, 8), (10, 11)]);
+    // Compare with removing each edge on random graphs
+    const N: usize = 25;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(687);
+    for m in [10, 20, 30, 40] {
+        let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+        for _ in 0..m {
+            t.add_edge(rng.gen_range(0..N), rng.gen_range(0..N));
+        }
+        let mut expected = vec![];
+        for (u, v) in t.edges().collect::<Vec<_>>() {
+            let components = t.connected_components().len();
+            t.remove_edge(u, v);
+            if t.connected_components().len() > components {
+                expected.push((u, v));
+            }
+            t.add_edge(u, v);
+        }
+        expected.sort_unstable();
+        assert_eq!(t.bridges(), expected);
+        assert!(t
+            .edges()
+            .all(|(u, v)| t.would_disconnect(u, v) == expected.contains(&(u, v))));
+    }
+}
+
+#[test]
+fn test_would_disconnect() {
+    init_logger();
+    const N: usize = 30;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(685);
+    let mut t = D2CSolver::<TreapETT, LCT<Treaps>>::new(N);
+    for _ in 0..40 {
+        t.add_edge(rng.gen_range(0..N), rng.gen_range(0..N));
+    }
+    assert!(!t.would_disconnect(0, 0));
+    for _ in 0..300 {
+        let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+        let components = t.connected_components().len();
+        let would_disconnect = t.would_disconnect(u, v);
+        assert_eq!(would_disconnect, t.would_disconnect(v, u));
+        if t.remove_edge(u, v) {
+            assert_eq!(
+                would_disconnect,
+                t.connected_components().len() > components,
+                "({u}, {v})"
+            );
+            // Sometimes keep it removed, so the graph changes
+            if rng.gen_bool(0.8) {
+                t.add_edge(u, v);
+            }
+        } else {
+            assert!(!would_disconnect);
+            t.add_edge(u, v);
+        }
+    }
+}
+
 fn stress_iter() {
     let seed: u64 = thread_rng().gen();
     log::info!("seed = {seed}");
@@ -1,8 +1,9 @@
 use common::{slow_lists::SlowLists, AggSum};
 use dynamic_2core::{
-    euler_tour_tree::{EdgeRef, EulerTourTree},
-    lists::{splay::Splays, treap::Treaps, Idx, Lists},
+    euler_tour_tree::{EdgeRef, EulerTourTree, MergePolicy, NodeOnlyAgg},
+    lists::{splay::Splays, treap::Treaps, AggregatedData, Idx, Lists, Monoid},
 };
+use rand::{Rng, SeedableRng};
 
 mod common;
 
@@ -26,7 +27,7 @@ where
 
     // Uses the data for nodes and for edges.
     fn assert_node_order(t: &mut ETT<L>, root: Idx, order: &[i32]) {
-        let l = t.inner_lists();
+        let l = t.inner_lists_mut();
         let mut node = l.first(root);
         assert_eq!(l.len(node), order.len());
         for (i, x) in order.iter().enumerate() {
@@ -81,10 +82,497 @@ where
         Self::assert_node_order(t, 2, &[2, 23, 3, 32, 21, 10, 04, 4, 40, 0, 01, 1, 12]);
     }
 
+    fn test_reroot_returning() {
+        let t = &mut Self::build(6);
+        for (u, v) in [(0, 4), (0, 1), (1, 2), (2, 3)] {
+            Self::connect(t, u, v);
+        }
+        let tour = [0, 01, 1, 12, 2, 23, 3, 32, 21, 10, 04, 4, 40];
+        assert_eq!(t.reroot_returning(3), 0);
+        Self::assert_node_order(t, 2, &[3, 32, 21, 10, 04, 4, 40, 0, 01, 1, 12, 2, 23]);
+        assert_eq!(t.reroot_returning(4), 3);
+        assert_eq!(t.reroot_returning(4), 4);
+        assert_eq!(t.reroot_returning(3), 4);
+        assert_eq!(t.reroot_returning(0), 3);
+        Self::assert_node_order(t, 2, &tour);
+        assert_eq!(t.reroot_returning(5), 5);
+    }
+
+    fn test_merge_policy() {
+        let t = &mut Self::build(5);
+        Self::connect(t, 0, 1);
+        // Default keeps the root of the first node
+        Self::connect(t, 2, 0);
+        assert_eq!(t.root(1), 2);
+        Self::assert_node_order(t, 0, &[2, 20, 0, 01, 1, 10, 02]);
+
+        let t = &mut Self::build(5);
+        Self::connect(t, 0, 1);
+        t.connect_with_policy(2, 0, e(2, 0), e(0, 2), MergePolicy::LargerTree)
+            .unwrap();
+        assert_eq!(t.root(2), 0);
+        Self::assert_node_order(t, 0, &[0, 02, 2, 20, 01, 1, 10]);
+        Self::assert_all_connections(t, &["11100", "11100", "11100", "00010", "00001"]);
+
+        let t = &mut Self::build(5);
+        Self::connect(t, 3, 4);
+        t.connect_with_policy(3, 1, e(3, 1), e(1, 3), MergePolicy::SmallerRoot)
+            .unwrap();
+        assert_eq!(t.root(4), 1);
+        t.connect_with_policy(2, 4, e(2, 4), e(4, 2), MergePolicy::SmallerRoot)
+            .unwrap();
+        assert_eq!(t.root(2), 1);
+        Self::assert_all_connections(t, &["10000", "01111", "01111", "01111", "01111"]);
+        assert!(t
+            .connect_with_policy(1, 2, 0, 0, MergePolicy::LargerTree)
+            .is_none());
+    }
+
+    fn test_adjacent() {
+        let t = &mut Self::build(5);
+        let mut edges = vec![];
+        for i in 0..4 {
+            edges.push(Self::connect(t, i, i + 1));
+        }
+        for u in 0..5 {
+            for v in 0..5 {
+                assert_eq!(t.are_adjacent(u, v), u.abs_diff(v) == 1, "{u} {v}");
+            }
+        }
+        t.disconnect(edges[2]); // 2-3
+        assert!(!t.are_adjacent(2, 3));
+        assert!(!t.are_adjacent(3, 2));
+        assert!(t.are_adjacent(3, 4));
+        Self::connect(t, 4, 0);
+        assert!(t.are_adjacent(0, 4));
+        assert!(!t.are_adjacent(2, 3));
+    }
+
+    fn test_node_count() {
+        let t = &mut Self::build(0);
+        assert_eq!(t.node_count(), 0);
+        let t = &mut Self::build(6);
+        assert_eq!(t.node_count(), 6);
+        let e = Self::connect(t, 0, 1);
+        Self::connect(t, 1, 2);
+        Self::connect(t, 4, 5);
+        assert_eq!(t.node_count(), 6);
+        t.disconnect(e);
+        Self::connect(t, 3, 0);
+        assert_eq!(t.node_count(), 6);
+        assert!(t.inner_lists().total_size() > 6);
+    }
+
+    fn test_disconnect_roots_are_nodes() {
+        let t = &mut Self::build(5);
+        let e01 = Self::connect(t, 0, 1);
+        Self::connect(t, 0, 2);
+        let e23 = Self::connect(t, 2, 3);
+        Self::connect(t, 3, 4);
+        // Now the edge 0-2 appears as 20 before 02 in the tour
+        t.reroot(4);
+        let (a, b) = t.disconnect(e23);
+        assert_eq!((a, b), (4, 2));
+        // The tour of 2's side starts on an edge leaving 2
+        assert!(t.inner_lists_mut().first(0) >= 5);
+        t.reroot(2);
+        Self::assert_node_order(t, 0, &[2, 20, 01, 1, 10, 0, 02]);
+        let (a, b) = t.disconnect(e01);
+        assert_eq!((a, b), (2, 1));
+        for u in 0..5 {
+            assert!(t.root(u) < 5, "root of {u} is not a node");
+        }
+    }
+
+    fn test_edge_ref_between() {
+        let t = &mut Self::build(6);
+        let pairs = [(0, 1), (2, 1), (1, 3), (4, 5), (5, 3)];
+        let edges: Vec<_> = pairs.iter().map(|&(u, v)| Self::connect(t, u, v)).collect();
+        for (&(u, v), &edge) in pairs.iter().zip(&edges) {
+            for (a, b) in [(u, v), (v, u)] {
+                let found = t.edge_ref_between(a, b).unwrap();
+                assert_eq!(found.inner_idx(), edge.inner_idx());
+                assert_eq!(t.endpoints(found), [u, v]);
+                assert_eq!(t.edata(found), [&e(u, v), &e(v, u)]);
+            }
+        }
+        assert!(t.edge_ref_between(0, 2).is_none());
+        assert!(t.edge_ref_between(4, 4).is_none());
+        t.reroot(4);
+        let edge = t.edge_ref_between(3, 5).unwrap();
+        assert_eq!(t.endpoints(edge), [5, 3]);
+        t.disconnect(edges[2]);
+        assert!(t.edge_ref_between(1, 3).is_none());
+        assert!(t.edge_ref_between(3, 1).is_none());
+        // Compare with the set of edges as the forest changes
+        const N: usize = 10;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(609);
+        let t = &mut Self::build(N);
+        let mut edges = std::collections::BTreeMap::new();
+        for _ in 0..200 {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            if let Some(edge) = edges.remove(&(u.min(v), u.max(v))) {
+                t.disconnect(edge);
+            } else if let Some(edge) = t.connect(u, v, e(u, v), e(v, u)) {
+                edges.insert((u.min(v), u.max(v)), edge);
+            } else {
+                t.reroot(u);
+            }
+            for (a, b) in (0..N).flat_map(|a| (0..N).map(move |b| (a, b))) {
+                let expected = edges.get(&(a.min(b), a.max(b))).map(|e| e.inner_idx());
+                let found = t.edge_ref_between(a, b).map(|e| e.inner_idx());
+                assert_eq!(found, expected, "{a} {b}");
+            }
+        }
+    }
+
+    fn test_payload() {
+        let labels = ["a", "b", "c", "d"].map(String::from).to_vec();
+        let mut t = dynamic_2core::euler_tour_tree::ETT::<L, AggSum, String>::with_payloads(
+            (0..4).collect(),
+            labels.clone(),
+        );
+        let check = |t: &dynamic_2core::euler_tour_tree::ETT<L, AggSum, String>| {
+            for (u, label) in labels.iter().enumerate() {
+                assert_eq!(t.payload(u), label);
+            }
+        };
+        let e01 = t.connect(0, 1, e(0, 1), e(1, 0)).unwrap();
+        t.connect(2, 1, e(2, 1), e(1, 2)).unwrap();
+        t.connect(3, 0, e(3, 0), e(0, 3)).unwrap();
+        check(&t);
+        t.reroot(2);
+        check(&t);
+        t.disconnect(e01);
+        check(&t);
+        t.payload_mut(3).push('!');
+        assert_eq!(t.payload(3), "d!");
+        assert_eq!(t.tree_agg(3).0, 3 + e(3, 0) + e(0, 3));
+    }
+
+    fn test_connect_with() {
+        let pairs = [(0, 1), (2, 1), (1, 3), (4, 3), (0, 4)];
+        let (t1, t2) = (&mut Self::build(5), &mut Self::build(5));
+        for (u, w) in pairs {
+            let e1 = t1.connect(u, w, e(u, w), e(w, u));
+            let e2 = t2.connect_with(u, w, MergePolicy::KeepFirst, |uw| match uw {
+                true => e(u, w),
+                false => e(w, u),
+            });
+            assert_eq!(e1.map(|e| e.inner_idx()), e2.map(|e| e.inner_idx()));
+        }
+        let tour: Vec<_> = t1
+            .inner_lists_mut()
+            .view(0)
+            .into_iter()
+            .map(|(_, d)| *d)
+            .collect();
+        Self::assert_node_order(t2, 0, &tour);
+        // Data is only created when connecting
+        assert!(t2
+            .connect_with(4, 2, MergePolicy::KeepFirst, |_| unreachable!())
+            .is_none());
+    }
+
+    fn test_for_each_node() {
+        const N: usize = 6;
+        let t = &mut Self::build(N + 1);
+        for u in 0..N - 1 {
+            Self::connect(t, u, u + 1);
+        }
+        let mut seen = vec![];
+        t.for_each_node(3, |u, d| seen.push((u, *d)));
+        assert_eq!(seen, (0..N).map(|u| (u, u as i32)).collect::<Vec<_>>());
+        t.reroot(N - 1);
+        let mut seen = vec![];
+        t.for_each_node(0, |u, _| seen.push(u));
+        assert_eq!(seen, [N - 1, 0, 1, 2, 3, 4]);
+        let mut seen = vec![];
+        t.for_each_node(N, |u, _| seen.push(u));
+        assert_eq!(seen, [N]);
+    }
+
+    fn test_inner_lists_mut() {
+        let (t1, t2) = (&mut Self::build(4), &mut Self::build(4));
+        for t in [&mut *t1, &mut *t2] {
+            Self::connect(t, 0, 1);
+            Self::connect(t, 2, 1);
+        }
+        assert_eq!(t1.tree_agg(0), t2.tree_agg(0));
+        t1.mutate_data(2, |d| *d = 100);
+        t2.inner_lists_mut().mutate_data(2, |d| *d = 100);
+        assert_eq!(t1.tree_agg(0), t2.tree_agg(0));
+        let tour: Vec<_> = t1
+            .inner_lists_mut()
+            .view(0)
+            .into_iter()
+            .map(|(_, d)| *d)
+            .collect();
+        Self::assert_node_order(t2, 0, &tour);
+        assert_eq!(t2.data(2), &100);
+    }
+
+    fn test_is_ancestor() {
+        //     0
+        //    / \
+        //   1   2
+        //  / \   \
+        // 3   4   5
+        let parent = [None, Some(0), Some(0), Some(1), Some(1), Some(2)];
+        let t = &mut Self::build(7);
+        for (u, p) in parent.iter().enumerate() {
+            if let Some(p) = *p {
+                Self::connect(t, p, u);
+            }
+        }
+        // The expected ancestors of v are found by walking up parent, which is given again after each reroot.
+        let assert_ancestors = |t: &mut ETT<L>, parent: &[Option<usize>]| {
+            for v in 0..parent.len() {
+                let mut ancestors = vec![false; parent.len()];
+                let mut w = Some(v);
+                while let Some(x) = w {
+                    ancestors[x] = true;
+                    w = parent[x];
+                }
+                for (u, is_ancestor) in ancestors.into_iter().enumerate() {
+                    assert_eq!(t.is_ancestor(u, v), is_ancestor, "is_ancestor({u}, {v})");
+                }
+                assert!(!t.is_ancestor(v, 6) && !t.is_ancestor(6, v));
+            }
+        };
+        assert_ancestors(t, &parent);
+        t.reroot(4);
+        assert_ancestors(t, &[Some(1), Some(4), Some(0), Some(1), None, Some(2)]);
+        t.reroot(5);
+        assert_ancestors(t, &[Some(2), Some(0), Some(5), Some(1), Some(1), None]);
+        assert!(t.is_ancestor(6, 6));
+    }
+
+    fn test_edge_orientation() {
+        // 0 - 1 - 3 - 4 - 5
+        //     |
+        //     2
+        let pairs = [(0, 1), (2, 1), (1, 3), (4, 3), (4, 5)];
+        let policies = [
+            MergePolicy::KeepFirst,
+            MergePolicy::LargerTree,
+            MergePolicy::SmallerRoot,
+        ];
+        let t = &mut Self::build(6);
+        let edges: Vec<_> = pairs
+            .iter()
+            .zip(policies.iter().cycle())
+            .map(|(&(u, w), &policy)| {
+                let (uw, wu) = (100 + e(u, w), 200 + e(u, w));
+                t.connect_with_policy(u, w, uw, wu, policy).unwrap()
+            })
+            .collect();
+        // Nodes on w's side of the edge (u, w), found by walking the other edges
+        let side_of = |u: usize, w: usize| {
+            let mut side = vec![w];
+            let mut i = 0;
+            while i < side.len() {
+                for &(a, b) in &pairs {
+                    for (x, y) in [(a, b), (b, a)] {
+                        if x == side[i] && y != u && !side.contains(&y) {
+                            side.push(y);
+                        }
+                    }
+                }
+                i += 1;
+            }
+            side
+        };
+        for root in [0, 2, 5, 3, 1, 4] {
+            t.reroot(root);
+            let tour: Vec<_> = t
+                .inner_lists_mut()
+                .view(root)
+                .into_iter()
+                .map(|(_, d)| *d)
+                .collect();
+            for (&(u, w), &edge) in pairs.iter().zip(&edges) {
+                let (uw, wu) = (100 + e(u, w), 200 + e(u, w));
+                assert_eq!(t.endpoints(edge), [u, w]);
+                assert_eq!(t.edata(edge), [&uw, &wu]);
+                // The nodes between both entries are the side away from the root
+                let pos = |d| tour.iter().position(|&x| x == d).unwrap();
+                let (first, second) = (pos(uw).min(pos(wu)), pos(uw).max(pos(wu)));
+                let mut inside: Vec<_> = tour[first..second]
+                    .iter()
+                    .filter_map(|&d| usize::try_from(d).ok().filter(|&d| d < 6))
+                    .collect();
+                inside.sort();
+                let w_side = side_of(u, w);
+                let (mut expected, uw_first) = if w_side.contains(&root) {
+                    (side_of(w, u), false)
+                } else {
+                    (w_side, true)
+                };
+                expected.sort();
+                assert_eq!(inside, expected, "edge {u}-{w} rooted at {root}");
+                assert_eq!(pos(uw) < pos(wu), uw_first);
+            }
+        }
+        // Each direction is changed independently
+        t.mutate_edata(edges[1], false, |d| *d = -1);
+        t.mutate_edata(edges[1], true, |d| *d = -2);
+        assert_eq!(t.edata(edges[1]), [&-1, &-2]);
+    }
+
+    fn test_edge_orientation_stress() {
+        const N: usize = 30;
+        // Node u has data u, and the entry going from u to w has 1000 + 100 * u + w
+        let step = |d: i32| {
+            let d = d as usize;
+            if d < N {
+                (d, d)
+            } else {
+                ((d - 1000) / 100, (d - 1000) % 100)
+            }
+        };
+        let mut rng = rand::rngs::StdRng::seed_from_u64(701);
+        let policies = [
+            MergePolicy::KeepFirst,
+            MergePolicy::LargerTree,
+            MergePolicy::SmallerRoot,
+        ];
+        let t = &mut Self::build(N);
+        let mut edges = vec![];
+        for _ in 0..2000 {
+            let (u, w) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            match rng.gen_range(0..3) {
+                0 => {
+                    let policy = policies[rng.gen_range(0..3)];
+                    let (uw, wu) = ((1000 + 100 * u + w) as i32, (1000 + 100 * w + u) as i32);
+                    if let Some(edge) = t.connect_with_policy(u, w, uw, wu, policy) {
+                        edges.push(edge);
+                    }
+                }
+                1 if !edges.is_empty() => {
+                    let edge = edges.swap_remove(rng.gen_range(0..edges.len()));
+                    t.disconnect(edge);
+                }
+                _ => t.reroot(u),
+            }
+            for &edge in &edges {
+                let [a, b] = t.endpoints(edge);
+                let [ab, ba] = t.edata(edge);
+                assert_eq!((step(*ab), step(*ba)), ((a, b), (b, a)));
+            }
+            // Each entry of the tour starts where the previous one ended
+            let tour: Vec<_> = t
+                .inner_lists_mut()
+                .view(u)
+                .into_iter()
+                .map(|(_, d)| step(*d))
+                .collect();
+            // It may start on an edge after a disconnect, but always from the root
+            assert_eq!(tour[0].0, t.root(u));
+            for (i, &(_, to)) in tour.iter().enumerate() {
+                assert_eq!(to, tour[(i + 1) % tour.len()].0, "{tour:?}");
+            }
+        }
+    }
+
     fn test_all() {
         Self::test_simple();
         Self::test_reroot();
+        Self::test_reroot_returning();
+        Self::test_merge_policy();
+        Self::test_adjacent();
+        Self::test_node_count();
+        Self::test_disconnect_roots_are_nodes();
+        Self::test_edge_ref_between();
+        Self::test_payload();
+        Self::test_connect_with();
+        Self::test_for_each_node();
+        Self::test_inner_lists_mut();
+        Self::test_is_ancestor();
+        Self::test_edge_orientation();
+        Self::test_edge_orientation_stress();
+    }
+}
+
+#[derive(Debug, Clone)]
+struct AggMax(i32);
+
+impl Default for AggMax {
+    fn default() -> Self {
+        Self(i32::MIN)
+    }
+}
+
+impl Monoid for AggMax {
+    type Data = i32;
+    fn from(data: &i32) -> Self {
+        Self(*data)
+    }
+    fn merge(self, right: Self) -> Self {
+        Self(self.0.max(right.0))
+    }
+}
+
+impl AggregatedData for AggMax {
+    fn reverse(self) -> Self {
+        self
+    }
+}
+
+fn test_find_first_and_last_node<L: Lists<AggMax>>() {
+    let mut t = dynamic_2core::euler_tour_tree::ETT::<L, AggMax>::new(vec![3, 9, 1, 9, 2, 0]);
+    for i in 0..4 {
+        // Edges never match
+        t.connect(i, i + 1, -1, -1).unwrap();
     }
+    let at_least = |k: i32| (move |ag: &AggMax| ag.0 >= k, move |d: &i32| *d >= k);
+    let (agg_can, node_is) = at_least(9);
+    assert_eq!(t.find_first_node(4, agg_can, node_is), 1);
+    assert_eq!(t.find_last_node(0, agg_can, node_is), 3);
+    let (agg_can, node_is) = at_least(3);
+    assert_eq!(t.find_first_node(2, agg_can, node_is), 0);
+    assert_eq!(t.find_last_node(2, agg_can, node_is), 3);
+    let (agg_can, node_is) = at_least(10);
+    assert_eq!(t.find_first_node(2, agg_can, node_is), L::EMPTY);
+    assert_eq!(t.find_last_node(2, agg_can, node_is), L::EMPTY);
+    // Only searches in the tree of the given node
+    let (agg_can, node_is) = at_least(0);
+    assert_eq!(t.find_first_node(5, agg_can, node_is), 5);
+    t.reroot(3);
+    let (agg_can, node_is) = at_least(3);
+    assert_eq!(t.find_first_node(0, agg_can, node_is), 3);
+}
+
+#[test]
+fn test_find_first_and_last_node_all() {
+    test_find_first_and_last_node::<SlowLists<AggMax>>();
+    test_find_first_and_last_node::<Treaps<AggMax>>();
+    test_find_first_and_last_node::<Splays<AggMax>>();
+}
+
+fn test_node_only_agg<L: Lists<NodeOnlyAgg<AggSum>>>() {
+    let weights = [5, 1, 10, 100, 7];
+    let mut t = dynamic_2core::euler_tour_tree::ETT::<L, NodeOnlyAgg<AggSum>>::new(
+        weights.iter().map(|&w| Some(w)).collect(),
+    );
+    // Edges have no data, so they don't count towards the sum
+    let edge = t.connect(0, 1, None, None).unwrap();
+    t.connect(1, 2, None, None).unwrap();
+    t.connect(3, 0, None, None).unwrap();
+    assert_eq!(t.tree_agg(2).0, 116);
+    assert_eq!(t.tree_agg(4).0, 7);
+    t.mutate_data(3, |d| *d = Some(50));
+    assert_eq!(t.tree_agg(0).0, 66);
+    t.disconnect(edge);
+    assert_eq!(t.tree_agg(0).0, 55);
+    assert_eq!(t.tree_agg(2).0, 11);
+}
+
+#[test]
+fn test_node_only_agg_all() {
+    test_node_only_agg::<SlowLists<NodeOnlyAgg<AggSum>>>();
+    test_node_only_agg::<Treaps<NodeOnlyAgg<AggSum>>>();
+    test_node_only_agg::<Splays<NodeOnlyAgg<AggSum>>>();
 }
 
 #[test]
@@ -101,3 +589,23 @@ fn test_ett_with_treap() {
 fn test_ett_with_splay() {
     ETTTests::<Splays<_>>::test_all();
 }
+
+#[test]
+fn test_ett_churn_nodes() {
+    let mut t = ETT::<Treaps<AggSum>>::new((0..5).collect());
+    let baseline = t.inner_lists().allocated_nodes();
+    assert_eq!(baseline, 5);
+    for i in 0..10 {
+        let edge = t.connect(i % 5, (i + 1) % 5, 0, 0).unwrap();
+        t.disconnect(edge);
+    }
+    // Edge nodes are not reused, so each connection allocates two more
+    let l = t.inner_lists();
+    assert_eq!(l.allocated_nodes(), baseline + 20);
+    assert_eq!(l.live_nodes(), l.allocated_nodes());
+    let mut t = ETT::<Splays<AggSum>>::new((0..5).collect());
+    let edge = t.connect(0, 1, 0, 0).unwrap();
+    t.disconnect(edge);
+    assert_eq!(t.inner_lists().allocated_nodes(), 7);
+    assert_eq!(t.inner_lists().live_nodes(), 7);
+}
@@ -0,0 +1,59 @@
+use std::collections::BTreeSet;
+
+use dynamic_2core::{
+    generators::{barabasi_albert, grid, random_graph},
+    Dynamic2CoreSolver, FastDynamic2CoreSolver,
+};
+
+fn assert_simple(n: usize, edges: &[(usize, usize)]) {
+    let distinct: BTreeSet<_> = edges.iter().map(|&(u, v)| (u.min(v), u.max(v))).collect();
+    assert_eq!(distinct.len(), edges.len(), "repeated edges");
+    assert!(edges.iter().all(|&(u, v)| u != v && u < n && v < n));
+}
+
+#[test]
+fn test_random_graph() {
+    for (n, m) in [(10, 0), (10, 20), (10, 45), (100, 300)] {
+        let edges = random_graph(n, m, 632);
+        assert_eq!(edges.len(), m);
+        assert_simple(n, &edges);
+    }
+    assert_eq!(random_graph(50, 100, 1), random_graph(50, 100, 1));
+    assert_ne!(random_graph(50, 100, 1), random_graph(50, 100, 2));
+}
+
+#[test]
+#[should_panic(expected = "can't have 46 edges on 10 vertices")]
+fn test_random_graph_too_many_edges() {
+    random_graph(10, 46, 0);
+}
+
+#[test]
+fn test_grid() {
+    let (rows, cols) = (4, 7);
+    let edges = grid(rows, cols);
+    assert_eq!(edges.len(), rows * (cols - 1) + (rows - 1) * cols);
+    assert_simple(rows * cols, &edges);
+    let mut t = FastDynamic2CoreSolver::from_edges(rows * cols, edges);
+    for u in 0..rows * cols {
+        assert!(t.is_connected(0, u));
+        assert!(t.is_in_2core(u));
+    }
+    // A single row is a path
+    let mut t = FastDynamic2CoreSolver::from_edges(5, grid(1, 5));
+    assert!(t.is_connected(0, 4));
+    assert!(!t.is_in_2core(2));
+}
+
+#[test]
+fn test_barabasi_albert() {
+    for (n, m) in [(2, 1), (50, 1), (50, 3), (200, 5)] {
+        let edges = barabasi_albert(n, m, 632);
+        assert_eq!(edges.len(), m * (m + 1) / 2 + (n - m - 1) * m);
+        assert_simple(n, &edges);
+        let mut t = FastDynamic2CoreSolver::from_edges(n, edges);
+        assert!((0..n).all(|u| t.is_connected(0, u)));
+        // With a single edge per vertex it is a tree
+        assert_eq!((0..n).any(|u| t.is_in_2core(u)), m > 1);
+    }
+}
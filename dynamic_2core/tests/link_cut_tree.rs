@@ -0,0 +1,209 @@
+use common::{slow_lct::SlowLCT, slow_lists::SlowLists};
+use dynamic_2core::{
+    link_cut_tree::{LCTSized, LinkCutTree, SubtreeSize, LCT},
+    lists::{splay::Splays, treap::Treaps, Lists},
+};
+use rand::{Rng, SeedableRng};
+
+mod common;
+
+//     4       6
+//     |       |
+//     0       8
+//    / \      |
+//   1   2     7
+//   |   |
+//   3   5
+const FOREST: [Option<usize>; 9] = [
+    Some(4),
+    Some(0),
+    Some(0),
+    Some(1),
+    None,
+    Some(2),
+    None,
+    Some(8),
+    Some(6),
+];
+
+fn from_forest<T: LinkCutTree>() {
+    let mut t = T::from_forest(&FOREST);
+    let roots = [4, 4, 4, 4, 4, 4, 6, 6, 6];
+    for (u, r) in roots.into_iter().enumerate() {
+        assert_eq!(t.root(u), r, "root of {u}");
+    }
+    let depths = [1, 2, 2, 3, 0, 3, 0, 2, 1];
+    for (u, d) in depths.into_iter().enumerate() {
+        assert_eq!(t.depth(u), d, "depth of {u}");
+    }
+    assert_eq!(t.lca(3, 5), Some(0));
+    assert_eq!(t.lca(3, 1), Some(1));
+    assert_eq!(t.lca(1, 2), Some(0));
+    assert_eq!(t.lca(3, 4), Some(4));
+    assert_eq!(t.lca(7, 8), Some(8));
+    assert_eq!(t.lca(7, 6), Some(6));
+    assert_eq!(t.lca(3, 7), None);
+    for (u, r) in roots.into_iter().enumerate() {
+        assert_eq!(t.root(u), r, "root of {u}");
+    }
+    // Parents are kept
+    assert_eq!(t.cut(5), Some(2));
+    assert_eq!(t.cut(0), Some(4));
+    assert_eq!(t.cut(4), None);
+    assert_eq!(t.root(3), 0);
+    assert_eq!(t.depth(3), 2);
+    t.reroot(3);
+    assert_eq!(t.depth(0), 2);
+    assert_eq!(t.depth(2), 3);
+    assert_eq!(t.root(7), 6);
+}
+
+#[test]
+fn test_from_forest() {
+    from_forest::<SlowLCT>();
+    from_forest::<LCT<Treaps>>();
+    from_forest::<LCT<Splays>>();
+    from_forest::<LCTSized<Treaps<SubtreeSize>>>();
+}
+
+#[test]
+#[should_panic(expected = "cycle")]
+fn test_from_forest_cycle() {
+    LCT::<Treaps>::from_forest(&[None, Some(2), Some(3), Some(1)]);
+}
+
+#[test]
+#[should_panic(expected = "out of range")]
+fn test_from_forest_out_of_range() {
+    LCT::<Treaps>::from_forest(&[None, Some(3), Some(0)]);
+}
+
+#[test]
+#[should_panic(expected = "cycle")]
+fn test_from_forest_cycle_default() {
+    SlowLCT::from_forest(&[Some(0)]);
+}
+
+#[test]
+#[cfg(all(feature = "debug-hooks", debug_assertions))]
+#[should_panic(expected = "LCT invariant violated: parent cycle detected")]
+fn test_access_detects_parent_cycle() {
+    let mut t = LCT::<Treaps>::new(3);
+    t.set_path_parent_unchecked(0, 1);
+    t.set_path_parent_unchecked(1, 2);
+    t.set_path_parent_unchecked(2, 0);
+    t.root(0);
+}
+
+fn cut_edge<T: LinkCutTree>() {
+    let mut t = T::from_forest(&FOREST);
+    // Not adjacent: same tree, different trees, same node
+    assert!(!t.cut_edge(3, 0));
+    assert!(!t.cut_edge(1, 2));
+    assert!(!t.cut_edge(3, 7));
+    assert!(!t.cut_edge(4, 4));
+    for u in 0..6 {
+        assert_eq!(t.root(u), 4);
+    }
+    // Either orientation works
+    assert!(t.cut_edge(0, 1));
+    assert!(!t.cut_edge(1, 0));
+    assert_eq!(t.lca(1, 3), Some(1));
+    assert_eq!(t.lca(1, 0), None);
+    assert!(t.cut_edge(8, 6));
+    assert_eq!(t.lca(8, 6), None);
+    assert_eq!(t.root(7), 8);
+    assert!(t.cut_edge(5, 2));
+    assert!(t.cut_edge(0, 4));
+    assert!(t.cut_edge(0, 2));
+    for u in [0, 2, 4, 5, 6] {
+        for v in 0..9 {
+            assert!(!t.cut_edge(u, v), "{u} {v}");
+        }
+    }
+    assert!(t.cut_edge(7, 8));
+}
+
+#[test]
+fn test_cut_edge() {
+    cut_edge::<SlowLCT>();
+    cut_edge::<LCT<Treaps>>();
+    cut_edge::<LCT<Splays>>();
+    cut_edge::<LCTSized<Treaps<SubtreeSize>>>();
+}
+
+fn path<T: LinkCutTree>() {
+    let mut t = T::from_forest(&FOREST);
+    assert_eq!(t.path(3, 5), Some(vec![3, 1, 0, 2, 5]));
+    assert_eq!(t.path(5, 3), Some(vec![5, 2, 0, 1, 3]));
+    assert_eq!(t.path(4, 3), Some(vec![4, 0, 1, 3]));
+    assert_eq!(t.path(7, 6), Some(vec![7, 8, 6]));
+    assert_eq!(t.path(2, 2), Some(vec![2]));
+    assert_eq!(t.path(3, 7), None);
+    // The tree is unchanged, but rerooted at u
+    assert_eq!(t.root(4), 3);
+    for (u, p) in FOREST.into_iter().enumerate() {
+        if let Some(p) = p {
+            t.reroot(p);
+            assert_eq!(t.depth(u), 1, "{u} {p}");
+        }
+    }
+}
+
+#[test]
+fn test_path() {
+    path::<SlowLCT>();
+    path::<LCT<Treaps>>();
+    path::<LCT<Splays>>();
+    path::<LCTSized<Treaps<SubtreeSize>>>();
+}
+
+fn brute_subtree_size(t: &mut SlowLCT, n: usize, u: usize) -> usize {
+    (0..n).filter(|&v| t.lca(u, v) == Some(u)).count()
+}
+
+fn subtree_size<L: Lists<SubtreeSize>>(seed: u64) {
+    const N: usize = 30;
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let parents: Vec<_> = (0..N)
+        .map(|u| (u > 0 && rng.gen_bool(0.8)).then(|| rng.gen_range(0..u)))
+        .collect();
+    let mut t = LCTSized::<L>::from_forest(&parents);
+    let mut slow = SlowLCT::from_forest(&parents);
+    for _ in 0..300 {
+        let u = rng.gen_range(0..N);
+        let v = rng.gen_range(0..N);
+        match rng.gen_range(0..3) {
+            0 => assert_eq!(t.link(u, v), slow.link(u, v)),
+            1 => assert_eq!(t.cut(u), slow.cut(u)),
+            _ => {
+                t.reroot(u);
+                slow.reroot(u);
+            }
+        }
+        for w in 0..N {
+            assert_eq!(
+                t.subtree_size(w),
+                brute_subtree_size(&mut slow, N, w),
+                "subtree of {w}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_subtree_size() {
+    let mut t = LCTSized::<Treaps<SubtreeSize>>::from_forest(&FOREST);
+    let sizes = [5, 2, 2, 1, 6, 1, 3, 1, 2];
+    for (u, s) in sizes.into_iter().enumerate() {
+        assert_eq!(t.subtree_size(u), s, "subtree of {u}");
+    }
+    t.reroot(3);
+    assert_eq!(t.subtree_size(3), 6);
+    assert_eq!(t.subtree_size(0), 4);
+    for seed in 0..5 {
+        subtree_size::<SlowLists<SubtreeSize>>(704 + seed);
+        subtree_size::<Treaps<SubtreeSize>>(704 + seed);
+        subtree_size::<Splays<SubtreeSize>>(704 + seed);
+    }
+}
@@ -4,6 +4,7 @@ use common::{init_logger, log_traces, slow_lists::SlowLists, AggDigit, AggSum};
 use dynamic_2core::lists::*;
 use rand::prelude::*;
 use scopeguard::{OnUnwind, ScopeGuard};
+use small::SmallLists;
 use splay::Splays;
 use treap::Treaps;
 
@@ -122,6 +123,163 @@ impl<L: Lists<AggSum>> LTests<L> {
         Self::assert_data(l, r, &[-12, 1, 2, 3, 8, 12, 10, 99, 98, 97, 15, 20]);
     }
 
+    fn test_build_balanced() {
+        let l = &mut L::new(0);
+        assert_eq!(l.build_balanced([]), L::EMPTY);
+        l.create(-1);
+        let data: Vec<i32> = (1..=40).collect();
+        let root = l.build_balanced(data.iter().copied());
+        Self::assert_data(l, root, &data);
+        for k in 0..40 {
+            assert_eq!(l.find_kth(root, k), k + 1);
+        }
+        assert_eq!(l.total_size(), 41);
+        assert_eq!(l.num_lists(), 2);
+        assert_eq!(l.total_agg(root), 820);
+        assert!(!l.on_same_list(0, root));
+        // It is an ordinary list
+        let (_, mid, _) = l.split(root, 10..30);
+        assert_eq!(l.total_agg(mid), (11..=30).sum::<i32>());
+    }
+
+    fn test_create_many() {
+        let (mut one_by_one, mut many) = (L::new(0), L::new(0));
+        assert_eq!(many.create_many([].into_iter()), 0..0);
+        for batch in [1..4, 4..4, 4..20] {
+            let created: Vec<_> = batch.clone().map(|i| one_by_one.create(i)).collect();
+            let range = many.create_many(batch);
+            assert_eq!(range.clone().collect::<Vec<_>>(), created);
+            for u in range {
+                assert_eq!(many.data(u), one_by_one.data(u));
+                assert!(many.is_first(u) && many.is_last(u));
+            }
+        }
+        assert_eq!(many.total_size(), 19);
+        assert_eq!(many.num_lists(), 19);
+        let root = many.concat_all(1..19);
+        assert_eq!(many.total_agg(root), (2..20).sum::<i32>());
+    }
+
+    fn test_dedup_adjacent() {
+        let l = &mut L::from_iter([1, 1, 2, 2, 2, 3, 1]);
+        let root = l.dedup_adjacent(3, |a, b| a == b);
+        Self::assert_data(l, root, &[1, 2, 3, 1]);
+        assert_eq!(l.total_agg(root), 7);
+        assert_eq!(l.len(root), 4);
+        // Each run keeps its first element, and the rest of it is left as a list
+        assert!(l.on_same_list(root, 0) && l.on_same_list(root, 2));
+        assert_eq!(l.len(1), 1);
+        Self::assert_data(l, 3, &[2, 2]);
+        // Nothing to remove
+        let root = l.dedup_adjacent(root, |a, b| a == b);
+        Self::assert_data(l, root, &[1, 2, 3, 1]);
+        // Any equality, here by parity, which removes the run at the end
+        let root = l.dedup_adjacent(6, |a, b| a % 2 == b % 2);
+        Self::assert_data(l, root, &[1, 2, 3]);
+        assert_eq!(l.total_agg(root), 6);
+        let single = l.create(9);
+        assert_eq!(l.dedup_adjacent(single, |_, _| true), single);
+        assert_eq!(l.dedup_adjacent(L::EMPTY, |_, _| true), L::EMPTY);
+    }
+
+    fn test_join_with_separator() {
+        let l = &mut L::new(3);
+        let parts = l.create_many([1, 2, 3].into_iter());
+        let root = l.join_with_separator(parts, || 0);
+        Self::assert_data(l, root, &[1, 0, 2, 0, 3]);
+        assert_eq!(l.total_agg(root), 6);
+        assert_eq!(l.total_size(), 5);
+        // Parts may have several elements, empty ones get no separator, and each separator is a new node
+        let (_, left, right) = l.split(root, ..3);
+        let four = l.create(4);
+        let mut next = 10;
+        let root = l.join_with_separator([right, L::EMPTY, four, left], || {
+            next += 1;
+            next
+        });
+        Self::assert_data(l, root, &[0, 3, 11, 4, 12, 1, 0, 2]);
+        assert_eq!(l.total_agg(root), 33);
+        assert_eq!(l.join_with_separator([L::EMPTY], || 0), L::EMPTY);
+        let single = l.join_with_separator([four], || 0);
+        assert_eq!(l.root(single), l.root(four));
+        assert_eq!(l.total_size(), 8);
+    }
+
+    fn test_concat_keeps_first() {
+        const N: usize = 40;
+        let mut rng = StdRng::seed_from_u64(668);
+        let mut l = L::new(N);
+        (0..N).for_each(|i| _ = l.create(i as i32));
+        for _ in 0..200 {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            if rng.gen_bool(0.3) {
+                l.reverse(u);
+            }
+            if l.on_same_list(u, v) {
+                let len = l.len(u);
+                l.split(u, rng.gen_range(0..len)..);
+                continue;
+            }
+            let first = l.first(u);
+            let r = l.concat(u, v);
+            assert_eq!(l.first(r), first);
+            assert_eq!(l.first(v), first);
+        }
+        let first = l.first(3);
+        let r = l.concat(L::EMPTY, 3);
+        assert_eq!(l.first(r), first);
+    }
+
+    fn test_split_where() {
+        let data = [3, 1, 4, 1, 5, 9, 2, 6];
+        // Prefix sums are 3 4 8 9 14 23 25 31
+        for (threshold, left_len) in [
+            (-1, 0),
+            (0, 0),
+            (3, 1),
+            (8, 3),
+            (9, 4),
+            (14, 5),
+            (30, 7),
+            (31, 8),
+        ] {
+            let l = &mut Self::build(&data);
+            let (left, right) = l.split_where(0, |s| s.0 <= threshold);
+            for (part, data) in [(left, &data[..left_len]), (right, &data[left_len..])] {
+                if data.is_empty() {
+                    assert_eq!(part, L::EMPTY);
+                } else {
+                    Self::assert_data(l, part, data);
+                }
+            }
+        }
+        let mut l = L::new(0);
+        assert_eq!(l.split_where(L::EMPTY, |_| true), (L::EMPTY, L::EMPTY));
+    }
+
+    fn test_num_lists() {
+        const N: usize = 40;
+        let mut rng = StdRng::seed_from_u64(671);
+        let mut l = L::new(N);
+        assert_eq!(l.num_lists(), 0);
+        (0..N).for_each(|i| _ = l.create(i as i32));
+        assert_eq!(l.num_lists(), N);
+        for _ in 0..300 {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            match rng.gen_range(0..4) {
+                0 | 1 => _ = l.concat(u, v),
+                2 => {
+                    let len = l.len(u);
+                    let (a, b) = (rng.gen_range(0..=len), rng.gen_range(0..=len));
+                    l.split(u, a.min(b)..a.max(b));
+                }
+                _ => _ = l.split_after(u),
+            }
+            let roots = (0..N).filter(|&u| l.is_root(u)).count();
+            assert_eq!(l.num_lists(), roots);
+        }
+    }
+
     fn test_split() {
         let l = &mut Self::build(&[1, 2, 3, 7, 9, 2]);
         assert_eq!(l.range_agg(0, 0..1), 1);
@@ -141,6 +299,43 @@ impl<L: Lists<AggSum>> LTests<L> {
         Self::assert_data(l, right, &[3, 7]);
     }
 
+    /// Every range of short lists, including empty ranges at both ends, with and without reversing first.
+    fn test_split_edge_cases() {
+        for len in 1..5 {
+            let data: Vec<i32> = (1..=len).collect();
+            let len = len as usize;
+            for reversed in [false, true] {
+                let data: Vec<i32> = match reversed {
+                    false => data.clone(),
+                    true => data.iter().rev().copied().collect(),
+                };
+                for ql in 0..=len {
+                    for qr in ql..=len {
+                        let l = &mut Self::build(&(1..=len as i32).collect::<Vec<_>>());
+                        let u = len - 1;
+                        if reversed {
+                            l.reverse(u);
+                        }
+                        let (left, mid, right) = l.split(u, ql..qr);
+                        l.check_all();
+                        Self::assert_data(l, left, &data[..ql]);
+                        Self::assert_data(l, mid, &data[ql..qr]);
+                        Self::assert_data(l, right, &data[qr..]);
+                        // Parts are independent lists
+                        for (a, b) in [(left, mid), (mid, right), (left, right)] {
+                            if a != L::EMPTY && b != L::EMPTY {
+                                assert!(!l.on_same_list(a, b));
+                            }
+                        }
+                        let u = l.concat_all([left, mid, right]);
+                        l.check_all();
+                        Self::assert_data(l, u, &data);
+                    }
+                }
+            }
+        }
+    }
+
     fn test_same_as_not_content() {
         let (l, r1) = (&mut Self::build(&[1, 2, 3]), 0);
         let r2 = Self::add_list(l, &[1, 2, 3]);
@@ -234,15 +429,230 @@ impl<L: Lists<AggSum>> LTests<L> {
         idx_of_kth_value(100000, L::EMPTY);
     }
 
-    fn test_all() {
+    fn test_find_kth_out_of_range() {
+        let l = &mut Self::build(&[4, 5, 6]);
+        assert_eq!(l.find_kth(0, 2), 2);
+        assert_eq!(l.find_kth(1, 3), L::EMPTY);
+        assert_eq!(l.find_kth(2, 100), L::EMPTY);
+        assert_eq!(l.find_kth(L::EMPTY, 0), L::EMPTY);
+        assert_eq!(l.next(2), L::EMPTY);
+        let single = Self::add_list(l, &[7]);
+        assert_eq!(l.find_kth(single, 1), L::EMPTY);
+        assert!(l.is_last(single));
+    }
+
+    fn test_find_kth_in_range() {
+        let l = &mut Self::build(&[10, 11, 12, 13, 14, 15]);
+        assert_eq!(l.find_kth_in_range(3, 2..5, 0), 2);
+        assert_eq!(l.find_kth_in_range(3, 2..5, 2), 4);
+        assert_eq!(l.find_kth_in_range(3, 2..5, 3), L::EMPTY);
+        assert_eq!(l.find_kth_in_range(0, 2..=5, 3), 5);
+        assert_eq!(l.find_kth_in_range(0, .., 5), 5);
+        assert_eq!(l.find_kth_in_range(0, .., 6), L::EMPTY);
+        assert_eq!(l.find_kth_in_range(0, 4.., 1), 5);
+        assert_eq!(l.find_kth_in_range(0, 4.., 2), L::EMPTY);
+        assert_eq!(l.find_kth_in_range(0, 3..3, 0), L::EMPTY);
+        // Range past the end of the list
+        assert_eq!(l.find_kth_in_range(0, 5..10, 0), 5);
+        assert_eq!(l.find_kth_in_range(0, 5..10, 1), L::EMPTY);
+        assert_eq!(l.find_kth_in_range(0, 1..3, usize::MAX), L::EMPTY);
+        assert_eq!(l.find_kth_in_range(L::EMPTY, .., 0), L::EMPTY);
+        l.reverse(0);
+        assert_eq!(l.find_kth_in_range(0, 1..3, 1), 3);
+    }
+
+    fn test_map_in_place() {
+        let l = &mut Self::build(&[1, 2, 3, 4, 5, 6, 7]);
+        let other = Self::add_list(l, &[100]);
+        let (left, _, right) = l.split(0, 2..5);
+        l.reverse(right);
+        let u = l.concat(right, left);
+        l.reverse(u);
+        l.concat(0, 2);
+        let before = l.total_agg(0).0;
+        let mut seen = vec![];
+        l.map_in_place(4, |d| {
+            seen.push(*d);
+            *d *= 2
+        });
+        assert_eq!(seen, [2, 1, 6, 7, 3, 4, 5]);
+        assert_eq!(l.total_agg(0).0, 2 * before);
+        assert_eq!(l.range_agg(6, 1..4), 28);
+        Self::assert_data(l, 0, &[4, 2, 12, 14, 6, 8, 10]);
+        Self::assert_data(l, other, &[100]);
+        l.map_in_place(L::EMPTY, |_| panic!("empty list has no elements"));
+    }
+
+    fn test_prefix_suffix_agg() {
+        let l = &mut Self::build(&[3, 1, 4, 1, 5, 9, 2, 6]);
+        l.reverse_range(0, 2..6);
+        let total = l.total_agg(0).0;
+        let len = l.len(0);
+        for k in 0..=len {
+            let u = l.find_kth(0, k % len);
+            let (prefix, suffix) = (l.prefix_agg(u, k), l.suffix_agg(u, len - k));
+            assert_eq!(prefix.merge(suffix), total, "k = {k}");
+        }
+        assert_eq!(l.prefix_agg(0, 3), 3 + 1 + 9);
+        assert_eq!(l.suffix_agg(0, 3), 4 + 2 + 6);
+        assert_eq!(l.prefix_agg(0, 0), 0);
+        assert_eq!(l.suffix_agg(0, 0), 0);
+        assert_eq!(l.prefix_agg(0, len + 5), total);
+        assert_eq!(l.suffix_agg(0, len + 5), total);
+        let single = Self::add_list(l, &[7]);
+        assert_eq!(l.suffix_agg(single, 1), 7);
+        assert_eq!(l.prefix_agg(single, 1), 7);
+    }
+
+    fn test_replace_after() {
+        let l = &mut Self::build(&[1, 2, 3, 4]);
+        let other = Self::add_list(l, &[10, 20]);
+        l.reverse(other);
+        let first = l.first(other);
+        assert_eq!(l.replace_after(1, first), 2);
+        Self::assert_data(l, 0, &[1, 2, 20, 10]);
+        Self::assert_data(l, 2, &[3, 4]);
+        // Nothing after the last node, and nothing put in its place
+        assert_eq!(l.replace_after(other, L::EMPTY), L::EMPTY);
+        Self::assert_data(l, 0, &[1, 2, 20, 10]);
+        assert_eq!(l.replace_after(0, L::EMPTY), 1);
+        Self::assert_data(l, 0, &[1]);
+        Self::assert_data(l, 1, &[2, 20, 10]);
+    }
+
+    fn test_for_each() {
+        let l = &mut Self::build(&[1, 2, 3, 4, 5, 6]);
+        let other = Self::add_list(l, &[7]);
+        l.reverse_range(0, 1..5);
+        l.reverse(3);
+        let mut seen = vec![];
+        l.for_each(2, |u, d| seen.push((u, *d)));
+        assert_eq!(seen, [(5, 6), (1, 2), (2, 3), (3, 4), (4, 5), (0, 1)]);
+        Self::assert_data(l, 0, &[6, 2, 3, 4, 5, 1]);
+        let mut seen = vec![];
+        l.for_each(other, |u, _| seen.push(u));
+        assert_eq!(seen, [other]);
+        l.for_each(L::EMPTY, |_, _| panic!("empty list has no elements"));
+    }
+
+    fn test_splice() {
+        let l = &mut Self::build(&[1, 2, 3]);
+        let front = Self::add_list(l, &[10, 20]);
+        let r = l.splice(0, 0, front);
+        assert!(l.on_same_list(r, 2));
+        Self::assert_data(l, 0, &[10, 20, 1, 2, 3]);
+        let middle = Self::add_list(l, &[30]);
+        l.splice(4, 3, middle);
+        Self::assert_data(l, middle, &[10, 20, 1, 30, 2, 3]);
+        let end = Self::add_list(l, &[40, 50]);
+        let r = l.splice(end + 1, 0, 0);
+        assert!(l.on_same_list(r, end));
+        Self::assert_data(l, 3, &[10, 20, 1, 30, 2, 3, 40, 50]);
+        assert_eq!(l.total_agg(0), 156);
+    }
+
+    fn test_split_after() {
+        let l = &mut Self::build(&[1, 2, 3, 4, 5]);
+        l.reverse(0);
+        // [5, 4, 3, 2, 1]
+        let (left, right) = l.split_after(4);
+        Self::assert_data(l, left, &[5]);
+        Self::assert_data(l, right, &[4, 3, 2, 1]);
+        let (left, right) = l.split_after(2);
+        Self::assert_data(l, left, &[4, 3]);
+        Self::assert_data(l, right, &[2, 1]);
+        let (left, right) = l.split_after(0);
+        assert_eq!(right, L::EMPTY);
+        Self::assert_data(l, left, &[2, 1]);
+        assert_eq!(l.len(1), 2);
+        assert_eq!(l.total_agg(3), 7);
+    }
+
+    fn test_reverse_list_and_range() {
+        let l = &mut Self::build(&[1, 2, 3, 4, 5, 6]);
+        // Through a node in the middle, the whole list is reversed
+        l.reverse_list(3);
+        Self::assert_data(l, 0, &[6, 5, 4, 3, 2, 1]);
+        l.reverse(5);
+        Self::assert_data(l, 0, &[1, 2, 3, 4, 5, 6]);
+        let r = l.reverse_range(4, 1..4);
+        assert!(l.on_same_list(r, 0));
+        Self::assert_data(l, 0, &[1, 4, 3, 2, 5, 6]);
+        l.reverse_range(0, 4..);
+        Self::assert_data(l, 2, &[1, 4, 3, 2, 6, 5]);
+        l.reverse_range(0, 2..2);
+        l.reverse_range(0, ..=0);
+        Self::assert_data(l, 2, &[1, 4, 3, 2, 6, 5]);
+        assert_eq!(l.range_agg(0, ..3), 8);
+    }
+
+    fn test_view() {
+        let l = &mut Self::build(&[5, 3, 8, 1]);
+        let u = Self::add_list(l, &[2, 4]);
+        let view: Vec<_> = l.view(1).into_iter().collect();
+        assert_eq!(view, [(0, &5), (1, &3), (2, &8), (3, &1)]);
+        l.reverse(u);
+        let view = l.view(u);
+        assert_eq!(view.nodes(), [5, 4]);
+        let data: Vec<i32> = view.into_iter().map(|(_, d)| *d).collect();
+        Self::assert_data(l, u, &data);
+        assert_eq!(l.view(L::EMPTY).into_iter().len(), 0);
+    }
+
+    /// Many nested reversals, checking the order of every element against find_kth and a plain Vec.
+    fn test_order_after_reversals() {
+        const N: usize = 40;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(656);
+        let mut expected: Vec<i32> = (0..N as i32).collect();
+        let l = &mut Self::build(&expected);
+        let mut root = 0;
+        for _ in 0..200 {
+            let a = rng.gen_range(0..N);
+            let b = rng.gen_range(a..=N);
+            root = l.reverse_range(root, a..b);
+            expected[a..b].reverse();
+            for (k, &x) in expected.iter().enumerate() {
+                let u = l.find_kth(root, k);
+                assert_eq!(u, x as usize);
+                assert_eq!(l.order(u), k, "{l:?}");
+            }
+        }
+        Self::assert_data(l, root, &expected);
+    }
+
+    /// Tests that use at most 16 nodes, so they also fit in SmallLists with the default capacity.
+    fn test_small() {
         Self::test_new_empty();
         Self::test_new();
         Self::test_concat();
+        Self::test_dedup_adjacent();
+        Self::test_join_with_separator();
+        Self::test_split_where();
         Self::test_split();
+        Self::test_split_edge_cases();
         Self::test_same_as_not_content();
         Self::test_dsu();
         Self::test_change_data();
         Self::test_find_element();
+        Self::test_find_kth_out_of_range();
+        Self::test_find_kth_in_range();
+        Self::test_map_in_place();
+        Self::test_prefix_suffix_agg();
+        Self::test_replace_after();
+        Self::test_for_each();
+        Self::test_splice();
+        Self::test_split_after();
+        Self::test_reverse_list_and_range();
+        Self::test_view();
+    }
+
+    fn test_all() {
+        Self::test_small();
+        Self::test_concat_keeps_first();
+        Self::test_build_balanced();
+        Self::test_create_many();
+        Self::test_num_lists();
+        Self::test_order_after_reversals();
     }
 }
 
@@ -422,6 +832,448 @@ fn test_splay_all() {
     test_digits::<Splays<AggDigit>>();
 }
 
+#[test]
+fn test_small_lists() {
+    init_logger();
+    // Some tests use lists of 40 nodes
+    LTests::<SmallLists<AggSum, 64>>::test_all();
+    LTests::<SmallLists<AggSum, 16>>::test_small();
+    test_digits::<SmallLists<AggDigit, 16>>();
+}
+
+#[test]
+#[should_panic(expected = "SmallLists can hold at most 16 nodes")]
+fn test_small_lists_full() {
+    let mut l = SmallLists::<AggSum, 16>::from_iter(0..16);
+    l.create(16);
+}
+
+#[test]
+fn test_treap_range_agg_explain() {
+    init_logger();
+    let l = &mut Treaps::<AggDigit>::from_iter(1..=9);
+    // Some flips in the middle of the tree
+    l.reverse_range(0, 2..7);
+    l.reverse_range(0, 4..9);
+    let root = l.root(0);
+    for ql in 0..=9 {
+        for qr in ql..=9 {
+            let (agg, parts) = l.range_agg_explain(root, ql..qr);
+            assert_eq!(agg, Treaps::range_agg(l, root, ql..qr));
+            // The parts cover the range in order, and merging them gives the result
+            let mut pos = ql;
+            let mut merged = AggDigit::default();
+            for (u, range) in parts {
+                assert_eq!(range.start, pos);
+                assert!(range.contains(&Treaps::order(l, u)));
+                if range.len() == 1 {
+                    assert_eq!(l.find_kth(root, range.start), u);
+                }
+                pos = range.end;
+                merged = merged.merge(Treaps::range_agg(l, root, range));
+            }
+            assert_eq!(pos, qr);
+            assert_eq!(merged, agg);
+        }
+    }
+}
+
+#[test]
+fn test_treap_shared_aggregates() {
+    let mut t = Treaps::<AggSum>::from_iter([1, 2, 3, 4, 5]);
+    t.reverse(2);
+    let shared: &Treaps<AggSum> = &t;
+    assert_eq!(shared.total_agg(3), 15);
+    assert_eq!(shared.range_agg(0, ..2), 9);
+    assert_eq!(shared.range_agg(4, 1..=3), 9);
+    assert_eq!(shared.range_agg(1, 3..), 3);
+    assert_eq!(shared.root(0), shared.root(4));
+    assert_eq!(shared.total_agg(Treaps::<AggSum>::EMPTY), 0);
+}
+
+#[test]
+#[should_panic(expected = "Can't splice a list into itself")]
+fn test_splice_same_list() {
+    let mut l = Treaps::<AggSum>::from_iter([1, 2, 3]);
+    l.splice(0, 1, 2);
+}
+
+#[test]
+fn test_treap_cursor() {
+    let mut t = Treaps::<AggSum>::from_iter([1, 2, 3, 4, 5, 6]);
+    t.reverse(0);
+    let other = t.create(100);
+    let shared: &Treaps<AggSum> = &t;
+    let mut seen = vec![];
+    for (i, u) in shared.cursor(shared.find_kth(0, 0)).enumerate() {
+        seen.push(*shared.data(u));
+        // Prefix sums, taken while the cursor is alive
+        assert_eq!(shared.range_agg(u, ..=i), seen.iter().sum::<i32>());
+        assert_eq!(shared.order(u), i);
+    }
+    assert_eq!(seen, [6, 5, 4, 3, 2, 1]);
+    assert_eq!(shared.cursor(2).collect::<Vec<_>>(), [2, 1, 0]);
+    assert_eq!(shared.cursor(other).collect::<Vec<_>>(), [other]);
+    assert_eq!(shared.len(4), 6);
+}
+
+#[test]
+fn test_memory_bytes_linear() {
+    fn mem<L: Lists<AggSum>>(n: usize) -> usize {
+        L::from_iter(0..n as i32).memory_bytes()
+    }
+    for (small, big) in [
+        (mem::<Treaps<AggSum>>(1000), mem::<Treaps<AggSum>>(2000)),
+        (mem::<Splays<AggSum>>(1000), mem::<Splays<AggSum>>(2000)),
+    ] {
+        // At least the data and aggregate of each node
+        assert!(small >= 1000 * 2 * std::mem::size_of::<i32>());
+        let ratio = big as f64 / small as f64;
+        assert!((1.9..=2.0).contains(&ratio), "{small} {big}");
+    }
+}
+
+/// Rng that cycles through a fixed sequence of u32s. Seeding always starts the same sequence.
+struct SeqRng(&'static [u32], usize);
+
+impl rand::RngCore for SeqRng {
+    fn next_u32(&mut self) -> u32 {
+        let x = self.0[self.1 % self.0.len()];
+        self.1 += 1;
+        x
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.next_u32() as u64
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.iter_mut().for_each(|b| *b = self.next_u32() as u8);
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl rand::SeedableRng for SeqRng {
+    type Seed = [u8; 1];
+    fn from_seed(_seed: [u8; 1]) -> Self {
+        Self(&[1, 5, 2, 4, 3], 0)
+    }
+}
+
+#[test]
+fn test_treap_with_rng() {
+    // The node with largest priority is the root
+    let build = |priorities: &'static [u32]| {
+        let mut t = Treaps::<AggSum, SeqRng>::with_rng(5, SeqRng(priorities, 0));
+        for i in 0..5 {
+            t.create(i);
+        }
+        t.concat_all(0..5);
+        t
+    };
+    assert_eq!(Treaps::root(&build(&[1, 2, 3, 4, 5]), 0), 4);
+    assert_eq!(Treaps::root(&build(&[5, 4, 3, 2, 1]), 4), 0);
+    let mut t = build(&[1, 5, 2, 4, 3]);
+    assert_eq!(Treaps::root(&t, 0), 1);
+    assert_data(&mut t, 2, &[0, 1, 2, 3, 4]);
+    // [0 1] [2 3 4]: 1 and 3 are the roots
+    let (_, left, right) = t.split(0, ..2);
+    assert_eq!((Treaps::root(&t, 0), left), (1, 1));
+    assert_eq!((Treaps::root(&t, 2), right), (3, 3));
+    // new seeds the rng
+    let t = Treaps::<AggSum, SeqRng>::from_iter(0..5);
+    assert_eq!(Treaps::root(&t, 0), 1);
+}
+
+/// Aggregate of a list of directed steps (from, to), which is valid if consecutive steps match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct AggWalk(Option<(i32, i32, bool)>);
+
+impl Monoid for AggWalk {
+    type Data = (i32, i32);
+    fn from(&(from, to): &(i32, i32)) -> Self {
+        Self(Some((from, to, true)))
+    }
+    fn merge(self, right: Self) -> Self {
+        match (self.0, right.0) {
+            (Some((a, b, ok1)), Some((c, d, ok2))) => Self(Some((a, d, ok1 && ok2 && b == c))),
+            (l, r) => Self(l.or(r)),
+        }
+    }
+}
+
+impl AggregatedData for AggWalk {
+    fn reverse(self) -> Self {
+        Self(self.0.map(|(from, to, ok)| (to, from, ok)))
+    }
+    fn reverse_data(data: &mut (i32, i32)) {
+        std::mem::swap(&mut data.0, &mut data.1);
+    }
+}
+
+fn directional_data<L: Lists<AggWalk>>() {
+    let walk = |from, to, ok| AggWalk(Some((from, to, ok)));
+    let mut l = L::from_iter([(0, 1), (1, 2), (2, 3), (3, 4)]);
+    assert_eq!(l.total_agg(0), walk(0, 4, true));
+    l.reverse(2);
+    assert_eq!(l.total_agg(0), walk(4, 0, true));
+    assert_eq!(l.range_agg(0, 1..3), walk(3, 1, true));
+    l.mutate_data(3, |d| assert_eq!(*d, (4, 3)));
+    // Splitting and concatenating keep each step's direction
+    let (left, _, right) = l.split(0, 2..3);
+    let u = l.concat(right, left);
+    assert_eq!(l.total_agg(u), walk(1, 2, false));
+    let u = l.reverse_range(u, 1..);
+    assert_eq!(l.total_agg(u), walk(1, 4, false));
+    let first = l.find_kth(u, 0);
+    let (left, right) = l.split_after(first);
+    assert_eq!(l.total_agg(left), walk(1, 0, true));
+    assert_eq!(l.total_agg(right), walk(2, 4, true));
+    l.reverse(right);
+    let u = l.concat(right, left);
+    assert_eq!(l.total_agg(u), walk(4, 0, false));
+    let missing = l.create((2, 1));
+    let u = l.splice(u, 2, missing);
+    assert_eq!(l.total_agg(u), walk(4, 0, true));
+    let mut steps = vec![];
+    l.map_in_place(u, |d| steps.push(*d));
+    assert_eq!(steps, [(4, 3), (3, 2), (2, 1), (1, 0)]);
+    let found = l.find_element(u, |d| match d.current_data {
+        (3, 2) => SearchDirection::Found,
+        (from, _) if *from > 3 => SearchDirection::Right,
+        _ => SearchDirection::Left,
+    });
+    assert_eq!(found, 2);
+}
+
+#[test]
+fn test_directional_data() {
+    directional_data::<SlowLists<AggWalk>>();
+    directional_data::<Treaps<AggWalk>>();
+    directional_data::<Splays<AggWalk>>();
+}
+
+/// Resources claimed by a range of nodes, which must all be different.
+#[derive(Debug, Clone, Default)]
+struct AggUnique(BTreeSet<u32>);
+
+impl TryAggregatedData for AggUnique {
+    type Data = u32;
+    type Error = u32;
+    fn from(data: &Self::Data) -> Self {
+        Self(BTreeSet::from([*data]))
+    }
+    fn try_merge(mut self, right: Self) -> Result<Self, Self::Error> {
+        for x in right.0 {
+            if !self.0.insert(x) {
+                return Err(x);
+            }
+        }
+        Ok(self)
+    }
+    fn reverse(self) -> Self {
+        self
+    }
+}
+
+fn fallible_agg<L: Lists<Fallible<AggUnique>>>() {
+    let mut l = L::from_iter([1, 2, 3, 4]);
+    let other = l.create(3);
+    let claimed = |r: Result<AggUnique, u32>| r.map(|ag| ag.0.into_iter().collect::<Vec<_>>());
+    assert_eq!(claimed(l.try_total_agg(0)), Ok(vec![1, 2, 3, 4]));
+    // 3 is claimed twice
+    let u = l.concat(0, other);
+    assert_eq!(claimed(l.try_total_agg(u)), Err(3));
+    l.reverse(u);
+    assert_eq!(claimed(l.try_total_agg(1)), Err(3));
+    assert_eq!(claimed(l.range_agg(u, 2..).0), Ok(vec![1, 2, 3]));
+    // Removing one of them fixes it
+    let (_, _, rest) = l.split(u, 0..1);
+    assert_eq!(claimed(l.try_total_agg(rest)), Ok(vec![1, 2, 3, 4]));
+    l.mutate_data(1, |d| *d = 4);
+    assert_eq!(claimed(l.try_total_agg(rest)), Err(4));
+}
+
+#[test]
+fn test_fallible_agg() {
+    fallible_agg::<SlowLists<Fallible<AggUnique>>>();
+    fallible_agg::<Treaps<Fallible<AggUnique>>>();
+    fallible_agg::<Splays<Fallible<AggUnique>>>();
+}
+
+/// First element of a range. Reversing it would need the last element too, so it is only a [Monoid].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct AggFirst(Option<i32>);
+
+impl Monoid for AggFirst {
+    type Data = i32;
+    fn from(&data: &i32) -> Self {
+        Self(Some(data))
+    }
+    fn merge(self, right: Self) -> Self {
+        Self(self.0.or(right.0))
+    }
+}
+
+fn non_reversible_agg<L: Lists<AggFirst>>() {
+    let mut l = L::from_iter([5, 3, 8, 1]);
+    assert_eq!(l.total_agg(2), AggFirst(Some(5)));
+    let (left, right) = l.split_after(1);
+    assert_eq!(l.total_agg(left), AggFirst(Some(5)));
+    assert_eq!(l.total_agg(right), AggFirst(Some(8)));
+    let root = l.concat(right, left);
+    assert_eq!(l.total_agg(root), AggFirst(Some(8)));
+    assert_eq!(l.range_agg(root, 1..), AggFirst(Some(1)));
+    assert_eq!(l.range_agg(root, 4..), AggFirst(None));
+    // l.reverse(root) doesn't compile, as AggFirst isn't AggregatedData.
+}
+
+#[test]
+fn test_non_reversible_agg() {
+    non_reversible_agg::<SlowLists<AggFirst>>();
+    non_reversible_agg::<Treaps<AggFirst, StdRng, Irreversible>>();
+    non_reversible_agg::<Splays<AggFirst, Irreversible>>();
+}
+
+#[test]
+#[should_panic(expected = "Irreversible lists can't be reversed")]
+fn test_reverse_irreversible() {
+    let mut l = Treaps::<AggSum, StdRng, Irreversible>::from_iter([1, 2, 3]);
+    l.reverse(0);
+}
+
+/// Total length of string slices. Its data is a reference, which must never be zeroed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct AggLen(usize);
+
+impl Monoid for AggLen {
+    type Data = &'static str;
+    fn from(data: &&'static str) -> Self {
+        Self(data.len())
+    }
+    fn merge(self, right: Self) -> Self {
+        Self(self.0 + right.0)
+    }
+}
+
+impl AggregatedData for AggLen {
+    fn reverse(self) -> Self {
+        self
+    }
+    fn reverse_data(_data: &mut &'static str) {}
+}
+
+fn reference_data<L: Lists<AggLen>>() {
+    let mut l = L::from_iter(["a", "bc", "def", "ghij"]);
+    assert_eq!(l.total_agg(0), AggLen(10));
+    assert_eq!(l.range_agg(0, 1..3), AggLen(5));
+    assert_eq!(l.range_agg(0, 4..), AggLen(0));
+    assert_eq!(l.find_kth(0, 4), L::EMPTY);
+    let (left, _, right) = l.split(0, 1..3);
+    assert_eq!(l.total_agg(left), AggLen(1));
+    assert_eq!(l.total_agg(right), AggLen(4));
+    let u = l.concat(right, left);
+    assert_eq!(l.order(0), 1);
+    l.reverse(u);
+    let mut words = vec![];
+    l.for_each(u, |_, d| words.push(*d));
+    assert_eq!(words, ["a", "ghij"]);
+    assert_eq!(l.suffix_agg(u, 1), AggLen(4));
+    assert_eq!(*l.data(3), "ghij");
+}
+
+#[test]
+fn test_reference_data() {
+    reference_data::<SlowLists<AggLen>>();
+    reference_data::<Treaps<AggLen>>();
+    reference_data::<Splays<AggLen>>();
+}
+
+fn debug_is_canonical<L: Lists<AggSum>>() {
+    // Lists [0, 5, 3] and [4, 1, 2], with their concats done in different orders.
+    let build = |first_list_first: bool| {
+        let mut l = L::new(6);
+        (0..6).for_each(|i| _ = l.create(i));
+        let mut ops = vec![vec![(0, 5), (0, 3)], vec![(4, 1), (4, 2)]];
+        if !first_list_first {
+            ops.reverse();
+        }
+        for (u, v) in ops.into_iter().flatten() {
+            l.concat(u, v);
+        }
+        l
+    };
+    let (mut a, mut b) = (build(true), build(false));
+    assert_data(&mut a, 3, &[0, 5, 3]);
+    assert_data(&mut b, 2, &[4, 1, 2]);
+    let (a, b) = (build(true), build(false));
+    assert_eq!(format!("{a:?}"), format!("{b:?}"));
+}
+
+#[test]
+fn test_debug_is_canonical() {
+    debug_is_canonical::<Treaps<AggSum>>();
+    debug_is_canonical::<Splays<AggSum>>();
+    // Treaps have a unique shape for each list, so even the concat order
+    // within a list doesn't matter.
+    let [mut a, mut b] = [(); 2].map(|_| {
+        let mut l = Treaps::<AggSum>::new(4);
+        (0..4).for_each(|i| _ = l.create(i));
+        l
+    });
+    a.concat(3, 1);
+    a.concat(3, 2);
+    a.concat(0, 3);
+    b.concat(0, 3);
+    b.concat(1, 2);
+    b.concat(0, 1);
+    assert_eq!(format!("{a:?}"), format!("{b:?}"));
+}
+
+#[cfg(feature = "metrics")]
+fn amortized_counters<L: Lists<AggSum>>(n: usize, q: usize) -> dynamic_2core::metrics::Counters {
+    let mut l = L::from_iter((0..n as i32).map(|x| x % 7));
+    let start = l.counters();
+    let mut rng = StdRng::seed_from_u64(616);
+    for _ in 0..q {
+        let u = rng.gen_range(0..n);
+        match rng.gen_range(0..3) {
+            0 => {
+                l.find_kth(u, rng.gen_range(0..n));
+            }
+            1 => {
+                let (a, b, c) = l.split(u, rng.gen_range(0..n / 2)..rng.gen_range(n / 2..n));
+                l.concat_all([b, c, a]);
+            }
+            _ => {
+                l.order(u);
+            }
+        }
+    }
+    let end = l.counters();
+    dynamic_2core::metrics::Counters {
+        rotations: end.rotations - start.rotations,
+        splits: end.splits - start.splits,
+        concats: end.concats - start.concats,
+        accesses: end.accesses - start.accesses,
+    }
+}
+
+#[test]
+#[cfg(feature = "metrics")]
+fn test_counters_are_amortized_log() {
+    let (n, q) = (2000, 5000);
+    // Generous constant over q * lg n
+    let bound = 10 * q as u64 * (n as f64).log2().ceil() as u64;
+    let c = amortized_counters::<Splays<AggSum>>(n, q);
+    assert!(c.rotations > 0 && c.rotations <= bound, "{c:?}");
+    let c = amortized_counters::<Treaps<AggSum>>(n, q);
+    assert!(c.splits > 0 && c.splits <= bound, "{c:?}");
+    assert!(c.concats > 0 && c.concats <= bound, "{c:?}");
+    assert_eq!(c.rotations, 0);
+}
+
 #[test]
 fn test_treap_cmp1() {
     random_compare_with_slow::<Treaps<AggSum>, _>(5000, 100, -100000..100000, 10000);
@@ -469,3 +1321,110 @@ fn test_splay_stress() {
         random_compare_with_slow::<Splays<AggSum>, _>(30000, 200, -100000..100000, seed);
     }
 }
+
+#[test]
+#[cfg(all(feature = "debug-hooks", debug_assertions))]
+#[should_panic(expected = "Treap invariant violated: size")]
+fn test_treap_detects_corrupted_size() {
+    let mut l = Treaps::<AggSum>::new(2);
+    let (a, b) = (l.create(1), l.create(2));
+    l.set_size_unchecked(a, 100);
+    l.concat(a, b);
+}
+
+#[test]
+#[cfg(feature = "debug-hooks")]
+fn test_try_concat_checked_overflow() {
+    let mut l = Treaps::<AggSum>::new(4);
+    let (a, b) = (l.create(1), l.create(2));
+    let c = l.create(3);
+    // Pretend a is a huge list
+    l.set_size_unchecked(a, usize::MAX);
+    assert_eq!(
+        l.try_concat_checked(a, b),
+        Err(LengthOverflow {
+            left: usize::MAX,
+            right: 1
+        })
+    );
+    assert!(!l.on_same_list(a, b));
+    l.set_size_unchecked(a, 1);
+    let u = l.try_concat_checked(a, b).unwrap();
+    assert_eq!(l.len(u), 2);
+    assert_eq!(l.try_concat_checked(c, Treaps::<AggSum>::EMPTY), Ok(c));
+}
+
+#[test]
+#[cfg(all(feature = "debug-hooks", debug_assertions))]
+#[should_panic(expected = "Treap invariant violated: size of")]
+fn test_treap_detects_size_overflow() {
+    let mut l = Treaps::<AggSum>::new(2);
+    let (a, b) = (l.create(1), l.create(2));
+    let root = l.concat(a, b);
+    let child = if root == a { b } else { a };
+    l.set_size_unchecked(child, usize::MAX);
+    // Recalculates only the root, which adds up the sizes of its children
+    l.mutate_data(root, |d| *d = 3);
+}
+
+fn assert_invariants_after_random_ops<L: Lists<AggSum> + std::fmt::Debug>(
+    invariants: impl Fn(&L) -> InvariantReport,
+) {
+    let mut rng = StdRng::seed_from_u64(682);
+    let l = &mut L::from_iter(0..50);
+    for _ in 0..500 {
+        let (u, v) = (rng.gen_range(0..50), rng.gen_range(0..50));
+        match rng.gen_range(0..3) {
+            0 => _ = l.concat(u, v),
+            1 => _ = l.split_after(u),
+            _ => l.reverse(u),
+        }
+        assert_eq!(invariants(l), InvariantReport::default(), "{l:?}");
+    }
+}
+
+#[test]
+fn test_invariants_report_healthy() {
+    init_logger();
+    assert_invariants_after_random_ops::<Treaps<AggSum>>(Treaps::invariants);
+    assert_invariants_after_random_ops::<Splays<AggSum>>(Splays::invariants);
+    // Built directly, without concats
+    let mut l = Treaps::<AggSum>::new(0);
+    l.build_balanced(0..1000);
+    assert!(l.invariants().is_ok());
+}
+
+#[test]
+#[cfg(feature = "debug-hooks")]
+fn test_invariants_report_corruption() {
+    init_logger();
+    let mut l = Treaps::<AggSum>::new(2);
+    let (a, b) = (l.create(1), l.create(2));
+    let root = l.concat(a, b);
+    let child = if root == a { b } else { a };
+    l.set_size_unchecked(child, 100);
+    // Sizes are checked against the actual sizes of the children, so only the corrupted node is reported
+    assert_eq!(
+        l.invariants().violations,
+        [Violation::WrongSize {
+            node: child,
+            stored: 100,
+            actual: 1
+        }]
+    );
+    // 1 23 has a subtree 23 below the first node, a wrong flip in it makes the aggregate 132
+    let mut l = Splays::<AggDigit>::from_iter([1, 2, 3]);
+    assert_eq!(l.root(2), 0);
+    let mut corrupted = 0;
+    for u in [1, 2] {
+        l.toggle_flip_unchecked(u);
+        let violations = l.invariants().violations;
+        // A flip on a leaf doesn't change anything
+        if !violations.is_empty() {
+            assert_eq!(violations, [Violation::LazyFlip { node: u }]);
+            corrupted += 1;
+        }
+        l.toggle_flip_unchecked(u);
+    }
+    assert_eq!(corrupted, 1);
+}
@@ -0,0 +1,70 @@
+use dynamic_2core::{
+    generators::random_graph,
+    replay::{replay, Op, ReplayLog},
+    Dynamic2CoreSolver, FastDynamic2CoreSolver,
+};
+
+/// Adds a random graph, removes half of it and adds it back, recording everything.
+fn record(n: usize, seed: u64) -> ReplayLog {
+    let mut solver = FastDynamic2CoreSolver::new(n);
+    let mut log = ReplayLog::new(n);
+    let edges = random_graph(n, 2 * n, seed);
+    for &(u, v) in &edges {
+        assert!(log.apply(&mut solver, Op::AddEdge(u, v)));
+    }
+    // Repeated operations fail, which is also recorded
+    log.apply(&mut solver, Op::AddEdge(edges[0].1, edges[0].0));
+    for &(u, v) in edges.iter().step_by(2) {
+        log.apply(&mut solver, Op::RemoveEdge(u, v));
+    }
+    log.apply(&mut solver, Op::RemoveEdge(edges[0].0, edges[0].1));
+    for &(u, v) in edges.iter().step_by(2) {
+        log.apply(&mut solver, Op::AddEdge(u, v));
+    }
+    log
+}
+
+#[test]
+fn test_replay_roundtrip() {
+    let log = record(30, 644);
+    assert_eq!(log.steps().len(), 60 + 1 + 30 + 1 + 30);
+    assert_eq!(log.steps().iter().filter(|(_, result)| !result).count(), 2);
+    let mut text = vec![];
+    log.write_to(&mut text).unwrap();
+    let read = ReplayLog::read_from(&text[..]).unwrap();
+    assert_eq!(read, log);
+    replay(&read, &mut FastDynamic2CoreSolver::new(read.n()));
+    // Through a file as well
+    let path = std::env::temp_dir().join("d2c_test_replay_roundtrip.txt");
+    log.save(&path).unwrap();
+    assert_eq!(ReplayLog::load(&path).unwrap(), log);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "step 2: add 1 0")]
+fn test_replay_detects_mismatch() {
+    let mut log = ReplayLog::new(3);
+    log.record(Op::AddEdge(0, 1), true);
+    log.record(Op::AddEdge(1, 2), true);
+    log.record(Op::AddEdge(1, 0), true);
+    replay(&log, &mut FastDynamic2CoreSolver::new(3));
+}
+
+#[test]
+fn test_replay_invalid_text() {
+    for text in [
+        "",
+        "n x",
+        "n 3\nadd 0 1",
+        "n 3\nadd 0 true 1",
+        "n 3\nlink 0 1 true",
+    ] {
+        assert!(ReplayLog::read_from(text.as_bytes()).is_err(), "{text:?}");
+    }
+    let log = ReplayLog::read_from("n 3\nadd 0 1 true\nremove 1 0 true\n".as_bytes()).unwrap();
+    assert_eq!(
+        log.steps(),
+        [(Op::AddEdge(0, 1), true), (Op::RemoveEdge(1, 0), true)]
+    );
+}